@@ -0,0 +1,148 @@
+// Benchmarks for the stages of the pipeline that run for every sample or every hop: the
+// detectors at several window sizes, the smoothing stack and the routing of CC lanes, then the
+// whole engine. Each one processes a second of audio (or its hops), so the throughputs compare
+// directly to real time.
+//
+// Run them with `cargo bench`, and compare against a baseline with
+// `cargo bench -- --save-baseline before` then `cargo bench -- --baseline before`.
+
+use std::f32::consts::PI;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use aeolus::analysis::{
+    AnalysisQuality, AnalysisSpeed, Analyzer, AnalyzerConfig, DetectorMethod, PitchMethod, TrackingRange,
+};
+use aeolus::engine::Engine;
+use aeolus::events::AeolusEvent;
+use aeolus::mapping::MappingCurve;
+use aeolus::routing::{CcLane, LaneState};
+use aeolus::smoothing::PitchSmoother;
+
+const SAMPLE_RATE: f32 = 48000.0;
+/// The hop size the smoothing and routing benches pretend to follow
+const HOP_SIZE: usize = 64;
+
+/// A second of A3 with some vibrato, which keeps the detectors and smoothers busy
+fn input() -> Vec<f32> {
+    let mut phase = 0.0;
+    (0..SAMPLE_RATE as usize)
+        .map(|index| {
+            let time = index as f32 / SAMPLE_RATE;
+            let frequency = 220.0 * (1.0 + 0.01 * (2.0 * PI * 5.0 * time).sin());
+            phase += 2.0 * PI * frequency / SAMPLE_RATE;
+            0.5 * phase.sin()
+        })
+        .collect()
+}
+
+/// The MIDI pitch at each hop of `input()`
+fn pitches() -> Vec<f32> {
+    (0..SAMPLE_RATE as usize / HOP_SIZE)
+        .map(|hop| {
+            let time = (hop * HOP_SIZE) as f32 / SAMPLE_RATE;
+            57.0 + 0.17 * (2.0 * PI * 5.0 * time).sin()
+        })
+        .collect()
+}
+
+fn detectors(c: &mut Criterion) {
+    let input = input();
+    let mut group = c.benchmark_group("detectors");
+    group.throughput(Throughput::Elements(input.len() as u64));
+    let base = TrackingRange::Normal.analyzer_config(AnalysisQuality::Realtime, AnalysisSpeed::Balanced);
+    for method in [DetectorMethod::Yinfast, DetectorMethod::Yin, DetectorMethod::Yinfft] {
+        for buffer_size in [512, 1024, 2048, 4096] {
+            let config = AnalyzerConfig { method, buffer_size, hop_size: buffer_size / 16, ..base };
+            let mut analyzer = Analyzer::new(config);
+            analyzer.set_host_rate(SAMPLE_RATE);
+            group.bench_with_input(BenchmarkId::new(format!("{method:?}"), buffer_size), &input, |b, input| {
+                b.iter(|| {
+                    for &sample in input {
+                        black_box(analyzer.push(sample));
+                    }
+                })
+            });
+        }
+    }
+    // The alternatives to YIN, which take over from aubio's detector once picked
+    for method in [PitchMethod::Hps, PitchMethod::Swipe, PitchMethod::Cepstrum] {
+        for buffer_size in [512, 1024, 2048, 4096] {
+            let config = AnalyzerConfig { buffer_size, hop_size: buffer_size / 16, ..base };
+            let mut analyzer = Analyzer::new(config);
+            analyzer.set_host_rate(SAMPLE_RATE);
+            analyzer.set_method(method);
+            group.bench_with_input(BenchmarkId::new(format!("{method:?}"), buffer_size), &input, |b, input| {
+                b.iter(|| {
+                    for &sample in input {
+                        black_box(analyzer.push(sample));
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn smoothing(c: &mut Criterion) {
+    let pitches = pitches();
+    let hop_ms = HOP_SIZE as f32 / SAMPLE_RATE * 1000.0;
+    let mut group = c.benchmark_group("smoothing");
+    group.throughput(Throughput::Elements(pitches.len() as u64));
+    for time_ms in [0.0, 5.0, 50.0, 200.0] {
+        let mut smoother = PitchSmoother::default();
+        group.bench_with_input(BenchmarkId::from_parameter(time_ms), &pitches, |b, pitches| {
+            b.iter(|| {
+                for &pitch in pitches {
+                    black_box(smoother.process(pitch, hop_ms, time_ms));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn routing(c: &mut Criterion) {
+    let values: Vec<f32> = pitches().iter().map(|pitch| (pitch - 45.0) / 24.0).collect();
+    let hop_ms = HOP_SIZE as f32 / SAMPLE_RATE * 1000.0;
+    let mut group = c.benchmark_group("routing");
+    group.throughput(Throughput::Elements(values.len() as u64));
+    for curve in [MappingCurve::Linear, MappingCurve::Exponential, MappingCurve::Logarithmic, MappingCurve::SCurve] {
+        let lane = CcLane { enabled: true, number: 1, smoothing: 20.0, rate_limit: 100.0, curve, ..CcLane::default() };
+        let mut state = LaneState::default();
+        let mut sink: Vec<AeolusEvent> = Vec::with_capacity(values.len());
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{curve:?}")), &values, |b, values| {
+            b.iter(|| {
+                sink.clear();
+                for &value in values {
+                    lane.send_smoothed(&mut state, &mut sink, 0, value, hop_ms);
+                }
+                black_box(&sink);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn engine(c: &mut Criterion) {
+    let input = input();
+    let mut group = c.benchmark_group("engine");
+    group.throughput(Throughput::Elements(input.len() as u64));
+    group.sample_size(20);
+    for quality in [AnalysisQuality::Realtime, AnalysisQuality::Offline] {
+        let mut engine = Engine::new(SAMPLE_RATE, quality);
+        let mut sink: Vec<AeolusEvent> = Vec::with_capacity(input.len());
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{quality:?}")), &input, |b, input| {
+            b.iter(|| {
+                sink.clear();
+                for (index, &sample) in input.iter().enumerate() {
+                    black_box(engine.process_sample(&mut sink, index as u32, sample));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, detectors, smoothing, routing, engine);
+criterion_main!(benches);
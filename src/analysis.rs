@@ -0,0 +1,839 @@
+#[cfg(feature = "aubio")]
+use aubio::{Pitch, PitchMode};
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+use crate::cepstrum::CepstrumDetector;
+use crate::hps::HpsDetector;
+use crate::refinement::refine_frequency;
+use crate::resampling::*;
+use crate::subharmonics::suppress_subharmonic;
+use crate::swipe::SwipeDetector;
+#[cfg(not(feature = "aubio"))]
+use crate::yin::YinDetector;
+
+/// Which register the analysis is tuned for. Lower registers need longer analysis windows,
+/// which come with more latency.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum TrackingRange {
+    #[cfg_attr(feature = "plugin", name = "Normal")]
+    Normal,
+    /// Reliable tracking below ~80 Hz (bass guitar, low male vocals)
+    #[cfg_attr(feature = "plugin", name = "Low")]
+    Low,
+    /// Whistling and piccolo-register sources: less latency, and fewer subharmonic errors
+    #[cfg_attr(feature = "plugin", name = "High")]
+    High,
+    /// Follows the register currently being played, switching between the other ranges
+    #[cfg_attr(feature = "plugin", name = "Adaptive")]
+    Adaptive,
+}
+
+/// The ranges that have an analyzer of their own, as opposed to `TrackingRange::Adaptive`
+const FIXED_RANGES: [TrackingRange; 3] = [TrackingRange::Normal, TrackingRange::Low, TrackingRange::High];
+
+/// How long the adaptive mode takes to go from one range's analyzer to another
+const CROSSFADE_MS: f32 = 50.0;
+
+fn fixed_index(range: TrackingRange) -> usize {
+    FIXED_RANGES.iter().position(|fixed| *fixed == range).unwrap_or(0)
+}
+
+/// How much CPU the analysis may use
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AnalysisQuality {
+    Realtime,
+    /// For offline bounces, where there is no deadline to meet: bigger windows, a finer hop,
+    /// and several detectors whose estimates are combined, so that exported MIDI is cleaner than
+    /// the live preview
+    Offline,
+}
+
+/// The window sizes selectable through the "Fast/Accurate" macro parameter
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AnalysisSpeed {
+    /// Half the usual window and hop
+    Fast,
+    Balanced,
+    /// Twice the usual window
+    Accurate,
+}
+
+const SPEEDS: [AnalysisSpeed; 3] = [AnalysisSpeed::Fast, AnalysisSpeed::Balanced, AnalysisSpeed::Accurate];
+
+fn speed_index(speed: AnalysisSpeed) -> usize {
+    SPEEDS.iter().position(|known| *known == speed).unwrap_or(1)
+}
+
+impl AnalysisSpeed {
+    /// `value` goes from 0 (fast) to 1 (accurate)
+    pub fn from_macro(value: f32) -> Self {
+        if value < 1.0 / 3.0 {
+            AnalysisSpeed::Fast
+        } else if value < 2.0 / 3.0 {
+            AnalysisSpeed::Balanced
+        } else {
+            AnalysisSpeed::Accurate
+        }
+    }
+
+    pub fn to_index(self) -> usize {
+        speed_index(self)
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        SPEEDS.get(index).copied().unwrap_or(AnalysisSpeed::Balanced)
+    }
+}
+
+/// Which detection algorithm an analyzer uses. Those are aubio's, so builds without aubio use
+/// our own YIN implementation for all of them.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DetectorMethod {
+    Yinfast,
+    Yin,
+    Yinfft,
+}
+
+#[cfg(feature = "aubio")]
+impl DetectorMethod {
+    fn aubio_mode(self) -> PitchMode {
+        match self {
+            DetectorMethod::Yinfast => PitchMode::Yinfast,
+            DetectorMethod::Yin     => PitchMode::Yin,
+            DetectorMethod::Yinfft  => PitchMode::Yinfft,
+        }
+    }
+}
+
+/// The detection method users pick. YIN, in the variant each tracking range is tuned with, is
+/// the default, the others are there for the material it struggles with. Only YIN is combined
+/// with the other YIN variants in offline ensembles.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum PitchMethod {
+    #[cfg_attr(feature = "plugin", name = "YIN")]
+    Yin,
+    /// The harmonic product spectrum, for strongly harmonic sources where YIN keeps picking a
+    /// harmonic
+    #[cfg_attr(feature = "plugin", name = "Harmonic product spectrum")]
+    Hps,
+    /// SWIPE', slower but more accurate on voice
+    #[cfg_attr(feature = "plugin", name = "SWIPE'")]
+    Swipe,
+    /// The cepstrum, for band-limited and noisy sources
+    #[cfg_attr(feature = "plugin", name = "Cepstrum")]
+    Cepstrum,
+}
+
+/// The methods that aren't YIN, which every analyzer has a detector for
+const ALTERNATIVE_METHODS: [PitchMethod; 3] = [PitchMethod::Hps, PitchMethod::Swipe, PitchMethod::Cepstrum];
+
+/// The detectors combined in ensemble mode, in addition to the configured one
+const ENSEMBLE_METHODS: [DetectorMethod; 2] = [DetectorMethod::Yin, DetectorMethod::Yinfft];
+const MAX_ENSEMBLE_SIZE: usize = ENSEMBLE_METHODS.len() + 1;
+
+/// Everything needed to build a pitch detector
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AnalyzerConfig {
+    pub method:      DetectorMethod,
+    /// Applied to the input before it reaches the detector. The sizes below are in samples at
+    /// the resulting analysis rate.
+    pub resampling:  Resampling,
+    /// Length of the analysis window, in samples
+    pub buffer_size: usize,
+    /// How many new samples are needed for each new analysis
+    pub hop_size:    usize,
+    /// Input quieter than this (in dB) is considered silent
+    pub silence_db:  f32,
+    /// Detected frequencies outside of this range (in Hz) are considered unvoiced
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    /// Whether to run `ENSEMBLE_METHODS` next to `method`, and take the median of their estimates
+    pub ensemble:    bool,
+}
+
+/// Converts a number of samples at `ANALYSIS_RATE` into a number of samples at the host rate
+fn to_host_rate(samples: usize, host_rate: f32) -> usize {
+    (samples as f32 * host_rate / ANALYSIS_RATE).round() as usize
+}
+
+impl AnalyzerConfig {
+    /// The window length, in samples at the host rate
+    pub fn host_buffer_size(&self, host_rate: f32) -> usize {
+        to_host_rate(self.resampling.to_host_samples(self.buffer_size), host_rate)
+    }
+
+    /// The hop size, in samples at the host rate
+    pub fn host_hop_size(&self, host_rate: f32) -> usize {
+        to_host_rate(self.resampling.to_host_samples(self.hop_size), host_rate)
+    }
+}
+
+/// The longest window (in samples at the host rate) any range and speed uses, which bounds the
+/// latency
+pub fn max_host_buffer_size(quality: AnalysisQuality, host_rate: f32) -> usize {
+    FIXED_RANGES
+        .iter()
+        .flat_map(|range| SPEEDS.iter().map(move |speed| range.analyzer_config(quality, *speed)))
+        .map(|config| config.host_buffer_size(host_rate))
+        .max()
+        .unwrap_or(0)
+}
+
+impl TrackingRange {
+    pub fn analyzer_config(self, quality: AnalysisQuality, speed: AnalysisSpeed) -> AnalyzerConfig {
+        let balanced = self.realtime_config();
+        let realtime = match speed {
+            AnalysisSpeed::Fast => AnalyzerConfig {
+                buffer_size: balanced.buffer_size / 2,
+                hop_size: balanced.hop_size / 2,
+                ..balanced
+            },
+            AnalysisSpeed::Balanced => balanced,
+            AnalysisSpeed::Accurate => AnalyzerConfig {
+                buffer_size: 2 * balanced.buffer_size,
+                ..balanced
+            },
+        };
+        match quality {
+            AnalysisQuality::Realtime => realtime,
+            AnalysisQuality::Offline => AnalyzerConfig {
+                buffer_size: 2 * realtime.buffer_size,
+                hop_size: realtime.hop_size / 2,
+                ensemble: true,
+                ..realtime
+            },
+        }
+    }
+
+    fn realtime_config(self) -> AnalyzerConfig {
+        match self {
+            // The adaptive mode's latency changes all the time, which hosts don't like, so we
+            // report the normal range's latency instead
+            TrackingRange::Normal | TrackingRange::Adaptive => AnalyzerConfig {
+                method: DetectorMethod::Yinfast,
+                resampling: Resampling::None,
+                buffer_size: 1024,
+                hop_size: 64,
+                silence_db: -50.0, // aubio's default
+                min_frequency: 0.0,
+                max_frequency: f32::INFINITY,
+                ensemble: false,
+            },
+            // Decimating gives us a long window for cheap: this is 4096 samples' worth of audio
+            TrackingRange::Low => AnalyzerConfig {
+                method: DetectorMethod::Yinfast,
+                resampling: Resampling::Decimate(2),
+                buffer_size: 2048,
+                hop_size: 128,
+                silence_db: -50.0,
+                min_frequency: 0.0,
+                max_frequency: f32::INFINITY,
+                ensemble: false,
+            },
+            // Oversampling makes up for the short periods of those notes, that only span a few
+            // samples at the host rate: this is 512 samples' worth of audio. Those sources are
+            // loud and there's a lot of breath noise around them, hence the higher silence
+            // threshold.
+            TrackingRange::High => AnalyzerConfig {
+                method: DetectorMethod::Yinfast,
+                resampling: Resampling::Oversample(2),
+                buffer_size: 1024,
+                hop_size: 64,
+                silence_db: -40.0,
+                min_frequency: 500.0,
+                max_frequency: 5000.0,
+                ensemble: false,
+            },
+        }
+    }
+}
+
+/// Result of analyzing one hop's worth of audio
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    /// In Hz, zero meaning that the input is unvoiced (or silent)
+    pub frequency:  f32,
+    pub confidence: f32,
+}
+
+/// One hop as the stages after detection see it: the detection, along with the input level at
+/// the time, and whether the gates let it through as voiced. That way, each stage has what it
+/// needs without going back to the trackers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PitchFrame {
+    /// In Hz, meaningless when unvoiced
+    pub frequency:  f32,
+    pub confidence: f32,
+    /// The input envelope, as a linear gain
+    pub level:      f32,
+    /// Whether a pitch was detected, and no gate closed on it
+    pub voiced:     bool,
+}
+
+impl PitchFrame {
+    pub fn new(detection: Detection, level: f32) -> Self {
+        Self {
+            frequency: detection.frequency,
+            confidence: detection.confidence,
+            level,
+            // aubio reports unvoiced (or silent) input as a zero frequency. A detection without any
+            // confidence is only YIN's fallback of the lowest dip, which on the first windows of a
+            // note lands near Nyquist: the confidence-weighted smoothing would hold on to it.
+            voiced: detection.frequency > 0.0 && detection.confidence > 0.0,
+        }
+    }
+
+    /// The same frame, with a gate closed on it
+    pub fn gated(self) -> Self {
+        Self { voiced: false, ..self }
+    }
+
+    pub fn level_db(&self) -> f32 {
+        20.0 * self.level.max(1e-9).log10()
+    }
+}
+
+/// `aubio::Pitch` is not `Send`, because it contains a raw pointer (see [1]). The `Plugin` trait
+/// requires our plugin to be `Send` though, since the host may create it on one thread and run
+/// it on another. Moving an aubio object to another thread is fine (see [2]), as long as it is
+/// never used from two threads at once, since aubio keeps no thread-local state. This wrapper
+/// guarantees that much: it is not `Sync` or `Clone`, and it is only ever owned by an
+/// `Analyzer`, which itself is only owned by one `AnalyzerBank`, living either in the plugin or
+/// on the worker thread. So instead of implementing `Send` for the whole plugin (which is what
+/// we used to do), the unsafe part is restricted to this one pointer, whose use we can actually
+/// reason about.
+/// [1] https://github.com/katyo/aubio-rs/blob/4697a1424f6e856ffbe91045a794529d4ecde8a8/src/pitch.rs#L210
+/// [2] https://doc.rust-lang.org/nomicon/send-and-sync.html
+#[cfg(feature = "aubio")]
+struct AubioPitch(Pitch);
+
+// SAFETY: see above. `AubioPitch` owns its `aubio_pitch_t` exclusively: the pointer is created
+// with it, freed when it's dropped, and never copied out or aliased, so there's no other handle
+// to the object left behind on the thread it's moved from. And since it's never shared, only
+// moved, only one thread can access the object at any time.
+#[cfg(feature = "aubio")]
+unsafe impl Send for AubioPitch {}
+
+/// A pitch detector. Detectors are fed a whole window at a time rather than one hop, so that
+/// they don't depend on having seen every hop, which is what lets the analysis skip hops.
+enum Detector {
+    #[cfg(feature = "aubio")]
+    Aubio(AubioPitch),
+    #[cfg(not(feature = "aubio"))]
+    Yin(YinDetector),
+    Hps(HpsDetector),
+    Swipe(SwipeDetector),
+    Cepstrum(CepstrumDetector),
+}
+
+impl Detector {
+    /// Returns `None` if the detector couldn't be created
+    fn new(method: DetectorMethod, config: &AnalyzerConfig, sample_rate: f32) -> Option<Self> {
+        #[cfg(feature = "aubio")]
+        {
+            // With a hop as long as the window, aubio's own buffer is just the window we pass in
+            Pitch::new(method.aubio_mode(), config.buffer_size, config.buffer_size, sample_rate as u32)
+                .ok()
+                .map(|pitch| Detector::Aubio(AubioPitch(pitch.with_silence(config.silence_db))))
+        }
+        #[cfg(not(feature = "aubio"))]
+        {
+            let _ = method;
+            Some(Detector::Yin(YinDetector::new(config.buffer_size, sample_rate, config.silence_db)))
+        }
+    }
+
+    /// The detector for one of `ALTERNATIVE_METHODS`, `None` for YIN
+    fn alternative(method: PitchMethod, config: &AnalyzerConfig, sample_rate: f32) -> Option<Self> {
+        match method {
+            PitchMethod::Yin      => None,
+            PitchMethod::Hps      => Some(Detector::Hps(HpsDetector::new(config.buffer_size, sample_rate, config.silence_db))),
+            PitchMethod::Swipe    => Some(Detector::Swipe(SwipeDetector::new(
+                config.buffer_size,
+                sample_rate,
+                config.silence_db,
+                config.min_frequency,
+                config.max_frequency,
+            ))),
+            PitchMethod::Cepstrum => Some(Detector::Cepstrum(CepstrumDetector::new(
+                config.buffer_size,
+                sample_rate,
+                config.silence_db,
+                config.min_frequency,
+                config.max_frequency,
+            ))),
+        }
+    }
+
+    /// Frequency (zero meaning unvoiced) and confidence, or `None` if detection failed
+    fn detect(&mut self, window: &[f32]) -> Option<(f32, f32)> {
+        match self {
+            #[cfg(feature = "aubio")]
+            Detector::Aubio(pitch) => {
+                let frequency = pitch.0.do_result(window).ok()?;
+                Some((frequency, pitch.0.get_confidence()))
+            }
+            #[cfg(not(feature = "aubio"))]
+            Detector::Yin(yin) => {
+                let frequency = yin.detect(window);
+                Some((frequency, yin.confidence()))
+            }
+            Detector::Hps(hps) => {
+                let frequency = hps.detect(window);
+                Some((frequency, hps.confidence()))
+            }
+            Detector::Swipe(swipe) => {
+                let frequency = swipe.detect(window);
+                Some((frequency, swipe.confidence()))
+            }
+            Detector::Cepstrum(cepstrum) => {
+                let frequency = cepstrum.detect(window);
+                Some((frequency, cepstrum.confidence()))
+            }
+        }
+    }
+}
+
+/// A pitch detector (or several), together with the samples that are waiting to be analyzed
+pub struct Analyzer {
+    config:        AnalyzerConfig,
+    /// The analysis sample rate, after resampling
+    sample_rate:   f32,
+    /// From the host's rate to `ANALYSIS_RATE`
+    converter:     RateConverter,
+    /// From `ANALYSIS_RATE` to the analysis rate
+    resampler:     Resampler,
+    /// The configured detector, followed by the ensemble ones (if any). Detectors that aubio
+    /// failed to create are left out.
+    detectors:     Vec<Detector>,
+    /// One detector for each of `ALTERNATIVE_METHODS`, used instead of the above when their
+    /// method is picked
+    alternatives:  Vec<(PitchMethod, Detector)>,
+    method:        PitchMethod,
+    /// How many samples of the current hop were pushed so far
+    pending:       usize,
+    /// The last `buffer_size` samples, as a ring buffer
+    history:       Vec<f32>,
+    history_index: usize,
+    /// `history` in chronological order, for the detectors and the refinement stage. This is only
+    /// a scratch buffer, allocated beforehand so that analysis doesn't allocate.
+    window:        Vec<f32>,
+    /// Whether coarse detections get refined
+    refine:        bool,
+    /// Whether detections an octave below the pitch get corrected
+    undertones:    bool,
+    /// Only one hop in this many is analyzed
+    decimation:    usize,
+    /// Hops completed since the last analyzed one
+    skipped:       usize,
+}
+
+impl Analyzer {
+    /// This allocates, so it should not be called on the audio thread. The analyzer expects
+    /// input at `ANALYSIS_RATE` until told otherwise with `set_host_rate()`.
+    pub fn new(config: AnalyzerConfig) -> Self {
+        let analysis_rate = ANALYSIS_RATE * config.resampling.rate_factor();
+        Self {
+            config,
+            sample_rate: analysis_rate,
+            converter: RateConverter::new(ANALYSIS_RATE, ANALYSIS_RATE),
+            resampler: Resampler::new(config.resampling, ANALYSIS_RATE),
+            detectors: std::iter::once(config.method)
+                .chain(ENSEMBLE_METHODS.into_iter().filter(|method| config.ensemble && *method != config.method))
+                .filter_map(|method| Detector::new(method, &config, analysis_rate))
+                .collect(),
+            alternatives: ALTERNATIVE_METHODS
+                .into_iter()
+                .filter_map(|method| Some((method, Detector::alternative(method, &config, analysis_rate)?)))
+                .collect(),
+            method: PitchMethod::Yin,
+            pending: 0,
+            history: vec![0.0; config.buffer_size],
+            history_index: 0,
+            window: vec![0.0; config.buffer_size],
+            refine: false,
+            undertones: false,
+            decimation: 1,
+            skipped: 0,
+        }
+    }
+
+    pub fn set_refinement(&mut self, refine: bool) {
+        self.refine = refine;
+    }
+
+    pub fn set_subharmonic_suppression(&mut self, suppress: bool) {
+        self.undertones = suppress;
+    }
+
+    pub fn set_decimation(&mut self, decimation: usize) {
+        self.decimation = decimation.max(1);
+    }
+
+    /// The detectors get the whole window each time, so switching between them is seamless
+    pub fn set_method(&mut self, method: PitchMethod) {
+        self.method = method;
+    }
+
+    /// This does not allocate
+    pub fn set_host_rate(&mut self, host_rate: f32) {
+        self.converter = RateConverter::new(host_rate, ANALYSIS_RATE);
+    }
+
+    pub fn config(&self) -> AnalyzerConfig {
+        self.config
+    }
+
+    /// Drops the pending samples. It does not seem to be possible to reset the state of an
+    /// `aubio::Pitch`, so the analysis window itself still contains old audio. It shouldn't make
+    /// a difference once the supposedly small time that it takes to play in a buffer's worth of
+    /// audio has elapsed.
+    pub fn reset(&mut self) {
+        self.pending = 0;
+        self.skipped = 0;
+        self.converter.reset();
+        self.resampler.reset();
+    }
+
+    /// Adds a sample (at the host rate) to the pending audio, and performs pitch analysis (if
+    /// possible) once a full hop is pending
+    pub fn push(&mut self, sample: f32) -> Option<Detection> {
+        let mut converted = [0.0; MAX_RATE_CONVERSION];
+        let mut resampled = [0.0; MAX_OVERSAMPLING];
+        let mut detection = None;
+        let converted_count = self.converter.process(sample, &mut converted);
+        for &sample in &converted[..converted_count] {
+            let resampled_count = self.resampler.process(sample, &mut resampled);
+            for &sample in &resampled[..resampled_count] {
+                if let Some(result) = self.push_resampled(sample) {
+                    detection = Some(result);
+                }
+            }
+        }
+        detection
+    }
+
+    /// Same as `push()`, but for a sample at the analysis rate
+    fn push_resampled(&mut self, sample: f32) -> Option<Detection> {
+        self.history[self.history_index] = sample;
+        self.history_index = (self.history_index + 1) % self.history.len();
+        self.pending += 1;
+        if self.pending < self.config.hop_size {
+            return None;
+        }
+        self.pending = 0;
+        self.skipped += 1;
+        if self.skipped < self.decimation {
+            return None;
+        }
+        self.skipped = 0;
+
+        // The oldest sample is the one that's going to be overwritten next
+        let (newest, oldest) = self.history.split_at(self.history_index);
+        self.window[..oldest.len()].copy_from_slice(oldest);
+        self.window[oldest.len()..].copy_from_slice(newest);
+
+        // With a single detector, this is just its estimate. With an ensemble, the input is
+        // considered voiced if most detectors say so, and the median estimate is used.
+        let mut estimates = [0.0; MAX_ENSEMBLE_SIZE];
+        let mut voiced = 0;
+        let mut answered = 0;
+        let mut confidence = 0.0;
+        let method = self.method;
+        let detectors = match self.alternatives.iter_mut().find(|(own, _)| *own == method) {
+            Some((_, detector)) => std::slice::from_mut(detector),
+            None                => self.detectors.as_mut_slice(),
+        };
+        for detector in detectors {
+            if let Some((frequency, detector_confidence)) = detector.detect(&self.window) {
+                answered += 1;
+                confidence += detector_confidence;
+                if frequency > 0.0 {
+                    estimates[voiced] = frequency;
+                    voiced += 1;
+                }
+            }
+        }
+        if answered == 0 {
+            return None; // pitch analyzer not available, or no pitch found
+        }
+        let confidence = confidence / answered as f32;
+        let frequency = if 2 * voiced > answered {
+            let estimates = &mut estimates[..voiced];
+            estimates.sort_unstable_by(f32::total_cmp);
+            estimates[voiced / 2]
+        } else {
+            0.0
+        };
+
+        // Before the range check, which the corrected estimate may be outside of
+        let frequency = if self.undertones {
+            suppress_subharmonic(&self.window, self.sample_rate, frequency)
+        } else {
+            frequency
+        };
+        let frequency = if (self.config.min_frequency..=self.config.max_frequency).contains(&frequency) {
+            frequency
+        } else {
+            0.0
+        };
+        let frequency = if self.refine && frequency > 0.0 {
+            refine_frequency(&self.window, self.sample_rate, frequency)
+        } else {
+            frequency
+        };
+        Some(Detection {
+            frequency,
+            confidence,
+        })
+    }
+}
+
+/// How many analyzers a bank holds, one per fixed range and speed
+const SLOT_COUNT: usize = SPEEDS.len() * FIXED_RANGES.len();
+
+/// One analyzer per fixed tracking range and speed, all built beforehand so that switching
+/// ranges or speeds doesn't allocate on the audio thread.
+///
+/// The analyzers that aren't in use don't get fed, so their windows hold stale audio. When the
+/// range or speed changes, the analyzers for the new settings are thus fed alongside the ones in
+/// use until their windows are full of current audio, and only then take over. This makes for
+/// a window's worth of lag in following the parameter, rather than a dropout followed by a hop
+/// or two of garbage. Replaying recent audio into them instead would be quicker, but would cost
+/// a burst of detections within a single block.
+pub struct AnalyzerBank {
+    /// Indexed by `slot()`
+    analyzers:         Vec<Analyzer>,
+    /// Each analyzer's most recent detection, indexed by `slot()`
+    latest:            Vec<Option<Detection>>,
+    /// The range the adaptive mode currently follows
+    active:            TrackingRange,
+    /// The range the adaptive mode is crossfading from, if any
+    previous:          Option<TrackingRange>,
+    /// Samples elapsed since the adaptive mode last switched ranges
+    fade_elapsed:      usize,
+    crossfade_samples: usize,
+    /// The range and speed whose analyzers drive the output, once something was pushed
+    in_use:            Option<(TrackingRange, AnalysisSpeed)>,
+    /// The range and speed being switched to, and how many samples their analyzers got so far
+    warming:           Option<((TrackingRange, AnalysisSpeed), usize)>,
+    /// Only one hop in this many is analyzed
+    decimation:        usize,
+    host_rate:         f32,
+    quality:           AnalysisQuality,
+}
+
+fn slot(range: TrackingRange, speed: AnalysisSpeed) -> usize {
+    speed_index(speed) * FIXED_RANGES.len() + fixed_index(range)
+}
+
+/// The analyzers (as a bit mask of their slots) that `range` and `speed` need
+fn slots(range: TrackingRange, speed: AnalysisSpeed) -> u16 {
+    if range == TrackingRange::Adaptive {
+        FIXED_RANGES.iter().fold(0, |mask, fixed| mask | (1 << slot(*fixed, speed)))
+    } else {
+        1 << slot(range, speed)
+    }
+}
+
+/// An empty bank, which never detects anything, to be replaced in `Plugin::initialize()`
+impl Default for AnalyzerBank {
+    fn default() -> Self {
+        Self {
+            analyzers: Vec::new(),
+            latest: Vec::new(),
+            active: TrackingRange::Normal,
+            previous: None,
+            fade_elapsed: 0,
+            crossfade_samples: 0,
+            in_use: None,
+            warming: None,
+            decimation: 1,
+            host_rate: ANALYSIS_RATE,
+            quality: AnalysisQuality::Realtime,
+        }
+    }
+}
+
+impl AnalyzerBank {
+    /// This allocates, so it should not be called on the audio thread
+    pub fn new(quality: AnalysisQuality) -> Self {
+        let mut bank = Self {
+            analyzers: SPEEDS
+                .iter()
+                .flat_map(|speed| FIXED_RANGES.iter().map(move |range| (*range, *speed)))
+                .map(|(range, speed)| Analyzer::new(range.analyzer_config(quality, speed)))
+                .collect(),
+            latest: vec![None; SLOT_COUNT],
+            quality,
+            ..Self::default()
+        };
+        bank.set_host_rate(ANALYSIS_RATE);
+        bank
+    }
+
+    pub fn quality(&self) -> AnalysisQuality {
+        self.quality
+    }
+
+    /// Since all analyzers run at `ANALYSIS_RATE`, a change of the host's sample rate only needs
+    /// their input converters to be reconfigured
+    pub fn set_host_rate(&mut self, host_rate: f32) {
+        for analyzer in &mut self.analyzers {
+            analyzer.set_host_rate(host_rate);
+        }
+        self.host_rate = host_rate;
+        self.crossfade_samples = (CROSSFADE_MS / 1000.0 * host_rate) as usize;
+    }
+
+    pub fn set_refinement(&mut self, refine: bool) {
+        for analyzer in &mut self.analyzers {
+            analyzer.set_refinement(refine);
+        }
+    }
+
+    pub fn set_subharmonic_suppression(&mut self, suppress: bool) {
+        for analyzer in &mut self.analyzers {
+            analyzer.set_subharmonic_suppression(suppress);
+        }
+    }
+
+    pub fn set_method(&mut self, method: PitchMethod) {
+        for analyzer in &mut self.analyzers {
+            analyzer.set_method(method);
+        }
+    }
+
+    /// Makes the analyzers only analyze one hop in `decimation`, for when CPU is tight. The hop
+    /// sizes reported along with detections are multiplied accordingly.
+    pub fn set_decimation(&mut self, decimation: usize) {
+        if decimation != self.decimation {
+            for analyzer in &mut self.analyzers {
+                analyzer.set_decimation(decimation);
+            }
+            self.decimation = decimation.max(1);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for analyzer in &mut self.analyzers {
+            analyzer.reset();
+        }
+        for latest in &mut self.latest {
+            *latest = None;
+        }
+        self.active = TrackingRange::Normal;
+        self.previous = None;
+        self.in_use = None;
+        self.warming = None;
+    }
+
+    /// Feeds a sample to the analyzer(s) needed for `range` and `speed`. Whenever the analyzer
+    /// driving the output completes a hop, its detection is returned together with its hop size
+    /// (in samples at the host rate).
+    pub fn push(
+        &mut self,
+        sample: f32,
+        range:  TrackingRange,
+        speed:  AnalysisSpeed,
+    ) -> Option<(Detection, usize)> {
+        if self.analyzers.is_empty() {
+            return None;
+        }
+        let requested = (range, speed);
+        let (range, speed) = *self.in_use.get_or_insert(requested);
+        if requested == (range, speed) {
+            self.warming = None;
+        } else if self.warming.map(|(warming, _)| warming) != Some(requested) {
+            self.warming = Some((requested, 0));
+        }
+        // In adaptive mode, every analyzer at the current speed is kept warm so that we can
+        // switch ranges at any time
+        let in_use = slots(range, speed);
+        let warm = self.warming.map_or(0, |((range, speed), _)| slots(range, speed) & !in_use);
+
+        let mut detections = [None; SLOT_COUNT];
+        for (index, analyzer) in self.analyzers.iter_mut().enumerate() {
+            if (in_use | warm) & (1 << index) != 0 {
+                detections[index] = analyzer.push(sample);
+                if detections[index].is_some() {
+                    self.latest[index] = detections[index];
+                }
+            }
+        }
+        self.fade_elapsed = self.fade_elapsed.saturating_add(1);
+
+        if let Some((requested, fed)) = &mut self.warming {
+            *fed += 1;
+            let window = (0..SLOT_COUNT)
+                .filter(|index| warm & (1 << index) != 0)
+                .map(|index| self.analyzers[index].config().host_buffer_size(self.host_rate))
+                .max()
+                .unwrap_or(0);
+            if *fed >= window {
+                self.in_use = Some(*requested);
+                self.warming = None;
+            }
+        }
+
+        let index = slot(if range == TrackingRange::Adaptive { self.active } else { range }, speed);
+        let detection = detections[index]?;
+        let hop_size = self.decimation * self.analyzers[index].config().host_hop_size(self.host_rate);
+        if range != TrackingRange::Adaptive {
+            return Some((detection, hop_size));
+        }
+        let output = self.crossfade(detection, speed);
+        self.adapt(detection, speed);
+        Some((output, hop_size))
+    }
+
+    /// Blends the active analyzer's detection with the previous one's while a switch is going on
+    fn crossfade(&self, detection: Detection, speed: AnalysisSpeed) -> Detection {
+        let previous = match self.previous.and_then(|range| self.latest[slot(range, speed)]) {
+            Some(previous) => previous,
+            None           => return detection,
+        };
+        if self.fade_elapsed >= self.crossfade_samples
+            || previous.frequency <= 0.0
+            || detection.frequency <= 0.0
+        {
+            return detection;
+        }
+        // Pitch is perceived logarithmically, so we crossfade in the log domain
+        let t = self.fade_elapsed as f32 / self.crossfade_samples as f32;
+        Detection {
+            frequency: (previous.frequency.log2() * (1.0 - t) + detection.frequency.log2() * t).exp2(),
+            confidence: previous.confidence * (1.0 - t) + detection.confidence * t,
+        }
+    }
+
+    /// Picks the range to follow from now on. The thresholds overlap to avoid switching back and
+    /// forth around a boundary.
+    fn adapt(&mut self, detection: Detection, speed: AnalysisSpeed) {
+        // The high range rejects lower notes, so when it doesn't find anything we rely on the
+        // normal range's analyzer, which doesn't reject anything
+        let frequency = if detection.frequency > 0.0 {
+            detection.frequency
+        } else {
+            match self.latest[slot(TrackingRange::Normal, speed)] {
+                Some(normal) if normal.frequency > 0.0 => normal.frequency,
+                _ => return,
+            }
+        };
+        let target = match self.active {
+            TrackingRange::Low  if frequency < 100.0 => TrackingRange::Low,
+            TrackingRange::High if frequency > 500.0 => TrackingRange::High,
+            _ if frequency < 80.0  => TrackingRange::Low,
+            _ if frequency > 700.0 => TrackingRange::High,
+            _ => TrackingRange::Normal,
+        };
+        if target != self.active {
+            self.previous = Some(self.active);
+            self.active = target;
+            self.fade_elapsed = 0;
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::analysis::PitchFrame;
+use crate::engine::EngineSettings;
+use crate::events::EventSink;
+use crate::outputs::OutputState;
+
+/// How many events the queue holds before the oldest ones are dropped. The engine dispatches
+/// after every sample (and every hop), which publishes a handful at most.
+const QUEUE_CAPACITY: usize = 16;
+
+/// A voiced hop, as the pitch stage made it out: the detector's frame, and what smoothing,
+/// correction and quantization turned it into. Pitches are fractional MIDI notes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PitchEstimate {
+    pub frame:     PitchFrame,
+    /// The smoothed pitch, without the vibrato if it's split off
+    pub pitch:     f32,
+    /// `pitch`, partially corrected towards the quantized note
+    pub corrected: f32,
+    /// The note of the scale `pitch` was quantized to, after hysteresis and hold
+    pub quantized: f32,
+    /// `corrected`, with the bends' own (usually shorter) smoothing time, and the vibrato
+    pub bend:      f32,
+    /// In milliseconds, the length of the hop
+    pub hop_ms:    f32,
+    /// How much richer than a sine the input is at `pitch`, while the spectral balance is
+    /// followed (1 otherwise)
+    pub richness:  f32,
+    /// The first two formants (in Hz), while they're followed and could be found
+    pub formants:  Option<(f32, f32)>,
+    /// What the velocity source makes of the hop, for the notes starting on it
+    pub velocity:  f32,
+    /// Practice mode: how far (in cents) `pitch` is from the target, if there is one
+    pub deviation: Option<f32>,
+}
+
+/// What the analysis stages publish, for the output generators to turn into MIDI. Timings are
+/// the same as for `AeolusEvent`s.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AnalysisEvent {
+    /// A hop was analyzed, voiced or not, before anything else it publishes. For the outputs
+    /// that count time in hops.
+    Hop          { timing: u32, hop_size: usize },
+    /// A voiced hop
+    PitchChanged { timing: u32, estimate: PitchEstimate },
+    /// The input became voiced, right before the first `PitchChanged` after silence
+    Voiced       { timing: u32 },
+    /// The input stayed unvoiced for longer than the bridge time
+    Unvoiced     { timing: u32 },
+    /// A transient, while percussive mode looks for them. The velocity (between 0 and 1) follows
+    /// its level.
+    Onset        { timing: u32, velocity: f32 },
+    /// A beat, while the tempo is tracked. The strength is between 0 and 1.
+    Beat         { timing: u32, strength: f32, bpm: Option<f32> },
+}
+
+impl AnalysisEvent {
+    pub fn timing(&self) -> u32 {
+        match *self {
+            AnalysisEvent::Hop { timing, .. }
+            | AnalysisEvent::PitchChanged { timing, .. }
+            | AnalysisEvent::Voiced { timing }
+            | AnalysisEvent::Unvoiced { timing }
+            | AnalysisEvent::Onset { timing, .. }
+            | AnalysisEvent::Beat { timing, .. } => timing,
+        }
+    }
+}
+
+/// A first-in first-out queue of analysis events, with a fixed capacity so that publishing never
+/// allocates
+pub struct EventQueue {
+    events: [Option<AnalysisEvent>; QUEUE_CAPACITY],
+    /// Where the oldest event is
+    start:  usize,
+    len:    usize,
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self { events: [None; QUEUE_CAPACITY], start: 0, len: 0 }
+    }
+}
+
+impl EventQueue {
+    /// Queues `event`, dropping the oldest one if the queue is full
+    pub fn publish(&mut self, event: AnalysisEvent) {
+        if self.len == QUEUE_CAPACITY {
+            self.pop();
+        }
+        self.events[(self.start + self.len) % QUEUE_CAPACITY] = Some(event);
+        self.len += 1;
+    }
+
+    /// Takes the oldest event out of the queue
+    pub fn pop(&mut self) -> Option<AnalysisEvent> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.start].take();
+        self.start = (self.start + 1) % QUEUE_CAPACITY;
+        self.len -= 1;
+        event
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// What the engine gives the output generators along with the events
+pub struct OutputContext<'a> {
+    pub settings:    &'a EngineSettings,
+    pub sample_rate: f32,
+    /// What the outputs share with each other and with the engine, such as the sounding note
+    pub state:       &'a mut OutputState,
+}
+
+impl<'a> OutputContext<'a> {
+    pub fn new(settings: &'a EngineSettings, sample_rate: f32, state: &'a mut OutputState) -> Self {
+        Self { settings, sample_rate, state }
+    }
+
+    /// A duration in milliseconds, in samples
+    pub fn samples(&self, ms: f32) -> usize {
+        (ms / 1000.0 * self.sample_rate) as usize
+    }
+}
+
+/// Turns analysis events into MIDI. The engine's own outputs (the CC lanes, the note segmenter
+/// and the bends and expressions of MPE and guitar mode, see `outputs.rs`) are generators too,
+/// which run first; the ones added with `Engine::add_generator()` get the same events after
+/// them, so that a new output mode doesn't have to touch the pipeline.
+///
+/// Generators run on the audio thread: they shouldn't allocate or block.
+pub trait OutputGenerator: Send {
+    fn handle(&mut self, sink: &mut dyn EventSink, context: &mut OutputContext, event: &AnalysisEvent);
+
+    /// Closes whatever the generator left sounding. Called by `Engine::release()`, and thus by
+    /// `Engine::panic()`.
+    fn release(&mut self, _sink: &mut dyn EventSink, _context: &mut OutputContext, _timing: u32) {}
+
+    /// Forgets everything, without sending anything. Called by `Engine::reset()`.
+    fn reset(&mut self) {}
+}
@@ -0,0 +1,223 @@
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::analysis::TrackingRange;
+use crate::key::Key;
+use crate::scale::{RootNote, ScaleType};
+use crate::mapping::Range;
+use crate::utils::freq_to_midi;
+
+/// Margin (in semitones) left around the played range, so that its ends aren't clipped
+const RANGE_MARGIN: f32 = 2.0;
+/// The level (in dB) the sustained note should have after the input gain
+const TARGET_LEVEL_DB: f32 = -18.0;
+/// Pitch wobble (in cents) at which the fast/accurate macro is turned all the way up
+const MAX_WOBBLE_CENTS: f32 = 30.0;
+/// Enough for several seconds at the editor's frame rate
+const SUSTAINED_CAPACITY: usize = 1024;
+/// Stored instead of a key while none was detected
+const NO_KEY: u8 = u8::MAX;
+
+/// The last detection, and how often they come, passed from the audio thread to the editor
+pub struct LiveReading {
+    /// In Hz, NaN if the input is unvoiced
+    frequency:   AtomicF32,
+    /// As a linear gain, after the input gain
+    level:       AtomicF32,
+    /// Detections per second
+    update_rate: AtomicF32,
+    /// The key auto-key mode follows, as its root's pitch class, plus 12 for minor keys
+    key:         AtomicU8,
+}
+
+impl Default for LiveReading {
+    fn default() -> Self {
+        Self {
+            frequency: AtomicF32::new(f32::NAN),
+            level: AtomicF32::new(0.0),
+            update_rate: AtomicF32::new(0.0),
+            key: AtomicU8::new(NO_KEY),
+        }
+    }
+}
+
+impl LiveReading {
+    pub fn update(&self, frequency: f32, level: f32) {
+        let frequency = if frequency > 0.0 { frequency } else { f32::NAN };
+        self.frequency.store(frequency, Ordering::Relaxed);
+        self.level.store(level, Ordering::Relaxed);
+    }
+
+    pub fn frequency(&self) -> Option<f32> {
+        Some(self.frequency.load(Ordering::Relaxed)).filter(|frequency| !frequency.is_nan())
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level.load(Ordering::Relaxed)
+    }
+
+    pub fn set_update_rate(&self, update_rate: f32) {
+        self.update_rate.store(update_rate, Ordering::Relaxed);
+    }
+
+    pub fn update_rate(&self) -> f32 {
+        self.update_rate.load(Ordering::Relaxed)
+    }
+
+    pub fn set_key(&self, key: Option<Key>) {
+        let key = key.map_or(NO_KEY, |key| {
+            key.root.pitch_class() + if key.scale == ScaleType::Major { 0 } else { 12 }
+        });
+        self.key.store(key, Ordering::Relaxed);
+    }
+
+    pub fn key(&self) -> Option<Key> {
+        match self.key.load(Ordering::Relaxed) {
+            NO_KEY => None,
+            key => Some(Key {
+                root: RootNote::ALL[key as usize % 12],
+                scale: if key < 12 { ScaleType::Major } else { ScaleType::NaturalMinor },
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CalibrationStep {
+    Lowest,
+    Highest,
+    Sustained,
+}
+
+impl CalibrationStep {
+    /// What the user is asked to do
+    pub fn instructions(self) -> &'static str {
+        match self {
+            CalibrationStep::Lowest    => "Play your lowest note",
+            CalibrationStep::Highest   => "Play your highest note",
+            CalibrationStep::Sustained => "Play a sustained note, at your usual loudness",
+        }
+    }
+}
+
+/// Settings derived from what was played during calibration
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CalibrationResult {
+    /// In MIDI notes, margin included
+    pub min_note:       f32,
+    pub max_note:       f32,
+    pub tracking_range: TrackingRange,
+    /// How much smoothing the wobble of the sustained note calls for
+    pub speed_accuracy: f32,
+    /// What to add to the input gain for the sustained note to reach the target level. Since the
+    /// analyzers' silence threshold is fixed, this is also what moves the gate.
+    pub gain_change_db: f32,
+}
+
+/// The calibration wizard's state, kept by the editor and fed the live reading every frame
+#[derive(Default)]
+pub struct Calibration {
+    step:              Option<CalibrationStep>,
+    /// In MIDI notes
+    lowest:            Option<f32>,
+    highest:           Option<f32>,
+    /// Pitches (in MIDI notes) and levels (in dB) seen during the sustained note
+    sustained_pitches: Vec<f32>,
+    sustained_levels:  Vec<f32>,
+}
+
+impl Calibration {
+    /// The current step, or `None` if the wizard isn't running
+    pub fn step(&self) -> Option<CalibrationStep> {
+        self.step
+    }
+
+    pub fn start(&mut self) {
+        *self = Self {
+            step: Some(CalibrationStep::Lowest),
+            sustained_pitches: Vec::with_capacity(SUSTAINED_CAPACITY),
+            sustained_levels: Vec::with_capacity(SUSTAINED_CAPACITY),
+            ..Self::default()
+        };
+    }
+
+    pub fn cancel(&mut self) {
+        self.step = None;
+    }
+
+    pub fn observe(&mut self, reading: &LiveReading) {
+        let Some(frequency) = reading.frequency() else {
+            return;
+        };
+        let pitch = freq_to_midi(frequency);
+        match self.step {
+            Some(CalibrationStep::Lowest) => {
+                self.lowest = Some(self.lowest.map_or(pitch, |lowest| lowest.min(pitch)));
+            }
+            Some(CalibrationStep::Highest) => {
+                self.highest = Some(self.highest.map_or(pitch, |highest| highest.max(pitch)));
+            }
+            Some(CalibrationStep::Sustained) if self.sustained_pitches.len() < SUSTAINED_CAPACITY => {
+                self.sustained_pitches.push(pitch);
+                self.sustained_levels.push(20.0 * reading.level().max(1e-9).log10());
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether the current step heard something, so that the next one can come
+    pub fn can_advance(&self) -> bool {
+        match self.step {
+            Some(CalibrationStep::Lowest)    => self.lowest.is_some(),
+            Some(CalibrationStep::Highest)   => self.highest.is_some(),
+            Some(CalibrationStep::Sustained) => !self.sustained_pitches.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Goes to the next step. After the last one, the wizard stops and returns its result.
+    pub fn advance(&mut self) -> Option<CalibrationResult> {
+        self.step = match self.step {
+            Some(CalibrationStep::Lowest)  => Some(CalibrationStep::Highest),
+            Some(CalibrationStep::Highest) => Some(CalibrationStep::Sustained),
+            Some(CalibrationStep::Sustained) | None => None,
+        };
+        if self.step.is_none() {
+            self.result()
+        } else {
+            None
+        }
+    }
+
+    fn result(&self) -> Option<CalibrationResult> {
+        let (lowest, highest) = (self.lowest?, self.highest?);
+        let (lowest, highest) = (lowest.min(highest), lowest.max(highest));
+        let count = self.sustained_pitches.len() as f32;
+        if count == 0.0 {
+            return None;
+        }
+
+        // The wobble is measured around the mean, so that a slightly flat note doesn't count
+        let mean_pitch = self.sustained_pitches.iter().sum::<f32>() / count;
+        let variance = self.sustained_pitches.iter()
+            .map(|pitch| (100.0 * (pitch - mean_pitch)).powi(2))
+            .sum::<f32>() / count;
+        let mean_level = self.sustained_levels.iter().sum::<f32>() / count;
+
+        // Same bounds as the low and high analyzers are tuned for
+        let tracking_range = match (lowest < 40.0, highest > 84.0) {
+            (true, true)   => TrackingRange::Adaptive,
+            (true, false)  => TrackingRange::Low,
+            (false, _) if lowest > 72.0 => TrackingRange::High,
+            (false, _)     => TrackingRange::Normal,
+        };
+
+        Some(CalibrationResult {
+            min_note: Range::new(0.0, 127.0).clamp(lowest - RANGE_MARGIN),
+            max_note: Range::new(0.0, 127.0).clamp(highest + RANGE_MARGIN),
+            tracking_range,
+            speed_accuracy: Range::new(0.0, MAX_WOBBLE_CENTS).clamped_position(variance.sqrt()),
+            gain_change_db: TARGET_LEVEL_DB - mean_level,
+        })
+    }
+}
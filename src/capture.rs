@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::events::{AeolusEvent, EventSink};
+use crate::smf::{write_smf, TimedEvent};
+
+/// How many events are kept. Older ones are dropped to make room for new ones, so with a single
+/// CC lane this is several minutes' worth.
+const CAPTURE_CAPACITY: usize = 1 << 18;
+/// Events the audio thread keeps aside when the capture is locked by the GUI
+const PENDING_CAPACITY: usize = 4096;
+const DEFAULT_TEMPO: f64 = 120.0;
+
+/// A note of the capture, with its bounds in seconds since the first captured event
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CapturedNote {
+    pub note:  u8,
+    pub start: f64,
+    /// Notes that are still sounding end with the last captured event
+    pub end:   f64,
+}
+
+/// The last events Aeolus generated, whether the host recorded them or not
+pub struct Capture {
+    events:      VecDeque<TimedEvent>,
+    sample_rate: f32,
+    /// The host's tempo (in BPM) when the last events were captured
+    tempo:       f64,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            events: VecDeque::with_capacity(CAPTURE_CAPACITY),
+            sample_rate: 44100.0,
+            tempo: DEFAULT_TEMPO,
+        }
+    }
+}
+
+impl Capture {
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Doesn't allocate, so this can be called from the audio thread
+    fn push(&mut self, event: TimedEvent) {
+        if self.events.len() == CAPTURE_CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn events(&self) -> Vec<TimedEvent> {
+        self.events.iter().copied().collect()
+    }
+
+    /// The captured notes, in the order they started
+    pub fn notes(&self) -> Vec<CapturedNote> {
+        let Some(first) = self.events.front() else {
+            return Vec::new();
+        };
+        let seconds = |position: u64| position.saturating_sub(first.position) as f64 / self.sample_rate as f64;
+        let mut notes = Vec::new();
+        // Indices in `notes`, by channel and note, of the notes that are sounding
+        let mut sounding = vec![None; 16 * 128];
+        for TimedEvent { position, event } in &self.events {
+            match *event {
+                AeolusEvent::NoteOn { channel, note, .. } => {
+                    sounding[16 * note as usize + channel as usize] = Some(notes.len());
+                    notes.push(CapturedNote { note, start: seconds(*position), end: f64::NAN });
+                }
+                AeolusEvent::NoteOff { channel, note, .. } => {
+                    if let Some(index) = sounding[16 * note as usize + channel as usize].take() {
+                        notes[index].end = seconds(*position);
+                    }
+                }
+                _ => (),
+            }
+        }
+        let last = self.events.back().map_or(0.0, |event| seconds(event.position));
+        for note in &mut notes {
+            if note.end.is_nan() {
+                note.end = last;
+            }
+        }
+        notes
+    }
+
+    /// The captured events, as the bytes of a Standard MIDI File
+    pub fn to_smf(&self) -> Vec<u8> {
+        let events = self.events();
+        write_smf(&events, self.sample_rate, self.tempo)
+    }
+}
+
+/// The audio thread's side of the capture
+pub struct Recorder {
+    capture: Arc<Mutex<Capture>>,
+    /// Events that couldn't be added to the capture yet, because it was locked
+    pending: Vec<TimedEvent>,
+}
+
+impl Recorder {
+    pub fn new(capture: Arc<Mutex<Capture>>) -> Self {
+        Self { capture, pending: Vec::with_capacity(PENDING_CAPACITY) }
+    }
+
+    /// Records `events`, never blocking. If the capture is locked (the GUI is exporting it),
+    /// events are kept aside until the next call, or dropped if there are too many of them.
+    pub fn record(&mut self, events: &[TimedEvent], tempo: Option<f64>) {
+        match self.capture.try_lock() {
+            Ok(mut capture) => {
+                for event in self.pending.drain(..).chain(events.iter().copied()) {
+                    capture.push(event);
+                }
+                if let Some(tempo) = tempo {
+                    capture.tempo = tempo;
+                }
+            }
+            Err(_) => {
+                let room = PENDING_CAPACITY - self.pending.len();
+                self.pending.extend(events.iter().take(room).copied());
+            }
+        }
+    }
+}
+
+/// Forwards events to another sink, and keeps them with their absolute position for the recorder
+pub struct RecordingSink<'a, S: EventSink> {
+    inner:    &'a mut S,
+    /// Absolute position of the start of the block, in samples
+    position: u64,
+    events:   &'a mut Vec<TimedEvent>,
+}
+
+impl<'a, S: EventSink> RecordingSink<'a, S> {
+    /// `events` is expected to have enough capacity for a whole block's worth of events
+    pub fn new(inner: &'a mut S, position: u64, events: &'a mut Vec<TimedEvent>) -> Self {
+        Self { inner, position, events }
+    }
+}
+
+impl<S: EventSink> EventSink for RecordingSink<'_, S> {
+    fn send(&mut self, event: AeolusEvent) {
+        if self.events.len() < self.events.capacity() {
+            self.events.push(TimedEvent { position: self.position + event.timing() as u64, event });
+        }
+        self.inner.send(event);
+    }
+}
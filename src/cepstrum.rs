@@ -0,0 +1,95 @@
+use crate::fft::Fft;
+use crate::spectrum::{is_silent, peak_offset, MagnitudeSpectrum};
+
+/// The padded FFT is this many times longer than the window, so that the cepstrum doesn't wrap
+/// around within the lags looked at
+const PADDING:           usize = 2;
+/// The shortest period looked for, in samples, below which the cepstrum is the spectral
+/// envelope rather than the harmonics' spacing (about 2200 Hz at the analysis rate)
+const MIN_PERIOD:        usize = 20;
+/// The magnitudes are floored this far (in dB) below the loudest bin before taking their log,
+/// so that the empty bands of band-limited sources don't swamp the harmonics
+const FLOOR_DB:          f32 = -80.0;
+/// How far above the cepstrum's RMS its peak roughly is for white noise, and how much further
+/// it has to go for full confidence
+const NOISE_PROMINENCE:  f32 = 4.0;
+const PROMINENCE_RANGE:  f32 = 8.0;
+/// Candidates with less confidence than this are taken for noise
+const VOICING_THRESHOLD: f32 = 0.3;
+
+/// The real cepstrum, the spectrum of the log magnitude spectrum: the harmonics' regular
+/// spacing makes a peak at the period. It only takes the harmonics that are there, not the
+/// fundamental itself, nor a clean waveform, which makes it hold up better than the time-domain
+/// methods on band-limited and noisy sources such as telephone recordings or lo-fi samples.
+///
+/// See A. M. Noll, "Cepstrum pitch determination", 1967.
+pub struct CepstrumDetector {
+    spectrum:    MagnitudeSpectrum,
+    inverse:     Fft,
+    /// Holds the log spectrum, then the cepstrum
+    real:        Vec<f32>,
+    imaginary:   Vec<f32>,
+    /// In samples, the range of periods looked for
+    min_period:  usize,
+    max_period:  usize,
+    sample_rate: f32,
+    silence_db:  f32,
+    confidence:  f32,
+}
+
+impl CepstrumDetector {
+    /// Periods are looked for between those of `max_frequency` and `min_frequency`, as far as
+    /// the window allows. This allocates.
+    pub fn new(buffer_size: usize, sample_rate: f32, silence_db: f32, min_frequency: f32, max_frequency: f32) -> Self {
+        let fft_size = (buffer_size * PADDING).next_power_of_two();
+        // At least two periods in the window, like YIN
+        let longest = buffer_size / 2;
+        Self {
+            spectrum: MagnitudeSpectrum::new(buffer_size, fft_size),
+            inverse: Fft::new(fft_size),
+            real: vec![0.0; fft_size],
+            imaginary: vec![0.0; fft_size],
+            min_period: ((sample_rate / max_frequency).ceil() as usize).max(MIN_PERIOD),
+            max_period: ((sample_rate / min_frequency.max(f32::EPSILON)) as usize).min(longest),
+            sample_rate,
+            silence_db,
+            confidence: 0.0,
+        }
+    }
+
+    /// Between 0 and 1, for the last detection
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Returns the frequency (in Hz) detected in `window`, zero meaning unvoiced or silent
+    pub fn detect(&mut self, window: &[f32]) -> f32 {
+        self.confidence = 0.0;
+        if self.min_period + 1 >= self.max_period || is_silent(window, self.silence_db) {
+            return 0.0;
+        }
+        let magnitudes = self.spectrum.analyze(window);
+        let loudest = magnitudes.iter().copied().fold(0.0, f32::max);
+        let floor = (loudest * 10f32.powf(FLOOR_DB / 20.0)).max(f32::MIN_POSITIVE);
+        // The spectrum of a real signal is symmetric, the inverse FFT needs both halves
+        let size = self.real.len();
+        for (bin, magnitude) in magnitudes.iter().enumerate() {
+            let log = magnitude.max(floor).ln();
+            self.real[bin] = log;
+            self.real[(size - bin) % size] = log;
+        }
+        self.imaginary.fill(0.0);
+        self.inverse.inverse(&mut self.real, &mut self.imaginary);
+
+        let cepstrum = &self.real[self.min_period..=self.max_period];
+        let peak = (1..cepstrum.len() - 1).max_by(|a, b| cepstrum[*a].total_cmp(&cepstrum[*b])).unwrap_or(1);
+        let rms = (cepstrum.iter().map(|value| value * value).sum::<f32>() / cepstrum.len() as f32).sqrt();
+        let prominence = if rms > 0.0 { cepstrum[peak] / rms } else { 0.0 };
+        self.confidence = ((prominence - NOISE_PROMINENCE) / PROMINENCE_RANGE).clamp(0.0, 1.0);
+        if self.confidence < VOICING_THRESHOLD {
+            return 0.0;
+        }
+        let offset = peak_offset(cepstrum[peak - 1], cepstrum[peak], cepstrum[peak + 1]);
+        self.sample_rate / ((self.min_period + peak) as f32 + offset)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::events::{AeolusEvent, EventSink, RealtimeMessage};
+use crate::tempo::Beat;
+
+/// MIDI clock's resolution
+const TICKS_PER_BEAT: usize = 24;
+/// The clock starts once beats are this strong
+const START_STRENGTH: f32 = 0.2;
+/// The clock stops when no beat was heard for this long
+const STOP_AFTER_MS:  f32 = 2000.0;
+
+/// Generates MIDI clock at the detected tempo. Ticks run freely between beats, and each beat
+/// restarts them, so that the receiver stays in phase with the performer. Ticks missed when
+/// a beat comes early are dropped rather than sent in a burst.
+#[derive(Default)]
+pub struct MidiClock {
+    running:    bool,
+    /// Samples until the next tick
+    countdown:  f32,
+    /// Samples since the last beat
+    since_beat: f32,
+}
+
+impl MidiClock {
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Sends Stop if the clock was running
+    pub fn stop(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if self.running {
+            sink.send(AeolusEvent::Realtime { timing, message: RealtimeMessage::Stop });
+        }
+        *self = Self::default();
+    }
+
+    /// Advances the clock by one sample, with the beat this sample revealed (if any) and the
+    /// current tempo estimate (in BPM)
+    pub fn process(
+        &mut self,
+        sink:        &mut impl EventSink,
+        timing:      u32,
+        beat:        Option<Beat>,
+        bpm:         Option<f32>,
+        sample_rate: f32,
+    ) {
+        let Some(bpm) = bpm else {
+            return;
+        };
+        let interval = 60.0 / bpm * sample_rate / TICKS_PER_BEAT as f32;
+        self.since_beat += 1.0;
+
+        if let Some(Beat { delay, strength }) = beat {
+            if self.running || strength >= START_STRENGTH {
+                // The beat happened `delay` samples ago, which is as close as we can get to it
+                let timing = timing.saturating_sub(delay as u32);
+                if !self.running {
+                    sink.send(AeolusEvent::Realtime { timing, message: RealtimeMessage::Start });
+                    self.running = true;
+                }
+                sink.send(AeolusEvent::Realtime { timing, message: RealtimeMessage::Clock });
+                self.countdown = interval - delay as f32;
+                self.since_beat = delay as f32;
+                return;
+            }
+        }
+        if !self.running {
+            return;
+        }
+        if self.since_beat / sample_rate * 1000.0 > STOP_AFTER_MS {
+            self.stop(sink, timing);
+            return;
+        }
+
+        self.countdown -= 1.0;
+        // Missing beats don't stop the ticks, which keep going at the last tempo
+        if self.countdown <= 0.0 {
+            sink.send(AeolusEvent::Realtime { timing, message: RealtimeMessage::Clock });
+            self.countdown += interval;
+        }
+    }
+}
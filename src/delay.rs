@@ -0,0 +1,101 @@
+/// How long a change of delay takes to crossfade from the old delay to the new one
+const CROSSFADE_MS: f32 = 20.0;
+
+/// A fixed-capacity delay line, for delaying the audio we pass through by the analysis latency
+pub struct DelayLine {
+    buffer:   Vec<f32>,
+    /// Where the next sample goes
+    position: usize,
+}
+
+impl DelayLine {
+    /// This allocates, so it should not be called on the audio thread
+    pub fn new(max_delay: usize) -> Self {
+        Self { buffer: vec![0.0; max_delay + 1], position: 0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.position = 0;
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        self.buffer[self.position] = sample;
+        self.position = (self.position + 1) % self.buffer.len();
+    }
+
+    /// The sample pushed `delay` pushes ago (capped to the capacity), 0 being the last one
+    pub fn read(&self, delay: usize) -> f32 {
+        let length = self.buffer.len();
+        self.buffer[(self.position + length - 1 - delay.min(length - 1)) % length]
+    }
+}
+
+/// Delays each channel of the output by the same amount. When the delay changes (because the
+/// latency did, or the delay was switched on or off), the old and new delays are crossfaded
+/// rather than jumped between, which would click.
+pub struct OutputDelay {
+    lines:        Vec<DelayLine>,
+    /// The current delay, once a block went through
+    delay:        Option<usize>,
+    /// The delay being faded out, and how many samples the fade has been going for
+    fading:       Option<(usize, usize)>,
+    fade_samples: usize,
+}
+
+/// An empty delay, which passes audio through, to be replaced in `Plugin::initialize()`
+impl Default for OutputDelay {
+    fn default() -> Self {
+        Self { lines: Vec::new(), delay: None, fading: None, fade_samples: 1 }
+    }
+}
+
+impl OutputDelay {
+    /// This allocates, so it should not be called on the audio thread
+    pub fn new(channels: usize, max_delay: usize, sample_rate: f32) -> Self {
+        Self {
+            lines: (0..channels).map(|_| DelayLine::new(max_delay)).collect(),
+            fade_samples: ((CROSSFADE_MS / 1000.0 * sample_rate) as usize).max(1),
+            ..Self::default()
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for line in &mut self.lines {
+            line.reset();
+        }
+        self.delay = None;
+        self.fading = None;
+    }
+
+    /// Delays `channels` (one slice per channel, all of the same length) by `delay` samples.
+    /// Channels without a line of their own are left as they are.
+    pub fn process(&mut self, channels: &mut [&mut [f32]], delay: usize) {
+        let current = *self.delay.get_or_insert(delay);
+        if delay != current {
+            self.fading = Some((current, 0));
+            self.delay = Some(delay);
+        }
+        let mut elapsed = 0;
+        for (line, channel) in self.lines.iter_mut().zip(channels.iter_mut()) {
+            elapsed = self.fading.map_or(0, |(_, elapsed)| elapsed);
+            for sample in channel.iter_mut() {
+                line.push(*sample);
+                *sample = match self.fading {
+                    Some((previous, _)) if elapsed < self.fade_samples => {
+                        let mix = elapsed as f32 / self.fade_samples as f32;
+                        mix * line.read(delay) + (1.0 - mix) * line.read(previous)
+                    }
+                    _ => line.read(delay),
+                };
+                elapsed += 1;
+            }
+        }
+        if let Some((_, fade_elapsed)) = &mut self.fading {
+            *fade_elapsed = elapsed;
+            if elapsed >= self.fade_samples {
+                self.fading = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::f32::consts::PI;
+
+use crate::fft::Fft;
+
+/// About 21 ms at 48 kHz: long enough to resolve hum harmonics, short enough not to smear notes
+const FRAME_MS:         f32 = 21.0;
+/// How much of the noise estimate is subtracted. A little more than all of it gets rid of the
+/// noise's fluctuations too.
+const OVER_SUBTRACTION: f32 = 1.5;
+/// Bins are never attenuated further than this, which keeps "musical noise" down
+const SPECTRAL_FLOOR:   f32 = 0.05;
+/// How much each learned frame moves the noise profile
+const LEARNING_RATE:    f32 = 0.1;
+
+/// Spectral subtraction: frames where there's nothing to track teach the denoiser what the
+/// background noise looks like, and that profile is then subtracted from every frame. This
+/// runs on the analysis path only, so the artifacts it leaves are never heard.
+///
+/// Frames overlap by half, with square-root Hann windows for analysis and synthesis, which adds
+/// up to a perfect reconstruction. This delays the signal by one frame.
+pub struct Denoiser {
+    fft:          Fft,
+    window:       Vec<f32>,
+    /// The last frame's worth of input, as a ring buffer
+    input:        Vec<f32>,
+    input_index:  usize,
+    /// Overlap-added output, as a ring buffer
+    output:       Vec<f32>,
+    output_index: usize,
+    /// Samples since the last frame was processed
+    hop_count:    usize,
+    real:         Vec<f32>,
+    imaginary:    Vec<f32>,
+    /// Magnitude of the noise in each bin
+    noise:        Vec<f32>,
+    learned:      bool,
+}
+
+impl Denoiser {
+    /// This allocates
+    pub fn new(sample_rate: f32) -> Self {
+        let size = ((FRAME_MS / 1000.0 * sample_rate) as usize).next_power_of_two().max(64);
+        Self {
+            fft: Fft::new(size),
+            window: (0..size)
+                .map(|index| (0.5 - 0.5 * (2.0 * PI * index as f32 / size as f32).cos()).sqrt())
+                .collect(),
+            input: vec![0.0; size],
+            input_index: 0,
+            output: vec![0.0; size],
+            output_index: 0,
+            hop_count: 0,
+            real: vec![0.0; size],
+            imaginary: vec![0.0; size],
+            noise: vec![0.0; size / 2 + 1],
+            learned: false,
+        }
+    }
+
+    /// In samples
+    pub fn latency(&self) -> u32 {
+        self.fft.size() as u32
+    }
+
+    /// Forgets the signal, but not the noise profile
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+        self.input_index = 0;
+        self.output_index = 0;
+        self.hop_count = 0;
+    }
+
+    /// Forgets the noise profile too
+    pub fn forget_noise(&mut self) {
+        self.noise.fill(0.0);
+        self.learned = false;
+    }
+
+    /// Denoises one sample. With `learn`, the frames that complete are added to the noise profile
+    /// (and pass through untouched).
+    pub fn process(&mut self, sample: f32, learn: bool) -> f32 {
+        let size = self.fft.size();
+        self.input[self.input_index] = sample;
+        self.input_index = (self.input_index + 1) % size;
+
+        let output = std::mem::take(&mut self.output[self.output_index]);
+        self.output_index = (self.output_index + 1) % size;
+
+        self.hop_count += 1;
+        if self.hop_count == size / 2 {
+            self.hop_count = 0;
+            self.process_frame(learn);
+        }
+        output
+    }
+
+    fn process_frame(&mut self, learn: bool) {
+        let size = self.fft.size();
+        for (index, (real, imaginary)) in self.real.iter_mut().zip(self.imaginary.iter_mut()).enumerate() {
+            *real = self.window[index] * self.input[(self.input_index + index) % size];
+            *imaginary = 0.0;
+        }
+        self.fft.forward(&mut self.real, &mut self.imaginary);
+
+        for (bin, noise) in self.noise.iter_mut().enumerate() {
+            let magnitude = self.real[bin].hypot(self.imaginary[bin]);
+            if learn {
+                *noise = if self.learned { *noise + LEARNING_RATE * (magnitude - *noise) } else { magnitude };
+                continue;
+            }
+            let gain = if magnitude > 0.0 {
+                (1.0 - OVER_SUBTRACTION * *noise / magnitude).max(SPECTRAL_FLOOR)
+            } else {
+                SPECTRAL_FLOOR
+            };
+            // The negative frequencies mirror the positive ones
+            let mirror = (size - bin) % size;
+            self.real[bin] *= gain;
+            self.imaginary[bin] *= gain;
+            if mirror != bin {
+                self.real[mirror] *= gain;
+                self.imaginary[mirror] *= gain;
+            }
+        }
+        if learn {
+            self.learned = true;
+        }
+
+        // The frame is added starting with the next sample to be read, hence the latency
+        self.fft.inverse(&mut self.real, &mut self.imaginary);
+        for (index, (window, real)) in self.window.iter().zip(&self.real).enumerate() {
+            self.output[(self.output_index + index) % size] += window * real;
+        }
+    }
+}
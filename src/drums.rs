@@ -0,0 +1,182 @@
+use crate::envelope::EnvelopeFollower;
+use crate::events::{AeolusEvent, EventSink};
+use crate::filters::OnePoleLowpass;
+
+/// Crossovers between the low (kick), mid (snare) and high (hi-hat) bands
+const LOW_CUTOFF_HZ:  f32 = 150.0;
+const HIGH_CUTOFF_HZ: f32 = 3000.0;
+/// How much each band's level counts when classifying transients, roughly flattening the
+/// spectral tilt of drums (and mouths), which otherwise makes everything a kick
+const BAND_WEIGHTS:   [f32; 3] = [1.0, 1.5, 4.0];
+/// A transient is when the fast envelope jumps this far above the slow one
+const ONSET_RATIO:    f32 = 2.0;
+/// Transients quieter than this (-40 dB) are ignored
+const ONSET_FLOOR:    f32 = 0.01;
+/// Transients are classified from the band levels over this long after they start
+const CLASSIFY_MS:    f32 = 5.0;
+/// Transients closer than this to the previous one are ignored
+const RETRIGGER_MS:   f32 = 50.0;
+/// How long each drum note lasts
+const DRUM_NOTE_MS:   f32 = 50.0;
+
+/// Velocities follow the transient's level in dB, from this up to full scale
+const VELOCITY_FLOOR_DB: f32 = -40.0;
+
+/// Which notes the transients trigger, for each band
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DrumNotes {
+    /// 0-based
+    pub channel: u8,
+    pub kick:    u8,
+    pub snare:   u8,
+    pub hat:     u8,
+}
+
+impl DrumNotes {
+    fn note(&self, band: usize) -> u8 {
+        [self.kick, self.snare, self.hat][band]
+    }
+}
+
+/// A transient, as sent by `DrumTrigger::process()`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Transient {
+    /// Offset (in samples) of the transient from the sample it was classified at
+    pub delay:    usize,
+    /// Between 0 and 1, following the transient's level
+    pub velocity: f32,
+}
+
+/// Percussive mode: finds transients in the input, classifies them by the band most of their
+/// energy is in, and sends the corresponding drum notes
+pub struct DrumTrigger {
+    sample_rate: f32,
+    low:         OnePoleLowpass,
+    high:        OnePoleLowpass,
+    bands:       [EnvelopeFollower; 3],
+    fast:        EnvelopeFollower,
+    slow:        EnvelopeFollower,
+    /// Samples since the last transient, once there was one
+    since_onset: Option<usize>,
+    /// While a transient is being classified: the remaining samples, the highest level of each
+    /// band and overall
+    classifying: Option<(usize, [f32; 3], f32)>,
+    /// The note sounding and its channel, if any
+    sounding:    Option<(u8, u8)>,
+    /// Samples until the sounding note is released
+    remaining:   usize,
+}
+
+impl DrumTrigger {
+    pub fn new(sample_rate: f32) -> Self {
+        let band = || EnvelopeFollower::new(sample_rate, 1.0, 30.0);
+        Self {
+            sample_rate,
+            low: OnePoleLowpass::new(sample_rate, LOW_CUTOFF_HZ),
+            high: OnePoleLowpass::new(sample_rate, HIGH_CUTOFF_HZ),
+            bands: [band(), band(), band()],
+            fast: EnvelopeFollower::new(sample_rate, 1.0, 30.0),
+            slow: EnvelopeFollower::new(sample_rate, 100.0, 300.0),
+            since_onset: None,
+            classifying: None,
+            sounding: None,
+            remaining: 0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        *self = Self::new(sample_rate);
+    }
+
+    /// Forgets everything, without closing the sounding note
+    pub fn reset(&mut self) {
+        *self = Self::new(self.sample_rate);
+    }
+
+    /// Closes the sounding note
+    pub fn release(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if let Some((note, channel)) = self.sounding.take() {
+            sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+        }
+    }
+
+    /// Advances by one sample, and returns the transient it finished classifying, if any
+    pub fn process(
+        &mut self,
+        sink:   &mut impl EventSink,
+        timing: u32,
+        sample: f32,
+        notes:  DrumNotes,
+    ) -> Option<Transient> {
+        let low = self.low.process(sample);
+        let below_high = self.high.process(sample);
+        let levels = [
+            self.bands[0].process(low),
+            self.bands[1].process(below_high - low),
+            self.bands[2].process(sample - below_high),
+        ];
+        let fast = self.fast.process(sample);
+        let slow = self.slow.process(sample);
+        if let Some(since_onset) = &mut self.since_onset {
+            *since_onset += 1;
+        }
+
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                self.release(sink, timing);
+            }
+        }
+
+        if let Some((remaining, peaks, peak)) = &mut self.classifying {
+            for (peak, level) in peaks.iter_mut().zip(levels) {
+                *peak = peak.max(level);
+            }
+            *peak = peak.max(fast);
+            *remaining -= 1;
+            if *remaining == 0 {
+                let (peaks, peak) = (*peaks, *peak);
+                self.classifying = None;
+                return Some(self.trigger(sink, timing, peaks, peak, notes));
+            }
+            return None;
+        }
+
+        let retrigger_samples = (RETRIGGER_MS / 1000.0 * self.sample_rate) as usize;
+        let ready = self.since_onset.is_none_or(|since_onset| since_onset >= retrigger_samples);
+        if ready && fast > ONSET_FLOOR && fast > ONSET_RATIO * slow {
+            let classify_samples = ((CLASSIFY_MS / 1000.0 * self.sample_rate) as usize).max(1);
+            self.classifying = Some((classify_samples, levels, fast));
+            self.since_onset = Some(0);
+        }
+        None
+    }
+
+    fn trigger(
+        &mut self,
+        sink:   &mut impl EventSink,
+        timing: u32,
+        peaks:  [f32; 3],
+        peak:   f32,
+        notes:  DrumNotes,
+    ) -> Transient {
+        let band = (0..3)
+            .max_by(|&a, &b| (BAND_WEIGHTS[a] * peaks[a]).total_cmp(&(BAND_WEIGHTS[b] * peaks[b])))
+            .unwrap_or(0);
+        // The note was detected a little after the transient started, which we make up for
+        let classify_samples = (CLASSIFY_MS / 1000.0 * self.sample_rate) as u32;
+        let timing = timing.saturating_sub(classify_samples);
+        let level_db = 20.0 * peak.max(1e-9).log10();
+        let velocity = (1.0 - level_db / VELOCITY_FLOOR_DB).clamp(0.1, 1.0);
+
+        self.release(sink, timing);
+        let note = notes.note(band);
+        sink.send(AeolusEvent::NoteOn { timing, channel: notes.channel, note, velocity });
+        self.sounding = Some((note, notes.channel));
+        // Counting from the transient itself, so that the note is closed before the next one can
+        // start
+        let note_samples = (DRUM_NOTE_MS / 1000.0 * self.sample_rate) as u32;
+        self.remaining = note_samples.saturating_sub(classify_samples).max(1) as usize;
+        Transient { delay: classify_samples as usize, velocity }
+    }
+}
@@ -0,0 +1,62 @@
+/// How far (in dB) the level has to go past a zone's threshold before the zone changes, so that
+/// it doesn't flicker while the level hovers around it
+const ZONE_HYSTERESIS_DB: f32 = 3.0;
+
+/// How loud the performer plays, as one of three zones
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DynamicsZone {
+    /// Up to pianissimo
+    Soft,
+    /// Around mezzo-forte
+    Medium,
+    /// Up to fortissimo
+    Loud,
+}
+
+impl DynamicsZone {
+    const ALL: [DynamicsZone; 3] = [DynamicsZone::Soft, DynamicsZone::Medium, DynamicsZone::Loud];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Level-based zones, so that playing softly or loudly selects different articulations or
+/// patches downstream. Each zone can send its notes to a channel of its own, offset their
+/// velocity, and be announced with a keyswitch.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DynamicsConfig {
+    /// In dB, the levels the medium and loud zones start at
+    pub medium_threshold: f32,
+    pub loud_threshold:   f32,
+    /// The channel each zone's notes go to, instead of the usual ones. Ignored in MPE mode.
+    pub channels:         Option<[u8; 3]>,
+    /// Added to the velocity of each zone's notes
+    pub velocity_offsets: [f32; 3],
+    /// The lowest of three consecutive keyswitches, one for each zone, sent when it's entered
+    pub keyswitches:      Option<u8>,
+}
+
+impl DynamicsConfig {
+    /// The zone `level_db` is in, coming from the `current` one
+    pub fn zone(&self, level_db: f32, current: Option<DynamicsZone>) -> DynamicsZone {
+        let thresholds = [self.medium_threshold, self.loud_threshold];
+        let zones_above = |margin: f32| thresholds.iter().filter(|threshold| level_db >= **threshold + margin).count();
+        // The zone only moves up once the level is clearly past the next threshold, and down
+        // once it's clearly below the current one
+        let index = match current {
+            Some(current) => current.index().clamp(zones_above(ZONE_HYSTERESIS_DB / 2.0), zones_above(-ZONE_HYSTERESIS_DB / 2.0)),
+            None          => zones_above(0.0),
+        };
+        DynamicsZone::ALL[index]
+    }
+
+    pub fn channel(&self, zone: DynamicsZone) -> Option<u8> {
+        self.channels.map(|channels| channels[zone.index()])
+    }
+
+    /// `velocity`, offset for the zone. It stays above 0, which would make NoteOns NoteOffs.
+    pub fn velocity(&self, zone: DynamicsZone, velocity: f32) -> f32 {
+        (velocity + self.velocity_offsets[zone.index()]).clamp(1.0 / 127.0, 1.0)
+    }
+}
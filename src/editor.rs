@@ -0,0 +1,947 @@
+use nih_plug::prelude::*;
+use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::calibration::{Calibration, CalibrationResult, LiveReading};
+use crate::capture::{Capture, CapturedNote};
+use crate::engine::PitchUnit;
+use crate::learn::{self, ControllerInput};
+use crate::load::{LoadMeter, RISKY_LOAD};
+use crate::logging::{LogFormat, PitchLogger};
+use crate::mapping::Range;
+use crate::plugin::AeolusParams;
+use crate::practice::{PracticeDisplay, CLOSE_CENTS};
+use crate::presets::{self, PresetLibrary, TAGS};
+use crate::routing::CcLaneParams;
+use crate::scale::{RootNote, ScaleType};
+use crate::statistics::{IntonationStats, IN_TUNE_CENTS};
+use crate::utils::{freq_to_midi, midi_to_freq, note_name};
+
+pub fn default_state() -> Arc<EguiState> {
+    EguiState::from_size(600, 800)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Page {
+    Main,
+    /// Every output, with where it goes, which is hard to get an overview of from the host's
+    /// flat parameter list
+    Routing,
+    Presets,
+}
+
+/// Piano roll zoom bounds, in pixels per second
+const MIN_ZOOM: f32 = 5.0;
+const MAX_ZOOM: f32 = 400.0;
+const ROLL_HEIGHT: f32 = 160.0;
+
+/// What the editor remembers between frames
+struct EditorState {
+    page: Page,
+    /// Piano roll zoom, in pixels per second
+    zoom: f32,
+    /// Outcome of the last export, shown below the buttons
+    export_status: Option<String>,
+    /// Where the last successful export went
+    exported_path: Option<PathBuf>,
+    log_path:   String,
+    log_format: LogFormat,
+    /// Why logging couldn't be started, if it couldn't
+    log_error:  Option<String>,
+    /// Outcome of the last statistics export
+    stats_status: Option<String>,
+    calibration:  Calibration,
+    /// What the last calibration set, shown until the next one
+    calibration_status: Option<String>,
+    /// Only scanned once the presets page is shown
+    presets:       Option<PresetLibrary>,
+    preset_search: String,
+    /// The instrument the list is filtered by, if any
+    preset_tag:    Option<&'static str>,
+    /// What the current configuration is saved as: name, folder and tags (one per `TAGS`)
+    preset_name:   String,
+    preset_folder: String,
+    preset_tags:   [bool; TAGS.len()],
+    /// Outcome of the last load or save
+    preset_status: Option<String>,
+    /// Every parameter's ID and name, listed once MIDI learn needs them
+    param_names: Option<Vec<(String, String)>>,
+    /// The parameter MIDI learn binds, as an index in `param_names`
+    learn_index: usize,
+    /// Whether MIDI learn waits for a CC to move
+    learning:    bool,
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        Self {
+            page: Page::Main,
+            zoom: 50.0,
+            export_status: None,
+            exported_path: None,
+            log_path: home_directory().join("aeolus-pitch.csv").display().to_string(),
+            log_format: LogFormat::Csv,
+            log_error: None,
+            stats_status: None,
+            calibration: Calibration::default(),
+            calibration_status: None,
+            presets: None,
+            preset_search: String::new(),
+            preset_tag: None,
+            preset_name: String::new(),
+            preset_folder: String::new(),
+            preset_tags: [false; TAGS.len()],
+            preset_status: None,
+            param_names: None,
+            learn_index: 0,
+            learning: false,
+        }
+    }
+}
+
+fn home_directory() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// User presets go in there, each folder in it being one of the browser's
+fn presets_directory() -> PathBuf {
+    home_directory().join("Aeolus Presets")
+}
+
+/// Exports are written next to each other, in the home directory if there is one
+fn export_path(prefix: &str, extension: &str) -> PathBuf {
+    let directory = home_directory();
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    directory.join(format!("{prefix}-{timestamp}.{extension}"))
+}
+
+fn export(capture: &Mutex<Capture>, state: &mut EditorState) {
+    // The lock is only held while copying, the audio thread never waits for it anyway
+    let bytes = capture.lock().unwrap().to_smf();
+    let path = export_path("aeolus-capture", "mid");
+    match std::fs::write(&path, bytes) {
+        Ok(()) => {
+            state.export_status = Some(format!("Exported to {}", path.display()));
+            state.exported_path = Some(path);
+        }
+        Err(error) => state.export_status = Some(format!("Export failed: {error}")),
+    }
+}
+
+fn export_statistics(statistics: &Mutex<IntonationStats>) -> String {
+    let csv = statistics.lock().unwrap().to_csv();
+    let path = export_path("aeolus-intonation", "csv");
+    match std::fs::write(&path, csv) {
+        Ok(())     => format!("Exported to {}", path.display()),
+        Err(error) => format!("Export failed: {error}"),
+    }
+}
+
+/// Starts logging if it isn't running, stops it otherwise
+fn toggle_logging(pitch_logger: &Mutex<Option<PitchLogger>>, state: &mut EditorState) {
+    let mut pitch_logger = pitch_logger.lock().unwrap();
+    if pitch_logger.take().is_some() {
+        // Dropping the logger joined its thread, after it wrote everything
+        return;
+    }
+    match PitchLogger::start(state.log_path.as_ref(), state.log_format) {
+        Ok(logger) => {
+            *pitch_logger = Some(logger);
+            state.log_error = None;
+        }
+        Err(error) => state.log_error = Some(format!("Couldn't log: {error}")),
+    }
+}
+
+/// The notes of the last take, to check the transcription before exporting it
+fn piano_roll(ui: &mut egui::Ui, notes: &[CapturedNote], zoom: f32) {
+    let lowest = notes.iter().map(|note| note.note).min().unwrap_or(60).saturating_sub(2);
+    let highest = notes.iter().map(|note| note.note).max().unwrap_or(72).saturating_add(2).min(127);
+    let duration = notes.iter().map(|note| note.end).fold(0.0, f64::max) as f32;
+    let row_height = ROLL_HEIGHT / (highest - lowest + 1) as f32;
+
+    egui::ScrollArea::horizontal().id_source("piano roll").stick_to_right(true).show(ui, |ui| {
+        let width = (duration * zoom).max(ui.available_width());
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, ROLL_HEIGHT), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        // Black keys get a darker row, like in DAWs
+        for note in lowest..=highest {
+            if matches!(note % 12, 1 | 3 | 6 | 8 | 10) {
+                let top = rect.bottom() - (note - lowest + 1) as f32 * row_height;
+                let row = egui::Rect::from_min_size(egui::pos2(rect.left(), top), egui::vec2(width, row_height));
+                painter.rect_filled(row, 0.0, ui.visuals().faint_bg_color);
+            }
+        }
+        for note in notes {
+            let top = rect.bottom() - (note.note - lowest + 1) as f32 * row_height;
+            let left = rect.left() + note.start as f32 * zoom;
+            // Even the shortest notes stay visible
+            let length = ((note.end - note.start) as f32 * zoom).max(2.0);
+            let bar = egui::Rect::from_min_size(egui::pos2(left, top), egui::vec2(length, row_height));
+            painter.rect_filled(bar.shrink(0.5), 1.0, ui.visuals().selection.bg_fill);
+        }
+    });
+}
+
+/// Sets a parameter as a single gesture
+fn set_param<P: Param>(setter: &ParamSetter, param: &P, value: P::Plain) {
+    setter.begin_set_parameter(param);
+    setter.set_parameter(param, value);
+    setter.end_set_parameter(param);
+}
+
+fn apply_calibration(setter: &ParamSetter, params: &AeolusParams, result: CalibrationResult) -> String {
+    set_param(setter, &params.min_note, result.min_note);
+    set_param(setter, &params.max_note, result.max_note);
+    set_param(setter, &params.min_freq, midi_to_freq(result.min_note));
+    set_param(setter, &params.max_freq, midi_to_freq(result.max_note));
+    set_param(setter, &params.tracking_range, result.tracking_range);
+    set_param(setter, &params.speed_accuracy, result.speed_accuracy);
+    let gain_db = (util::gain_to_db(params.gain.value()) + result.gain_change_db).clamp(-30.0, 30.0);
+    set_param(setter, &params.gain, util::db_to_gain(gain_db));
+    format!(
+        "Range set to {} to {}, input gain to {gain_db:+.1} dB",
+        note_name(result.min_note.round() as u8),
+        note_name(result.max_note.round() as u8),
+    )
+}
+
+/// Moves the output parameters to the live reading, so that hosts can record them
+fn update_outputs(setter: &ParamSetter, params: &AeolusParams, live: &LiveReading) {
+    // Unvoiced input leaves the pitch where it was, so that the curve doesn't drop to 0
+    if let Some(frequency) = live.frequency() {
+        let pitch = Range::new(0.0, 127.0).clamp(freq_to_midi(frequency));
+        if (pitch - params.pitch_output.value()).abs() > 0.005 {
+            set_param(setter, &params.pitch_output, pitch);
+        }
+    }
+    let level_db = Range::new(-60.0, 0.0).clamp(util::gain_to_db(live.level()));
+    if (level_db - params.level_output.value()).abs() > 0.05 {
+        set_param(setter, &params.level_output, level_db);
+    }
+}
+
+/// A checkbox for a boolean parameter, set as a single gesture
+fn param_checkbox(ui: &mut egui::Ui, setter: &ParamSetter, param: &BoolParam) {
+    let mut value = param.value();
+    if ui.checkbox(&mut value, "").changed() {
+        set_param(setter, param, value);
+    }
+}
+
+fn param_slider<P: Param>(ui: &mut egui::Ui, setter: &ParamSetter, param: &P) {
+    ui.add(widgets::ParamSlider::for_param(param, setter).with_width(80.0));
+}
+
+/// One row of the routing grid, for a CC lane. `range` draws what the lane's input range is,
+/// above the output range, and the value sent when unvoiced if the lane has one.
+fn cc_lane_row(
+    ui:       &mut egui::Ui,
+    setter:   &ParamSetter,
+    source:   &str,
+    lane:     &CcLaneParams,
+    unvoiced: bool,
+    range:    impl FnOnce(&mut egui::Ui),
+) {
+    param_checkbox(ui, setter, &lane.enabled);
+    ui.label(source);
+    ui.label("CC");
+    param_slider(ui, setter, &lane.channel);
+    param_slider(ui, setter, &lane.number);
+    ui.vertical(|ui| {
+        range(ui);
+        ui.horizontal(|ui| {
+            ui.label("to");
+            param_slider(ui, setter, &lane.min);
+            param_slider(ui, setter, &lane.max);
+        });
+        if unvoiced {
+            ui.horizontal(|ui| {
+                ui.label("unvoiced:");
+                param_slider(ui, setter, &lane.unvoiced);
+            });
+        }
+    });
+    param_slider(ui, setter, &lane.curve);
+    ui.vertical(|ui| {
+        param_slider(ui, setter, &lane.smoothing);
+        param_slider(ui, setter, &lane.rate_limit);
+    });
+    ui.end_row();
+}
+
+fn routing_page(ui: &mut egui::Ui, setter: &ParamSetter, params: &AeolusParams, live: &LiveReading) {
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.pitch_analysis);
+        ui.label("Pitch analysis (without it, only the tempo and drum outputs are sent)");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Input");
+        param_slider(ui, setter, &params.analysis_channel);
+        ui.label("Detector");
+        param_slider(ui, setter, &params.pitch_method);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pitch correction");
+        param_slider(ui, setter, &params.correction);
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.keyboard_split);
+        ui.label("Split at note");
+        param_slider(ui, setter, &params.split_point);
+        ui.label(": notes and pitch CCs below go to channel");
+        param_slider(ui, setter, &params.note_channel);
+        ui.label(", the others to");
+        param_slider(ui, setter, &params.split_upper_channel);
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.dynamics_zones);
+        ui.label("Dynamics zones, medium from");
+        param_slider(ui, setter, &params.zone_medium_threshold);
+        ui.label("and loud from");
+        param_slider(ui, setter, &params.zone_loud_threshold);
+        ui.label(", velocities offset by ±");
+        param_slider(ui, setter, &params.zone_velocity_offset);
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.zone_channels);
+        ui.label("Soft, medium and loud notes on channels");
+        param_slider(ui, setter, &params.zone_soft_channel);
+        param_slider(ui, setter, &params.zone_medium_channel);
+        param_slider(ui, setter, &params.zone_loud_channel);
+        param_checkbox(ui, setter, &params.zone_keyswitches);
+        ui.label("Keyswitches from");
+        param_slider(ui, setter, &params.zone_keyswitch_base);
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.vibrato_split);
+        ui.label("Send the pitch movement faster than");
+        param_slider(ui, setter, &params.vibrato_crossover);
+        ui.label("to the bends only, keeping notes and CC lanes steady through vibrato");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Scale");
+        param_slider(ui, setter, &params.scale_root);
+        param_slider(ui, setter, &params.scale_type);
+        param_checkbox(ui, setter, &params.capture_scale);
+        ui.label("Capture from the MIDI input");
+        if params.auto_key.value() {
+            // The key is only known once enough was played
+            let key = live.key().map_or("None yet".to_owned(), |key| {
+                let root = RootNote::variants()[key.root.pitch_class() as usize];
+                format!("{root} {}", ScaleType::variants()[key.scale.to_index()].to_lowercase())
+            });
+            ui.label(format!("Detected key: {key}"));
+        } else if params.scale_type.value() == ScaleType::Learned {
+            let scale = params.scale();
+            let notes: Vec<_> = (0..12)
+                .filter(|pitch_class| scale.mask() & (1 << pitch_class) != 0)
+                .map(|pitch_class| RootNote::variants()[pitch_class])
+                .collect();
+            ui.label(notes.join(" "));
+        }
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.auto_key);
+        ui.label("Follow the detected key, when");
+        param_slider(ui, setter, &params.auto_key_confidence);
+        ui.label("confident, changing at most every");
+        param_slider(ui, setter, &params.auto_key_interval);
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.scale_gate);
+        ui.label("Treat pitches further than");
+        param_slider(ui, setter, &params.scale_gate_distance);
+        ui.label("from the scale as unvoiced");
+    });
+    ui.horizontal(|ui| {
+        param_checkbox(ui, setter, &params.delay_audio);
+        ui.label("Delay the audio by the analysis latency, to line it up with the MIDI");
+    });
+    ui.separator();
+
+    egui::Grid::new("routing").striped(true).show(ui, |ui| {
+        for title in ["", "Source", "Destination", "Channel", "Number", "Range", "Curve", "Rate limit"] {
+            ui.strong(title);
+        }
+        ui.end_row();
+
+        cc_lane_row(ui, setter, "Pitch", &params.cc_lane, true, |ui| {
+            ui.horizontal(|ui| match params.pitch_unit.value() {
+                PitchUnit::MidiNote => {
+                    param_slider(ui, setter, &params.min_note);
+                    param_slider(ui, setter, &params.max_note);
+                    param_slider(ui, setter, &params.out_of_range);
+                }
+                PitchUnit::Frequency => {
+                    param_slider(ui, setter, &params.min_freq);
+                    param_slider(ui, setter, &params.max_freq);
+                    param_slider(ui, setter, &params.out_of_range);
+                }
+                PitchUnit::Cents => {
+                    ui.label("±");
+                    param_slider(ui, setter, &params.cents_range);
+                }
+            });
+        });
+        // Changes of the main lane are held for at least this long, on top of its own smoothing
+        ui.label("");
+        ui.label("");
+        ui.label("Main CC hold");
+        ui.label("");
+        ui.label("");
+        ui.label("");
+        ui.label("");
+        param_slider(ui, setter, &params.hold_time);
+        ui.end_row();
+
+        cc_lane_row(ui, setter, "Cents deviation", &params.cents_lane, true, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("±");
+                param_slider(ui, setter, &params.cents_range);
+            });
+        });
+
+        cc_lane_row(ui, setter, "Confidence", &params.confidence_lane, false, |ui| {
+            ui.label("0 to 1");
+        });
+
+        // Sent only when the register changes, so smoothing and rate limits don't apply
+        cc_lane_row(ui, setter, "Voice register", &params.register_lane, true, |ui| {
+            ui.label("4 steps");
+        });
+
+        cc_lane_row(ui, setter, "First formant", &params.f1_lane, true, |ui| {
+            ui.label("200 Hz to 1 kHz (log)");
+        });
+
+        cc_lane_row(ui, setter, "Second formant", &params.f2_lane, true, |ui| {
+            ui.label("600 Hz to 3 kHz (log)");
+        });
+
+        cc_lane_row(ui, setter, "Practice accuracy", &params.accuracy_lane, true, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("±");
+                param_slider(ui, setter, &params.accuracy_range);
+            });
+        });
+
+        // Notes, and what comes with them, go wherever the channel assignment sends them
+        let note_channels = if params.mpe_enabled.value() {
+            "MPE zone".to_owned()
+        } else if params.keyboard_split.value() {
+            format!("{} / {}", params.note_channel.value(), params.split_upper_channel.value())
+        } else if params.channel_rotation.value() {
+            format!("{} to {}", params.rotation_first.value(), params.rotation_last.value())
+        } else {
+            params.note_channel.value().to_string()
+        };
+
+        param_checkbox(ui, setter, &params.note_enabled);
+        ui.vertical(|ui| {
+            ui.label("Quantized pitch");
+            ui.horizontal(|ui| {
+                ui.label("Velocity:");
+                param_slider(ui, setter, &params.velocity_source);
+            });
+            ui.horizontal(|ui| {
+                param_checkbox(ui, setter, &params.release_velocity);
+                ui.label("Release velocity");
+            });
+            ui.horizontal(|ui| {
+                param_checkbox(ui, setter, &params.sustain_pedal);
+                ui.label("Sustain pedal (CC64 in)");
+            });
+            ui.horizontal(|ui| {
+                param_checkbox(ui, setter, &params.note_filter);
+                ui.label("Only from");
+                param_slider(ui, setter, &params.note_filter_low);
+                ui.label("to");
+                param_slider(ui, setter, &params.note_filter_high);
+            });
+        });
+        ui.label("Notes");
+        ui.label(&note_channels);
+        ui.label("");
+        ui.label("");
+        ui.label("");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.mpe_enabled);
+        ui.label("Pitch deviation");
+        ui.label("Pitch bend");
+        ui.label(&note_channels);
+        ui.label("");
+        ui.horizontal(|ui| {
+            ui.label("±");
+            param_slider(ui, setter, &params.mpe_bend_range);
+        });
+        ui.label("Linear");
+        param_slider(ui, setter, &params.bend_smoothing);
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.guitar_mode);
+        ui.label("Pitch deviation");
+        ui.label("Pitch bend (guitar mode)");
+        ui.label(&note_channels);
+        param_slider(ui, setter, &params.guitar_bend_reset);
+        ui.horizontal(|ui| {
+            ui.label("±");
+            param_slider(ui, setter, &params.guitar_bend_range);
+        });
+        ui.label("Linear");
+        param_slider(ui, setter, &params.bend_smoothing);
+        ui.end_row();
+
+        // Applies to both kinds of bends above
+        ui.label("");
+        ui.label("Note ends");
+        ui.label("Bend return");
+        ui.label(&note_channels);
+        param_slider(ui, setter, &params.bend_return);
+        param_slider(ui, setter, &params.bend_return_time);
+        ui.label("Linear");
+        ui.label("");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.mpe_pressure);
+        ui.label("Level");
+        ui.label("Channel pressure");
+        ui.label(&note_channels);
+        ui.label("");
+        ui.label("");
+        ui.label("Decibels");
+        ui.label("None");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.mpe_brightness);
+        ui.label("Richness");
+        ui.label("Brightness");
+        ui.label(&note_channels);
+        ui.label("74");
+        ui.label("");
+        ui.label("Logarithmic");
+        ui.label("None");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.portamento);
+        ui.label("Slides");
+        ui.label("Portamento");
+        ui.label(&note_channels);
+        ui.label("65 and 5");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.clock_output);
+        ui.label("Beats");
+        ui.label("MIDI clock");
+        ui.label("All");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.beat_notes);
+        ui.label("Beats");
+        ui.label("Notes");
+        param_slider(ui, setter, &params.beat_channel);
+        ui.vertical(|ui| {
+            param_slider(ui, setter, &params.beat_note);
+            param_slider(ui, setter, &params.downbeat_note);
+            param_slider(ui, setter, &params.beats_per_bar);
+        });
+        ui.label("Strength");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.drums);
+        ui.label("Transients");
+        ui.label("Drum notes");
+        param_slider(ui, setter, &params.drum_channel);
+        ui.vertical(|ui| {
+            param_slider(ui, setter, &params.kick_note);
+            param_slider(ui, setter, &params.snare_note);
+            param_slider(ui, setter, &params.hat_note);
+        });
+        ui.label("Level");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.poly_pressure);
+        ui.label("Level");
+        ui.label("Poly pressure");
+        ui.label(&note_channels);
+        ui.label("");
+        ui.label("");
+        ui.label("Decibels");
+        ui.label("None");
+        ui.end_row();
+
+        param_checkbox(ui, setter, &params.high_resolution);
+        ui.label("Pitch");
+        ui.label("Note expression");
+        ui.label(&note_channels);
+        ui.end_row();
+    });
+    ui.label("Note expressions are only sent to CLAP hosts.");
+}
+
+/// The factory and user presets, searchable and filtered by instrument, and saving the current
+/// configuration. Hosts all have their own preset handling, but some hardly have any.
+fn presets_page(ui: &mut egui::Ui, setter: &ParamSetter, params: &AeolusParams, state: &mut EditorState) {
+    let library = state.presets.get_or_insert_with(|| PresetLibrary::scan(presets_directory()));
+    let mut rescan = false;
+    ui.horizontal(|ui| {
+        ui.label("Search");
+        ui.text_edit_singleline(&mut state.preset_search);
+        rescan = ui.button("Refresh").on_hover_text("Look for presets added since").clicked();
+    });
+    ui.horizontal_wrapped(|ui| {
+        ui.selectable_value(&mut state.preset_tag, None, "All");
+        for tag in TAGS {
+            ui.selectable_value(&mut state.preset_tag, Some(tag), tag);
+        }
+    });
+    egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+        let matching = library.entries().iter().filter(|entry| entry.matches(&state.preset_search, state.preset_tag));
+        for entry in matching {
+            ui.horizontal(|ui| {
+                if ui.button("Load").clicked() {
+                    state.preset_status = Some(match entry.load() {
+                        Ok(preset) => {
+                            presets::restore(setter.raw_context, params, preset.state);
+                            format!("Loaded {}", entry.name)
+                        }
+                        Err(error) => format!("Couldn't load {}: {error}", entry.name),
+                    });
+                }
+                ui.label(&entry.name);
+                let origin = if entry.is_factory() { "Factory" } else { entry.folder.as_str() };
+                ui.weak(format!("{origin} {}", entry.tags.join(", ")).trim().to_owned());
+            });
+        }
+    });
+    if let Some(status) = &state.preset_status {
+        ui.label(status);
+    }
+
+    ui.separator();
+    ui.heading("Save");
+    ui.horizontal(|ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut state.preset_name);
+    });
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Folder");
+        ui.text_edit_singleline(&mut state.preset_folder);
+        for folder in library.folders() {
+            if ui.selectable_label(state.preset_folder == *folder, folder).clicked() {
+                state.preset_folder = folder.clone();
+            }
+        }
+    });
+    ui.horizontal_wrapped(|ui| {
+        for (tag, tagged) in TAGS.iter().zip(&mut state.preset_tags) {
+            ui.checkbox(tagged, *tag);
+        }
+    });
+    let name = state.preset_name.trim();
+    if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+        let tags = TAGS.iter().zip(state.preset_tags).filter(|(_, tagged)| *tagged).map(|(tag, _)| tag.to_string());
+        let preset = presets::capture(setter.raw_context, name.to_owned(), tags.collect());
+        state.preset_status = Some(match library.save(&state.preset_folder, &preset) {
+            Ok(path) => {
+                rescan = true;
+                format!("Saved to {}", path.display())
+            }
+            Err(error) => format!("Couldn't save: {error}"),
+        });
+    }
+    ui.label(format!("User presets are in {}", library.directory().display()));
+    if rescan {
+        state.presets = None;
+    }
+}
+
+/// What the editor shares with the audio thread, besides the parameters
+pub struct Shared {
+    pub capture:      Arc<Mutex<Capture>>,
+    pub pitch_logger: Arc<Mutex<Option<PitchLogger>>>,
+    pub practice:     Arc<PracticeDisplay>,
+    pub statistics:   Arc<Mutex<IntonationStats>>,
+    pub live:         Arc<LiveReading>,
+    pub panic:        Arc<AtomicBool>,
+    pub controllers:  Arc<ControllerInput>,
+    pub audio_load:   Arc<LoadMeter>,
+    pub worker_load:  Arc<LoadMeter>,
+}
+
+pub fn create(params: Arc<AeolusParams>, editor_state: Arc<EguiState>, shared: Shared) -> Option<Box<dyn Editor>> {
+    let Shared { capture, pitch_logger, practice, statistics, live, panic, controllers, audio_load, worker_load } = shared;
+    create_egui_editor(
+        editor_state,
+        EditorState::default(),
+        |_, _| {},
+        move |egui_ctx, setter, state| {
+            update_outputs(setter, &params, &live);
+            learn::apply(setter, &params, &controllers);
+            // The display (and the output parameters) follow the audio thread, not the user's
+            // input
+            egui_ctx.request_repaint();
+            egui::TopBottomPanel::top("pages").show(egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut state.page, Page::Main, "Main");
+                    ui.selectable_value(&mut state.page, Page::Routing, "Routing");
+                    ui.selectable_value(&mut state.page, Page::Presets, "Presets");
+                    ui.separator();
+                    // A/B comparison, on every page
+                    let active = params.ab_slots.lock().map_or(0, |slots| slots.active);
+                    for (slot, name) in ["A", "B"].into_iter().enumerate() {
+                        if ui.selectable_label(active == slot, name).clicked() && active != slot {
+                            presets::switch_slot(setter.raw_context, &params);
+                        }
+                    }
+                    let copy = ui.button(format!("Copy to {}", ["A", "B"][1 - active]));
+                    if copy.on_hover_text("Overwrite the other slot with this configuration").clicked() {
+                        presets::copy_to_other_slot(setter.raw_context, &params);
+                    }
+                    // Handled by the audio thread at the start of the next block
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Panic").on_hover_text("Stop all notes and reset the CC lanes").clicked() {
+                            panic.store(true, Ordering::Relaxed);
+                        }
+                    });
+                });
+            });
+            egui::CentralPanel::default().show(egui_ctx, |ui| {
+                match state.page {
+                    Page::Main    => (),
+                    Page::Routing => return routing_page(ui, setter, &params, &live),
+                    Page::Presets => return presets_page(ui, setter, &params, state),
+                }
+
+                ui.heading("Calibration");
+                match state.calibration.step() {
+                    None => {
+                        if ui.button("Calibrate").clicked() {
+                            state.calibration.start();
+                            state.calibration_status = None;
+                        }
+                        if let Some(status) = &state.calibration_status {
+                            ui.label(status);
+                        }
+                    }
+                    Some(step) => {
+                        state.calibration.observe(&live);
+                        ui.label(step.instructions());
+                        ui.horizontal(|ui| {
+                            let next = ui.add_enabled(state.calibration.can_advance(), egui::Button::new("Next"));
+                            if next.clicked() {
+                                if let Some(result) = state.calibration.advance() {
+                                    state.calibration_status = Some(apply_calibration(setter, &params, result));
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                state.calibration.cancel();
+                            }
+                        });
+                    }
+                }
+
+                ui.separator();
+                ui.heading("Capture");
+                let captured = capture.lock().map(|capture| capture.len()).unwrap_or(0);
+                ui.label(format!("{captured} events captured"));
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(captured > 0, egui::Button::new("Export .mid")).clicked() {
+                        export(&capture, state);
+                    }
+                    if ui.button("Clear").clicked() {
+                        if let Ok(mut capture) = capture.lock() {
+                            capture.clear();
+                        }
+                        state.export_status = None;
+                        state.exported_path = None;
+                    }
+                });
+                if let Some(status) = &state.export_status {
+                    ui.label(status);
+                }
+                // TODO Let the clip be dragged straight into the DAW. baseview (which nih_plug's
+                //      editors run on) can't act as a drag source yet, so until it can, the path
+                //      can at least be pasted into the DAW's import dialog.
+                if let Some(path) = &state.exported_path {
+                    if ui.button("Copy path").clicked() {
+                        ui.output_mut(|output| output.copied_text = path.display().to_string());
+                    }
+                }
+                let notes = capture.lock().map(|capture| capture.notes()).unwrap_or_default();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} notes", notes.len()));
+                    ui.add(
+                        egui::Slider::new(&mut state.zoom, MIN_ZOOM..=MAX_ZOOM)
+                            .logarithmic(true)
+                            .text("Zoom"),
+                    );
+                });
+                piano_roll(ui, &notes, state.zoom);
+
+                ui.separator();
+                ui.heading("Pitch log");
+                let logging = pitch_logger.lock().map(|logger| logger.is_some()).unwrap_or(false);
+                ui.add_enabled(!logging, egui::TextEdit::singleline(&mut state.log_path));
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(!logging, |ui| {
+                        ui.radio_value(&mut state.log_format, LogFormat::Csv, "CSV");
+                        ui.radio_value(&mut state.log_format, LogFormat::Json, "JSON");
+                    });
+                    if ui.button(if logging { "Stop logging" } else { "Start logging" }).clicked() {
+                        toggle_logging(&pitch_logger, state);
+                    }
+                });
+                if let Some(error) = &state.log_error {
+                    ui.label(error);
+                }
+
+                ui.separator();
+                ui.heading("Practice");
+                match (practice.target(), practice.deviation()) {
+                    (None, _) => {
+                        ui.label("Play a target note on the MIDI input");
+                    }
+                    (Some(target), None) => {
+                        ui.label(format!("Target: {}", note_name(target)));
+                    }
+                    (Some(target), Some(deviation)) => {
+                        let color = if deviation.abs() <= IN_TUNE_CENTS {
+                            egui::Color32::GREEN
+                        } else if deviation.abs() <= CLOSE_CENTS {
+                            egui::Color32::YELLOW
+                        } else {
+                            egui::Color32::RED
+                        };
+                        ui.colored_label(color, format!("Target: {}, {deviation:+.0} ct", note_name(target)));
+                    }
+                }
+
+                ui.separator();
+                ui.heading("Intonation");
+                if let Ok(stats) = statistics.lock() {
+                    match stats.in_tune_ratio() {
+                        Some(ratio) => ui.label(format!(
+                            "In tune (within {IN_TUNE_CENTS} ct) {:.0}% of the time",
+                            100.0 * ratio,
+                        )),
+                        None => ui.label("Nothing played yet"),
+                    };
+                    egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+                        for note in stats.notes() {
+                            ui.label(format!(
+                                "{}: {:.1} s, {:+.0} ct on average, {:.0}% in tune",
+                                note_name(note.note),
+                                note.seconds,
+                                note.mean_deviation,
+                                100.0 * note.in_tune,
+                            ));
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Export CSV").clicked() {
+                        state.stats_status = Some(export_statistics(&statistics));
+                    }
+                    if ui.button("Clear").clicked() {
+                        if let Ok(mut stats) = statistics.lock() {
+                            stats.clear();
+                        }
+                        state.stats_status = None;
+                    }
+                });
+                if let Some(status) = &state.stats_status {
+                    ui.label(status);
+                }
+
+                ui.separator();
+                ui.heading("MIDI learn");
+                midi_learn(ui, &params, &controllers, state);
+
+                ui.separator();
+                ui.heading("Performance");
+                ui.horizontal(|ui| {
+                    param_slider(ui, setter, &params.decimation);
+                    ui.label(format!("Pitch updates {:.0} times per second", live.update_rate()));
+                });
+                load_row(ui, "Audio thread", &audio_load);
+                if params.background_analysis.value() {
+                    load_row(ui, "Analysis thread", &worker_load);
+                }
+                if ui.button("Reset").clicked() {
+                    audio_load.reset();
+                    worker_load.reset();
+                }
+            });
+        },
+    )
+}
+
+/// Binds a controller's knob to a parameter, and lists the bindings
+fn midi_learn(ui: &mut egui::Ui, params: &AeolusParams, controllers: &ControllerInput, state: &mut EditorState) {
+    let names = state.param_names.get_or_insert_with(|| learn::param_names(params));
+    ui.horizontal(|ui| {
+        let selected = names.get(state.learn_index).map_or("", |(_, name)| name.as_str());
+        egui::ComboBox::from_id_source("learned parameter").selected_text(selected).show_ui(ui, |ui| {
+            for (index, (_, name)) in names.iter().enumerate() {
+                ui.selectable_value(&mut state.learn_index, index, name);
+            }
+        });
+        let button = ui.button(if state.learning { "Cancel" } else { "Learn" });
+        if button.on_hover_text("Then move a knob or fader on the MIDI input").clicked() {
+            state.learning = !state.learning;
+            controllers.forget_last();
+        }
+    });
+    let mut bindings = params.midi_bindings.lock().unwrap();
+    if state.learning {
+        match (controllers.last_moved(), names.get(state.learn_index)) {
+            (Some(cc), Some((id, _))) => {
+                bindings.bind(cc, id.clone());
+                state.learning = false;
+            }
+            _ => {
+                ui.label("Move a knob or fader on the controller");
+            }
+        }
+    }
+    let mut forgotten = None;
+    for (cc, id) in bindings.iter() {
+        ui.horizontal(|ui| {
+            let name = names.iter().find(|(own, _)| own == id).map_or(id, |(_, name)| name.as_str());
+            ui.label(format!("CC {cc} moves {name}"));
+            if ui.small_button("Forget").clicked() {
+                forgotten = Some(cc);
+            }
+        });
+    }
+    if let Some(cc) = forgotten {
+        bindings.unbind(cc);
+    }
+    ui.label("Bindings only act while this window is open.");
+}
+
+/// A thread's load, with a warning when the worst case gets close to the limit
+fn load_row(ui: &mut egui::Ui, thread: &str, load: &LoadMeter) {
+    ui.horizontal(|ui| {
+        ui.label(format!(
+            "{thread}: {:.0}% on average, {:.0}% at worst",
+            100.0 * load.average(),
+            100.0 * load.worst(),
+        ));
+        let overruns = load.overruns();
+        if overruns > 0 {
+            ui.colored_label(egui::Color32::RED, format!("{overruns} blocks too late, expect dropouts"));
+        } else if load.worst() > RISKY_LOAD {
+            ui.colored_label(egui::Color32::YELLOW, "Close to the limit, try a larger buffer or a lighter detector");
+        }
+    });
+}
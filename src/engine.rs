@@ -0,0 +1,1197 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+use crate::analysis::{
+    max_host_buffer_size, AnalysisQuality, AnalysisSpeed, AnalyzerBank, Detection, PitchFrame, PitchMethod,
+    TrackingRange,
+};
+use crate::bus::{AnalysisEvent, EventQueue, OutputContext, OutputGenerator, PitchEstimate};
+use crate::clock::MidiClock;
+use crate::denoise::Denoiser;
+use crate::drums::{DrumNotes, DrumTrigger};
+use crate::dynamics::DynamicsConfig;
+use crate::envelope::{DecayRate, EnvelopeFollower};
+use crate::events::{AeolusEvent, EventSink};
+use crate::filters::DcBlocker;
+use crate::formants::FormantTracker;
+use crate::gate::{GateMode, NoiseFloor};
+use crate::guitar::{center_bends, GuitarConfig};
+use crate::hold::Hold;
+use crate::key::{AutoKey, Key, KeyDetector};
+use crate::mapping::Range;
+use crate::midi::send_all_notes_off;
+use crate::mpe::MpeConfig;
+use crate::notes::{ChannelAssignment, NotePriority};
+use crate::outputs::{BuiltinOutputs, OutputState};
+use crate::pulse::{BeatNotes, BeatTrigger};
+use crate::quantizer::SemitoneQuantizer;
+use crate::resampling::rate_conversion_latency;
+use crate::register::SpectralBalance;
+use crate::routing::{CcLane, LaneState};
+use crate::scale::Scale;
+use crate::smoothing::PitchSmoother;
+use crate::tempo::TempoTracker;
+use crate::trill::TrillMode;
+use crate::utils::*;
+use crate::velocity::{release_velocity, SpectralFlux, VelocitySource};
+use crate::vibrato::VibratoSplitter;
+
+
+// Only used until we're told the actual sample rate:
+const SAMPLE_RATE:         f32 = 44100.0;
+const ENVELOPE_ATTACK_MS:  f32 = 5.0;
+const ENVELOPE_RELEASE_MS: f32 = 100.0;
+/// Pitch smoothing time at the "accurate" end of the Fast/Accurate macro
+const MAX_SMOOTHING_MS:    f32 = 40.0;
+/// The denoiser only learns from input quieter than this (-30 dB)
+const DENOISE_LEARNING_LEVEL: f32 = 0.0316;
+/// Once the input stayed below this level (-60 dB) for `IDLE_MS`, the detectors stop running
+const IDLE_LEVEL: f32 = 0.001;
+const IDLE_MS:    f32 = 1000.0;
+/// How long the thresholds of the analysis settings take to glide to a new value
+const SETTING_GLIDE_MS: f32 = 50.0;
+
+/// What the main CC lane encodes
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum PitchUnit {
+    /// The absolute MIDI note, mapped between the note range bounds
+    #[cfg_attr(feature = "plugin", name = "MIDI note")]
+    MidiNote,
+    /// The frequency on a logarithmic scale, mapped between the frequency range bounds
+    #[cfg_attr(feature = "plugin", name = "Frequency")]
+    Frequency,
+    /// The deviation from the nearest semitone, as a bipolar value centered at 64
+    #[cfg_attr(feature = "plugin", name = "Cents")]
+    Cents,
+}
+
+/// What the main CC lane does with pitches outside of its range bounds
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum OutOfRange {
+    /// Stick to the nearest bound
+    #[cfg_attr(feature = "plugin", name = "Clamp")]
+    Clamp,
+    /// Transpose by whole octaves back into the range, which lets a narrow window follow a wide
+    /// instrument. Ranges narrower than an octave are clamped instead.
+    #[cfg_attr(feature = "plugin", name = "Fold")]
+    Fold,
+    /// Start over from the other bound, like a sawtooth
+    #[cfg_attr(feature = "plugin", name = "Wrap")]
+    Wrap,
+}
+
+impl OutOfRange {
+    /// Maps `position` from between `low` and `high` to between 0 and 1, `octave` being the
+    /// width of an octave in the same unit
+    pub fn map(self, position: f32, low: f32, high: f32, octave: f32) -> f32 {
+        let value = Range::new(low, high).position(position);
+        let octave = octave / (high - low).abs();
+        let value = match self {
+            OutOfRange::Fold if octave <= 1.0 && value < 0.0 => value + octave * (-value / octave).ceil(),
+            OutOfRange::Fold if octave <= 1.0 && value > 1.0 => value - octave * ((value - 1.0) / octave).ceil(),
+            OutOfRange::Wrap if value.is_finite() => value.rem_euclid(1.0),
+            _ => value,
+        };
+        Range::UNIT.clamp(value)
+    }
+}
+
+/// Where events are placed, relative to the hop that caused them. Events can't be moved to
+/// before the start of the block being processed though, so with the plugin, back-dating is
+/// limited to the host's block size.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum EventTiming {
+    /// When the hop completed, which is the earliest we know about it
+    #[cfg_attr(feature = "plugin", name = "Hop end")]
+    HopEnd,
+    /// When the hop started
+    #[cfg_attr(feature = "plugin", name = "Hop start")]
+    HopStart,
+    /// Back-dated by the analysis latency, so that recorded MIDI lines up with the transients
+    #[cfg_attr(feature = "plugin", name = "Compensated")]
+    Compensated,
+}
+
+/// What the pitch bend of a channel does once the note it was bending ends (or moves to another
+/// channel). Receivers differ in whether they apply bends to the release of a note, and thus in
+/// which of these avoids audible chirps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum BendReturn {
+    /// Stays where it was, until the next note on the channel sets it
+    #[cfg_attr(feature = "plugin", name = "Hold")]
+    Hold,
+    /// Goes back to the center along with the NoteOff
+    #[cfg_attr(feature = "plugin", name = "Snap")]
+    Snap,
+    /// Goes back to the center over the bend return time
+    #[cfg_attr(feature = "plugin", name = "Ramp")]
+    Ramp,
+}
+
+/// Everything that drives the engine. The plugin fills this in from its parameters (see there
+/// for what each setting does), other applications can fill it in however they like.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct EngineSettings {
+    pub cc_lane:         CcLane,
+    pub pitch_unit:      PitchUnit,
+    pub out_of_range:    OutOfRange,
+    /// Between 0 (fast) and 1 (accurate)
+    pub speed_accuracy:  f32,
+    /// Whether the pitch smoothing weights each hop by the detector's confidence, so that
+    /// doubtful estimates hardly move the output while confident ones update it quickly
+    pub confidence_smoothing: bool,
+    /// In milliseconds, the smoothing time of the pitch bends (MPE and guitar mode) and note
+    /// expressions. The macro above smooths everything else, CC lanes included, but bends
+    /// usually want to stay tighter than that.
+    pub bend_smoothing:  f32,
+    pub bend_return:     BendReturn,
+    /// In milliseconds, for `BendReturn::Ramp`
+    pub bend_return_time: f32,
+    pub tracking_range:  TrackingRange,
+    pub pitch_method:    PitchMethod,
+    pub refinement:      bool,
+    /// Whether to correct detections an octave below the pitch
+    pub subharmonic_suppression: bool,
+    /// Only one hop in this many is analyzed, the output holding in between. This saves CPU in
+    /// sessions with many instances, at the cost of a coarser output.
+    pub decimation:      usize,
+    pub cc_stepped:      bool,
+    /// In cents
+    pub hysteresis:      f32,
+    /// What notes (and stepped CC) are quantized to
+    pub scale:           Scale,
+    /// When set, the scale follows the detected key instead
+    pub auto_key:        Option<AutoKey>,
+    /// Between 0 and 1, how far the continuous pitch outputs (CC lanes and bends) are pulled
+    /// towards the nearest semitone. Notes are always quantized fully.
+    pub correction:      f32,
+    /// When set, the crossover frequency (in Hz) splitting the pitch track: the slower drift
+    /// alone goes to the notes and CC lanes, while the bends also get the faster modulation on
+    /// top. Notes then stay put through the vibrato, which still comes out in the bends, and
+    /// pitch correction only pulls the drift.
+    pub vibrato_split:   Option<f32>,
+    /// When set, pitches further than this (in cents) from the scale's nearest note are treated
+    /// as unvoiced, which keeps slides and noise between the intended notes out of the output
+    pub scale_gate:      Option<f32>,
+    /// In milliseconds
+    pub bridge_time:     f32,
+    /// In milliseconds
+    pub hold_time:       f32,
+    pub min_note:        f32,
+    pub max_note:        f32,
+    pub min_freq:        f32,
+    pub max_freq:        f32,
+    /// In cents
+    pub cents_range:     f32,
+    pub cents_lane:      CcLane,
+    pub confidence_lane: CcLane,
+    pub note_enabled:    bool,
+    /// The lowest and highest notes sent, if filtered. The notes detected outside are dropped,
+    /// the CC lanes still follow them.
+    pub note_filter:     Option<(u8, u8)>,
+    /// Ignored in MPE mode. With a split, the main and cents lanes follow the notes to the
+    /// channel of their side.
+    pub note_channels:   ChannelAssignment,
+    pub velocity_source: VelocitySource,
+    /// Whether NoteOffs get a velocity following how fast the level was falling
+    pub release_velocity: bool,
+    /// Whether to follow the sustain pedal (CC64) given to `set_sustain()`
+    pub sustain_pedal:   bool,
+    pub mpe:             Option<MpeConfig>,
+    /// "MIDI guitar" mode, if enabled: the channel pitch bend follows the deviation from the
+    /// sounding note. Ignored in MPE mode, which bends each note already.
+    pub guitar:          Option<GuitarConfig>,
+    /// In MPE mode, whether to send the level as channel pressure on the sounding note's member
+    /// channel
+    pub mpe_pressure:    bool,
+    /// In MPE mode, whether to send the harmonic richness as the sounding note's brightness
+    /// (CC74 on its member channel)
+    pub mpe_brightness:  bool,
+    pub poly_pressure:   bool,
+    /// Level-based zones, which can each have their own note channel, velocity offset and
+    /// keyswitch, if enabled
+    pub dynamics:        Option<DynamicsConfig>,
+    /// Practice mode: how close the input is to the target note
+    pub accuracy_lane:   CcLane,
+    /// In cents, the deviation from the target at which accuracy reaches zero
+    pub accuracy_range:  f32,
+    pub event_timing:    EventTiming,
+    /// Whether to send per-note pitch as high-resolution note expressions, on top of the MIDI
+    /// 1.0 outputs. Only enable this when the host supports them.
+    pub high_resolution: bool,
+    pub note_priority:   NotePriority,
+    /// In milliseconds, how long detected notes are remembered as candidates for the priority
+    pub priority_window: f32,
+    /// Whether to send portamento on/off (CC65) and time (CC5) along with notes, following the
+    /// performer's slides
+    pub portamento:      bool,
+    pub trill_mode:      TrillMode,
+    /// The note held while a collapsed trill goes on
+    pub trill_keyswitch: u8,
+    /// The estimated vocal register, as a stepped CC going from chest to falsetto
+    pub register_lane:   CcLane,
+    /// The lowest of four consecutive keyswitches, one for each register, if they are enabled
+    pub register_keyswitches: Option<u8>,
+    /// The first two formants, each on a logarithmic scale over its usual range
+    pub f1_lane:         CcLane,
+    pub f2_lane:         CcLane,
+    /// Whether to subtract the background noise (learned while the input is unvoiced) before
+    /// analysis. This delays the analysis by a frame.
+    pub denoise:         bool,
+    pub gate_mode:       GateMode,
+    /// In dB, used in fixed mode, and in adaptive mode until the noise floor is known
+    pub gate_threshold:  f32,
+    /// In dB, how far above the noise floor the adaptive threshold stays
+    pub gate_margin:     f32,
+    /// Whether to send MIDI clock (with start and stop) following the beats detected in the
+    /// input. Tempo tracking only runs when this is on.
+    pub clock_output:    bool,
+    /// Which notes to send on each beat, if any
+    pub beat_notes:      Option<BeatNotes>,
+    /// Percussive mode: which notes to send for the transients in each band, if enabled. The
+    /// detected pitch then no longer makes notes, but still drives the CC lanes.
+    pub drums:           Option<DrumNotes>,
+    /// Whether to run the pitch detectors at all. Switching this off leaves only the level,
+    /// tempo and drum lanes, for when Aeolus is used for rhythm alone.
+    pub pitch_analysis:  bool,
+}
+
+/// Same as the plugin's parameter defaults
+impl Default for EngineSettings {
+    fn default() -> Self {
+        Self {
+            cc_lane: CcLane { enabled: true, number: 1, ..CcLane::default() },
+            pitch_unit: PitchUnit::MidiNote,
+            out_of_range: OutOfRange::Clamp,
+            speed_accuracy: 0.5,
+            confidence_smoothing: true,
+            bend_smoothing: 5.0,
+            bend_return: BendReturn::Hold,
+            bend_return_time: 50.0,
+            tracking_range: TrackingRange::Normal,
+            pitch_method: PitchMethod::Yin,
+            refinement: true,
+            subharmonic_suppression: false,
+            decimation: 1,
+            cc_stepped: false,
+            hysteresis: 15.0,
+            scale: Scale::default(),
+            auto_key: None,
+            correction: 0.0,
+            vibrato_split: None,
+            scale_gate: None,
+            bridge_time: 30.0,
+            hold_time: 0.0,
+            min_note: 57.0,
+            max_note: 81.0,
+            min_freq: 220.0,
+            max_freq: 880.0,
+            cents_range: 50.0,
+            cents_lane: CcLane { enabled: false, number: 2, ..CcLane::default() },
+            confidence_lane: CcLane { enabled: false, number: 3, ..CcLane::default() },
+            note_enabled: true,
+            note_filter: None,
+            note_channels: ChannelAssignment::Fixed(0),
+            velocity_source: VelocitySource::Fixed,
+            release_velocity: false,
+            sustain_pedal: true,
+            mpe: None,
+            guitar: None,
+            mpe_pressure: true,
+            mpe_brightness: false,
+            poly_pressure: false,
+            dynamics: None,
+            accuracy_lane: CcLane { enabled: false, number: 4, ..CcLane::default() },
+            accuracy_range: 50.0,
+            event_timing: EventTiming::HopEnd,
+            high_resolution: false,
+            note_priority: NotePriority::Last,
+            priority_window: 100.0,
+            portamento: false,
+            trill_mode: TrillMode::Faithful,
+            trill_keyswitch: 24,
+            register_lane: CcLane { enabled: false, number: 9, ..CcLane::default() },
+            register_keyswitches: None,
+            f1_lane: CcLane { enabled: false, number: 14, ..CcLane::default() },
+            f2_lane: CcLane { enabled: false, number: 15, ..CcLane::default() },
+            denoise: false,
+            gate_mode: GateMode::Off,
+            gate_threshold: -50.0,
+            gate_margin: 10.0,
+            clock_output: false,
+            beat_notes: None,
+            drums: None,
+            pitch_analysis: true,
+        }
+    }
+}
+
+impl EngineSettings {
+    // The analysis lanes besides pitch detection are only run when one of their outputs is
+    // enabled, so that each lane costs nothing until it's used
+
+    pub fn register_analysis(&self) -> bool {
+        self.register_lane.enabled || self.register_keyswitches.is_some()
+    }
+
+    /// The spectral balance feeds both the register estimate and MPE brightness
+    pub fn balance_analysis(&self) -> bool {
+        self.register_analysis() || (self.mpe.is_some() && self.mpe_brightness)
+    }
+
+    pub fn formant_analysis(&self) -> bool {
+        self.f1_lane.enabled || self.f2_lane.enabled
+    }
+
+    pub fn tempo_analysis(&self) -> bool {
+        self.clock_output || self.beat_notes.is_some()
+    }
+
+    pub fn speed(&self) -> AnalysisSpeed {
+        AnalysisSpeed::from_macro(self.speed_accuracy)
+    }
+
+    /// Whether going from `previous` to these settings changes where notes go or what makes
+    /// them, in which case the sounding note has to be closed first, or it may never be. Beat
+    /// and drum notes close themselves after a fixed length, wherever they went.
+    pub fn reroutes_notes(&self, previous: &EngineSettings) -> bool {
+        self.note_enabled != previous.note_enabled
+            || self.channel_assignment() != previous.channel_assignment()
+            || self.mpe != previous.mpe
+            || self.drums.is_some() != previous.drums.is_some()
+            || self.trill_mode != previous.trill_mode
+            || self.trill_keyswitch != previous.trill_keyswitch
+            || self.tracking_range != previous.tracking_range
+            || self.pitch_analysis != previous.pitch_analysis
+    }
+
+    /// The guitar mode's configuration, unless MPE takes over the pitch bend
+    pub fn guitar_config(&self) -> Option<GuitarConfig> {
+        self.guitar.filter(|_| self.mpe.is_none())
+    }
+
+    /// In MPE mode, notes are spread over the member channels of the zone
+    pub fn channel_assignment(&self) -> ChannelAssignment {
+        if let Some(config) = self.mpe {
+            let (first, last) = config.member_range();
+            ChannelAssignment::RoundRobin(first, last)
+        } else {
+            self.note_channels
+        }
+    }
+}
+
+/// Glides the continuous analysis settings from hop to hop, see `Engine::update_analysis()`
+#[derive(Default)]
+struct SettingGlides {
+    hysteresis:     PitchSmoother,
+    scale_gate:     PitchSmoother,
+    gate_threshold: PitchSmoother,
+    gate_margin:    PitchSmoother,
+}
+
+impl SettingGlides {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The whole audio-to-MIDI pipeline, independent of nih_plug's plugin machinery: samples go in,
+/// events come out. This is what the plugin runs, and what tests and other applications can
+/// drive directly with `analyze_block()`.
+pub struct Engine {
+    /// The settings in effect, see `set_settings()`
+    settings: EngineSettings,
+    /// The settings last given to `set_settings()`, which the analysis settings are taken from
+    /// at the next hop
+    requested: EngineSettings,
+    setting_glides: SettingGlides,
+    /// Whether the analyzers were fed since their last hop
+    hop_pending: bool,
+    analyzers: AnalyzerBank,
+    quantizer: SemitoneQuantizer,
+    /// The MPE configuration that was last sent to the receiver, if any
+    sent_mpe_config: Option<MpeConfig>,
+    /// The guitar mode's bend range that was last sent, and the channels it was sent on
+    sent_guitar_config: Option<(GuitarConfig, ChannelAssignment)>,
+    /// Set when the settings changed in a way that needs the sounding notes closed, which
+    /// happens in the next `send_configuration()`
+    flush_pending: bool,
+    /// Set by `request_snapshot()`
+    snapshot_pending: bool,
+    dc_blocker: DcBlocker,
+    denoiser: Denoiser,
+    envelope: EnvelopeFollower,
+    sample_rate: f32,
+    /// How many samples have elapsed since the last voiced hop
+    unvoiced_samples: usize,
+    /// How long the input has been voiced for, while the first windows of a note are skipped
+    onset_samples: usize,
+    /// How many samples have elapsed since the input was last above `IDLE_LEVEL`
+    quiet_samples: usize,
+    noise_floor: NoiseFloor,
+    /// Holds the quantized pitch (used by notes and stepped CC) after each change
+    note_hold: Hold<f32>,
+    pitch_smoother: PitchSmoother,
+    /// Bends (and note expressions) have a smoothing time of their own
+    bend_smoother: PitchSmoother,
+    vibrato: VibratoSplitter,
+    balance: SpectralBalance,
+    formants: FormantTracker,
+    /// Follows the spectral flux, for note velocities
+    flux: SpectralFlux,
+    /// Follows how fast the envelope falls, for release velocities
+    decay: DecayRate,
+    /// Only created in `initialize()`
+    tempo: Option<TempoTracker>,
+    clock: MidiClock,
+    beat_trigger: BeatTrigger,
+    drums: DrumTrigger,
+    /// Practice mode: the note the performer is supposed to play
+    target: Option<u8>,
+    /// Practice mode: how far (in cents) the last voiced hop was from the target
+    target_deviation: Option<f32>,
+    key_detector: KeyDetector,
+    /// The key the quantizer follows in auto-key mode, and how many samples ago it was adopted
+    detected_key: Option<Key>,
+    since_key_change: usize,
+    /// What the analysis stages published, until it's dispatched to the generators
+    bus: EventQueue,
+    /// The sounding note, the CC lanes' state and the bends, shared by the built-in outputs
+    outputs: OutputState,
+    builtins: BuiltinOutputs,
+    /// The generators that were added, which run after the built-in ones
+    generators: Vec<Box<dyn OutputGenerator>>,
+    /// Whether the last of `Voiced` and `Unvoiced` that was published is `Voiced`
+    voiced: bool,
+}
+
+/// The analyzers are only created in `initialize()`, so this doesn't allocate much
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            settings: EngineSettings::default(),
+            requested: EngineSettings::default(),
+            setting_glides: SettingGlides::default(),
+            hop_pending: false,
+            analyzers: AnalyzerBank::default(),
+            quantizer: SemitoneQuantizer::default(),
+            sent_mpe_config: None,
+            sent_guitar_config: None,
+            flush_pending: false,
+            snapshot_pending: false,
+            dc_blocker: DcBlocker::new(SAMPLE_RATE),
+            denoiser: Denoiser::new(SAMPLE_RATE),
+            envelope: EnvelopeFollower::new(SAMPLE_RATE, ENVELOPE_ATTACK_MS, ENVELOPE_RELEASE_MS),
+            sample_rate: SAMPLE_RATE,
+            unvoiced_samples: 0,
+            onset_samples: 0,
+            quiet_samples: 0,
+            noise_floor: NoiseFloor::default(),
+            note_hold: Hold::default(),
+            pitch_smoother: PitchSmoother::default(),
+            bend_smoother: PitchSmoother::default(),
+            vibrato: VibratoSplitter::default(),
+            balance: SpectralBalance::new(SAMPLE_RATE),
+            formants: FormantTracker::new(SAMPLE_RATE),
+            flux: SpectralFlux::new(SAMPLE_RATE),
+            decay: DecayRate::new(SAMPLE_RATE),
+            tempo: None,
+            clock: MidiClock::default(),
+            beat_trigger: BeatTrigger::default(),
+            drums: DrumTrigger::new(SAMPLE_RATE),
+            target: None,
+            target_deviation: None,
+            key_detector: KeyDetector::default(),
+            detected_key: None,
+            since_key_change: usize::MAX,
+            bus: EventQueue::default(),
+            outputs: OutputState::default(),
+            builtins: BuiltinOutputs::default(),
+            generators: Vec::new(),
+            voiced: false,
+        }
+    }
+}
+
+impl Engine {
+    pub fn new(sample_rate: f32, quality: AnalysisQuality) -> Self {
+        let mut engine = Self::default();
+        engine.initialize(sample_rate, quality);
+        engine
+    }
+
+    /// (Re)creates the analyzers if needed. This allocates.
+    pub fn initialize(&mut self, sample_rate: f32, quality: AnalysisQuality) {
+        self.sample_rate = sample_rate;
+        self.dc_blocker.set_sample_rate(sample_rate);
+        // Its frame size depends on the sample rate, so the noise profile has to be learned again
+        self.denoiser = Denoiser::new(sample_rate);
+        self.balance.set_sample_rate(sample_rate);
+        self.formants.set_sample_rate(sample_rate);
+        self.flux = SpectralFlux::new(sample_rate);
+        self.decay.set_sample_rate(sample_rate);
+        self.tempo = Some(TempoTracker::new(sample_rate));
+        self.clock = MidiClock::default();
+        self.drums.set_sample_rate(sample_rate);
+        self.envelope.set_times(sample_rate, ENVELOPE_ATTACK_MS, ENVELOPE_RELEASE_MS);
+        // Rebuilt every time, even if only the sample rate or block size changed: aubio objects
+        // can't be reset, and whatever they still held would make the output depend on what was
+        // processed before. The old ones are freed as they're dropped here.
+        self.analyzers = AnalyzerBank::new(quality);
+        self.analyzers.set_host_rate(sample_rate);
+        self.analyzers.set_method(self.settings.pitch_method);
+        self.analyzers.set_refinement(self.settings.refinement);
+        self.analyzers.set_subharmonic_suppression(self.settings.subharmonic_suppression);
+        self.analyzers.set_decimation(self.settings.decimation);
+    }
+
+    pub fn quality(&self) -> AnalysisQuality {
+        self.analyzers.quality()
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn settings(&self) -> &EngineSettings {
+        &self.settings
+    }
+
+    /// Everything but the analysis settings takes effect right away. Those only change between
+    /// hops, so that hosts automating them don't make a hop half-analyzed with the old ones: the
+    /// range, speed, method, refinement, subharmonic suppression and decimation switch at the
+    /// next hop, while the hysteresis and gate thresholds glide there over `SETTING_GLIDE_MS`.
+    pub fn set_settings(&mut self, settings: EngineSettings) {
+        self.requested = settings;
+        let previous = self.settings;
+        self.settings = EngineSettings {
+            tracking_range: previous.tracking_range,
+            speed_accuracy: previous.speed_accuracy,
+            pitch_method: previous.pitch_method,
+            refinement: previous.refinement,
+            subharmonic_suppression: previous.subharmonic_suppression,
+            decimation: previous.decimation,
+            hysteresis: previous.hysteresis,
+            scale_gate: previous.scale_gate,
+            gate_threshold: previous.gate_threshold,
+            gate_margin: previous.gate_margin,
+            ..settings
+        };
+        self.flush_pending |= self.settings.reroutes_notes(&previous);
+        // Analyzers that aren't being fed have no hop to get inconsistent
+        if !self.hop_pending || !self.settings.pitch_analysis || self.is_idle() {
+            self.update_analysis(None);
+        }
+    }
+
+    /// Takes the analysis settings over from the requested ones. `hop_ms` is the length of the
+    /// hop that just ended, if this happens at a hop boundary: the thresholds only glide then.
+    fn update_analysis(&mut self, hop_ms: Option<f32>) {
+        let (requested, glides) = (self.requested, &mut self.setting_glides);
+        // With nothing to glide from (after a reset), the requested values are passed through
+        let glide = |smoother: &mut PitchSmoother, value| smoother.process(value, hop_ms.unwrap_or(0.0), SETTING_GLIDE_MS);
+        if requested.scale_gate.is_none() {
+            glides.scale_gate.reset();
+        }
+        let previous = self.settings;
+        self.settings = EngineSettings {
+            tracking_range: requested.tracking_range,
+            speed_accuracy: requested.speed_accuracy,
+            pitch_method: requested.pitch_method,
+            refinement: requested.refinement,
+            subharmonic_suppression: requested.subharmonic_suppression,
+            decimation: requested.decimation,
+            hysteresis: glide(&mut glides.hysteresis, requested.hysteresis),
+            scale_gate: requested.scale_gate.map(|distance| glide(&mut glides.scale_gate, distance)),
+            gate_threshold: glide(&mut glides.gate_threshold, requested.gate_threshold),
+            gate_margin: glide(&mut glides.gate_margin, requested.gate_margin),
+            ..previous
+        };
+        self.flush_pending |= self.settings.reroutes_notes(&previous);
+        self.analyzers.set_method(self.settings.pitch_method);
+        self.analyzers.set_refinement(self.settings.refinement);
+        self.analyzers.set_subharmonic_suppression(self.settings.subharmonic_suppression);
+        self.analyzers.set_decimation(self.settings.decimation);
+    }
+
+    /// The delay (in samples) between a change in the input and its detection.
+    ///
+    /// Only the window depends on the Fast/Accurate macro here, as the smoothing and
+    /// confirmation times change continuously, and hosts don't like latency changing all the time
+    pub fn latency(&self) -> u32 {
+        let window = self.settings.tracking_range
+            .analyzer_config(self.analyzers.quality(), self.settings.speed())
+            .host_buffer_size(self.sample_rate) as u32;
+        // The conversion to the analysis rate delays it a little more
+        let window = window + rate_conversion_latency(self.sample_rate) as u32;
+        if self.settings.denoise {
+            window + self.denoiser.latency()
+        } else {
+            window
+        }
+    }
+
+    /// How long (in samples) the input has to be voiced before the estimates are trusted: half
+    /// of the current window
+    fn onset_window(&self) -> usize {
+        self.settings.tracking_range
+            .analyzer_config(self.analyzers.quality(), self.settings.speed())
+            .host_buffer_size(self.sample_rate)
+            / 2
+    }
+
+    /// The most `latency()` can be with any settings, for sizing buffers beforehand
+    pub fn max_latency(&self) -> u32 {
+        let window = max_host_buffer_size(self.analyzers.quality(), self.sample_rate) as u32;
+        window + rate_conversion_latency(self.sample_rate) as u32 + self.denoiser.latency()
+    }
+
+    /// How many times per second the pitch outputs are updated
+    pub fn update_rate(&self) -> f32 {
+        let hop_size = self.settings.tracking_range
+            .analyzer_config(self.analyzers.quality(), self.settings.speed())
+            .host_hop_size(self.sample_rate);
+        self.sample_rate / (hop_size * self.settings.decimation.max(1)) as f32
+    }
+
+    pub fn reset(&mut self) {
+        self.analyzers.reset();
+        self.hop_pending = false;
+        self.setting_glides.reset();
+        self.update_analysis(None);
+        self.quantizer.reset();
+        self.sent_mpe_config = None;
+        self.sent_guitar_config = None;
+        self.dc_blocker.reset();
+        self.denoiser.reset();
+        self.balance.reset();
+        self.formants.reset();
+        self.flux.reset();
+        self.decay.reset();
+        if let Some(tempo) = &mut self.tempo {
+            tempo.reset();
+        }
+        self.clock = MidiClock::default();
+        self.beat_trigger = BeatTrigger::default();
+        self.drums.reset();
+        self.envelope.reset();
+        self.unvoiced_samples = 0;
+        self.onset_samples = 0;
+        self.quiet_samples = 0;
+        self.noise_floor.reset();
+        self.note_hold.reset();
+        self.pitch_smoother.reset();
+        self.bend_smoother.reset();
+        self.vibrato.reset();
+        self.target = None;
+        self.target_deviation = None;
+        self.key_detector.reset();
+        self.detected_key = None;
+        self.since_key_change = usize::MAX;
+        self.bus.clear();
+        self.voiced = false;
+        self.outputs.reset();
+        for generator in self.builtins.generators() {
+            generator.reset();
+        }
+        for generator in &mut self.generators {
+            generator.reset();
+        }
+        // We could manually feed as many zeroes as needed to the analyzers to flush their
+        // windows, but I don't think it's worth the hassle, so we don't do anything about that.
+    }
+
+    /// Closes the sounding note (if any), and those the sustain pedal holds, and starts over, as
+    /// if the input had gone silent for good. Unlike `reset()`, this can send events.
+    pub fn release(&mut self, sink: &mut impl EventSink, timing: u32) {
+        let mut context = OutputContext::new(&self.settings, self.sample_rate, &mut self.outputs);
+        for generator in self.builtins.generators() {
+            generator.release(sink, &mut context, timing);
+        }
+        for generator in &mut self.generators {
+            generator.release(sink, &mut context, timing);
+        }
+        self.outputs.notes.release_sustained(sink, timing);
+        self.clock.stop(sink, timing);
+        self.beat_trigger.release(sink, timing);
+        self.drums.release(sink, timing);
+        self.analyzers.reset();
+        self.quantizer.reset();
+        self.unvoiced_samples = 0;
+        self.onset_samples = 0;
+        self.note_hold.reset();
+        self.pitch_smoother.reset();
+        self.bend_smoother.reset();
+        self.target_deviation = None;
+        self.bus.clear();
+        self.voiced = false;
+    }
+
+    /// Closes the sounding and sustained notes, for switching modes or inputs without leaving
+    /// them stuck. Unlike `release()`, this keeps the analysis (and the clock) going, so the
+    /// output picks up again at the next hop.
+    pub fn flush(&mut self, sink: &mut impl EventSink, timing: u32) {
+        let mut context = OutputContext::new(&self.settings, self.sample_rate, &mut self.outputs);
+        self.builtins.notes.flush(sink, &mut context, timing);
+        self.outputs.notes.release_sustained(sink, timing);
+        self.flush_pending = false;
+    }
+
+    /// Stops everything we may have left hanging downstream: closes the sounding note, sends All
+    /// Notes Off and centers the pitch bend on every channel, and brings the enabled CC lanes
+    /// back to their resting values
+    pub fn panic(&mut self, sink: &mut impl EventSink, timing: u32) {
+        self.release(sink, timing);
+        for channel in 0..16 {
+            send_all_notes_off(sink, timing, channel);
+            sink.send(AeolusEvent::PitchBend { timing, channel, value: 0.5 });
+        }
+        self.outputs.last_bend = None;
+        self.outputs.bend_ramp = None;
+
+        // Bipolar lanes rest in the middle, the others at the bottom
+        let pitch_rest = if self.settings.pitch_unit == PitchUnit::Cents { 0.5 } else { 0.0 };
+        let rests = [pitch_rest, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0];
+        for ((lane, state), rest) in self.cc_lanes().into_iter().zip(rests) {
+            if lane.enabled {
+                lane.send(state, sink, timing, rest);
+            }
+        }
+    }
+
+    /// Every CC lane, along with its state: pitch, cents, confidence, accuracy, register, then
+    /// the formants
+    fn cc_lanes(&mut self) -> [(CcLane, &mut LaneState); 7] {
+        let settings = self.settings;
+        let lanes = &mut self.outputs.lanes;
+        [
+            (settings.cc_lane, &mut lanes.pitch),
+            (settings.cents_lane, &mut lanes.cents),
+            (settings.confidence_lane, &mut lanes.confidence),
+            (settings.accuracy_lane, &mut lanes.accuracy),
+            (settings.register_lane, &mut lanes.register),
+            (settings.f1_lane, &mut lanes.f1),
+            (settings.f2_lane, &mut lanes.f2),
+        ]
+    }
+
+    /// Sends the lanes' unvoiced values, for the lanes that have one. The editor doesn't offer
+    /// one for the confidence lane, which keeps following the detector anyway.
+    fn send_unvoiced_values(&mut self, sink: &mut impl EventSink, timing: u32) {
+        for (lane, state) in self.cc_lanes() {
+            if lane.enabled {
+                lane.send_unvoiced(state, sink, timing);
+            }
+        }
+    }
+
+    /// Closes the sounding note, and the glide towards it if there was one
+    fn release_note(&mut self, sink: &mut impl EventSink, timing: u32) {
+        let mut context = OutputContext::new(&self.settings, self.sample_rate, &mut self.outputs);
+        self.builtins.notes.release_note(sink, &mut context, timing);
+    }
+
+    /// Makes the next `send_configuration()` call send the MPE and guitar mode configurations
+    /// again
+    pub fn resend_configuration(&mut self) {
+        self.sent_mpe_config = None;
+        self.sent_guitar_config = None;
+    }
+
+    /// Makes the next `send_configuration()` call send a snapshot of our state: the
+    /// configurations, then the last value of every CC lane and the sounding note's bend. That
+    /// way, external gear is put in a consistent state without waiting for the next note.
+    pub fn request_snapshot(&mut self) {
+        self.snapshot_pending = true;
+    }
+
+    /// (Re)configures MPE receivers whenever MPE gets activated or its settings change. This
+    /// includes the pitch bend sensitivity (RPN 0) of every member channel, which is what
+    /// bend-based output relies on. The guitar mode's bend range is sent likewise. Before that,
+    /// the sounding note is closed if the settings changed where it should go (see
+    /// `EngineSettings::reroutes_notes()`).
+    pub fn send_configuration(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if self.flush_pending {
+            self.flush(sink, timing);
+        }
+        let snapshot = std::mem::take(&mut self.snapshot_pending);
+        if snapshot {
+            self.resend_configuration();
+        }
+        if !self.settings.sustain_pedal {
+            self.outputs.notes.set_sustain(sink, timing, false);
+        }
+        if self.settings.mpe != self.sent_mpe_config {
+            if let Some(config) = self.settings.mpe {
+                config.send_configuration(sink, timing);
+            }
+            self.sent_mpe_config = self.settings.mpe;
+        }
+        // The guitar mode configures the channels notes go to the same way, and leaves their
+        // bend centered when it's switched off
+        let guitar_config = self.settings.guitar_config().map(|config| (config, self.settings.channel_assignment()));
+        if guitar_config != self.sent_guitar_config {
+            if let Some((_, channels)) = self.sent_guitar_config {
+                center_bends(sink, timing, channels);
+            }
+            if let Some((config, channels)) = guitar_config {
+                config.send_configuration(sink, timing, channels);
+            }
+            self.sent_guitar_config = guitar_config;
+        }
+        if snapshot {
+            for (lane, state) in self.cc_lanes() {
+                if lane.enabled {
+                    lane.resend(state, sink, timing);
+                }
+            }
+            if let Some((channel, value)) = self.outputs.last_bend {
+                sink.send(AeolusEvent::PitchBend { timing, channel, value });
+            }
+        }
+    }
+
+    /// The sustain pedal, from the MIDI input: while it's held, notes that end keep sounding
+    /// until it's released, as on a keyboard
+    pub fn set_sustain(&mut self, sink: &mut impl EventSink, timing: u32, held: bool) {
+        self.outputs.notes.set_sustain(sink, timing, held && self.settings.sustain_pedal);
+    }
+
+    /// Practice mode: sets the note the input is compared to, if any
+    pub fn set_target(&mut self, target: Option<u8>) {
+        self.target = target;
+        if target.is_none() {
+            self.target_deviation = None;
+        }
+    }
+
+    pub fn target(&self) -> Option<u8> {
+        self.target
+    }
+
+    /// Practice mode: how far (in cents) the input currently is from the target, if there is a
+    /// target and the input is voiced
+    pub fn target_deviation(&self) -> Option<f32> {
+        self.target_deviation
+    }
+
+    /// The key auto-key mode settled on, if it did
+    pub fn detected_key(&self) -> Option<Key> {
+        self.detected_key
+    }
+
+    /// What the quantizer snaps to: the detected key in auto-key mode, the chosen scale otherwise
+    fn scale(&self) -> Scale {
+        match (self.settings.auto_key, self.detected_key) {
+            (Some(_), Some(key)) => key.scale(),
+            _ => self.settings.scale,
+        }
+    }
+
+    /// Feeds the key detector, and moves on to the key it suggests if it is confident enough and
+    /// the current key was kept long enough
+    fn follow_key(&mut self, pitch: f32, hop_ms: f32, auto_key: AutoKey) {
+        self.key_detector.push(pitch, hop_ms);
+        let min_interval = (auto_key.min_interval * self.sample_rate) as usize;
+        match self.key_detector.estimate() {
+            Some((key, confidence))
+                if confidence >= auto_key.confidence
+                    && Some(key) != self.detected_key
+                    && (self.detected_key.is_none() || self.since_key_change >= min_interval) =>
+            {
+                self.detected_key = Some(key);
+                self.since_key_change = 0;
+            }
+            _ => (),
+        }
+    }
+
+    /// Removes DC (and noise, if enabled), and updates the input level and the other per-sample
+    /// trackers. This is the first step of `process_sample()`, for when the analysis happens
+    /// elsewhere: the returned sample is what is to be analyzed.
+    pub fn condition(&mut self, sample: f32) -> f32 {
+        let sample = self.dc_blocker.process(sample);
+        if self.envelope.process(sample) < IDLE_LEVEL {
+            self.quiet_samples = self.quiet_samples.saturating_add(1);
+        } else {
+            self.quiet_samples = 0;
+        }
+        // Whatever quiet sound comes in while there's nothing to track is taken to be noise. The
+        // level condition keeps notes the detector lost from being learned as noise.
+        let sample = if self.settings.denoise {
+            let learn = self.unvoiced_samples > 0 && self.envelope.level() < DENOISE_LEARNING_LEVEL;
+            self.denoiser.process(sample, learn)
+        } else {
+            sample
+        };
+        if self.settings.balance_analysis() {
+            self.balance.process(sample);
+        }
+        if self.settings.formant_analysis() {
+            self.formants.push(sample);
+        }
+        if self.settings.velocity_source == VelocitySource::SpectralFlux {
+            self.flux.push(sample);
+        }
+        let release = if self.settings.release_velocity {
+            release_velocity(self.decay.process(self.envelope.level()))
+        } else {
+            0.0
+        };
+        self.outputs.notes.set_release_velocity(release);
+        sample
+    }
+
+    /// Follows the beats and transients of the (conditioned) input, and sends MIDI clock, beat
+    /// notes and drum notes accordingly. This is part of `process_sample()`, for when the
+    /// analysis happens elsewhere.
+    pub fn track_rhythm(&mut self, sink: &mut impl EventSink, timing: u32, sample: f32) {
+        let settings = self.settings;
+        let idle = self.is_idle();
+        let transient = match settings.drums {
+            Some(notes) => self.drums.process(sink, timing, sample, notes),
+            None => {
+                self.drums.release(sink, timing);
+                None
+            }
+        };
+        if let Some(transient) = transient {
+            let timing = timing.saturating_sub(transient.delay as u32);
+            self.bus.publish(AnalysisEvent::Onset { timing, velocity: transient.velocity });
+        }
+        if !settings.clock_output {
+            self.clock.stop(sink, timing);
+        }
+        if settings.beat_notes.is_none() {
+            self.beat_trigger.release(sink, timing);
+        }
+        if let Some(tempo) = self.tempo.as_mut().filter(|_| settings.tempo_analysis()) {
+            let beat = if idle { None } else { tempo.push(sample) };
+            if settings.clock_output {
+                self.clock.process(sink, timing, beat, tempo.bpm(), self.sample_rate);
+            }
+            if let Some(notes) = settings.beat_notes {
+                self.beat_trigger.process(sink, timing, beat, notes, self.sample_rate);
+            }
+            if let Some(beat) = beat {
+                let timing = timing.saturating_sub(beat.delay as u32);
+                self.bus.publish(AnalysisEvent::Beat { timing, strength: beat.strength, bpm: tempo.bpm() });
+            }
+        }
+        self.dispatch(sink);
+    }
+
+    /// Adds an output generator, which gets every analysis event from then on. This allocates,
+    /// so do it before processing starts.
+    pub fn add_generator(&mut self, generator: Box<dyn OutputGenerator>) {
+        self.generators.push(generator);
+    }
+
+    /// The detected tempo (in BPM), if the tempo is tracked and enough beats were heard
+    pub fn tempo(&self) -> Option<f32> {
+        self.tempo.as_ref().and_then(TempoTracker::bpm).filter(|_| self.settings.tempo_analysis())
+    }
+
+    /// Whether the input has been silent for a while, with nothing left sounding. The detectors
+    /// and the tempo tracker don't run then, so that idle instances cost next to nothing.
+    pub fn is_idle(&self) -> bool {
+        self.quiet_samples as f32 > IDLE_MS / 1000.0 * self.sample_rate
+            && self.outputs.notes.current().is_none()
+            && !self.clock.is_running()
+    }
+
+    /// The input level, as followed by the envelope
+    pub fn level(&self) -> f32 {
+        self.envelope.level()
+    }
+
+    /// Runs one sample through the whole pipeline. Returns the detection made and the hop size
+    /// (in samples), if this sample completed a hop.
+    pub fn process_sample(
+        &mut self,
+        sink:   &mut impl EventSink,
+        timing: u32,
+        sample: f32,
+    ) -> Option<(Detection, usize)> {
+        let sample = self.condition(sample);
+        self.track_rhythm(sink, timing, sample);
+        if !self.settings.pitch_analysis {
+            self.release_note(sink, timing);
+            return None;
+        }
+        if self.is_idle() {
+            return None;
+        }
+        let (range, speed) = (self.settings.tracking_range, self.settings.speed());
+        let pushed = self.analyzers.push(sample, range, speed);
+        self.hop_pending = pushed.is_none();
+        let (detection, hop_size) = pushed?;
+        self.handle_detection(sink, timing, detection, hop_size);
+        Some((detection, hop_size))
+    }
+
+    /// Runs a block of samples through the whole pipeline, and returns the resulting events. Their
+    /// timings are relative to the start of the block. This allocates, so it is meant for offline
+    /// use, not for the audio thread.
+    pub fn analyze_block(&mut self, samples: &[f32]) -> Vec<AeolusEvent> {
+        let mut events = Vec::new();
+        self.send_configuration(&mut events, 0);
+        for (index, &sample) in samples.iter().enumerate() {
+            self.process_sample(&mut events, index as u32, sample);
+        }
+        events
+    }
+
+    /// Sends the events resulting from one hop's analysis
+    pub fn handle_detection(
+        &mut self,
+        sink:      &mut impl EventSink,
+        timing:    u32,
+        detection: Detection,
+        hop_size:  usize,
+    ) {
+        self.update_analysis(Some(hop_size as f32 / self.sample_rate * 1000.0));
+        let frame = PitchFrame::new(detection, self.envelope.level());
+        let back_date = match self.settings.event_timing {
+            EventTiming::HopEnd      => 0,
+            EventTiming::HopStart    => hop_size as u32,
+            EventTiming::Compensated => self.latency(),
+        };
+        let timing = timing.saturating_sub(back_date);
+        self.bus.publish(AnalysisEvent::Hop { timing, hop_size });
+        self.note_hold.advance(hop_size);
+        self.outputs.advance_bend_ramp(sink, &self.settings, self.sample_rate, timing, hop_size);
+        self.since_key_change = self.since_key_change.saturating_add(hop_size);
+
+        if self.settings.confidence_lane.enabled {
+            let hop_ms = hop_size as f32 / self.sample_rate * 1000.0;
+            self.settings.confidence_lane.send_smoothed(&mut self.outputs.lanes.confidence, sink, timing, frame.confidence, hop_ms);
+        }
+
+        // The noise floor is learned from what the detector considers unvoiced, not from what
+        // the gate closes on, lest it creep up towards the level of the notes
+        let level = frame.level_db();
+        if !frame.voiced {
+            self.noise_floor.update(level, hop_size as f32 / self.sample_rate * 1000.0);
+        }
+        let threshold = match self.settings.gate_mode {
+            GateMode::Off      => None,
+            GateMode::Fixed    => Some(self.settings.gate_threshold),
+            GateMode::Adaptive => Some(self.noise_floor.level()
+                .map_or(self.settings.gate_threshold, |floor| floor + self.settings.gate_margin)),
+        };
+        let frame = if matches!(threshold, Some(threshold) if level < threshold) { frame.gated() } else { frame };
+        let frame = match self.settings.scale_gate {
+            Some(distance) if frame.voiced && !self.in_scale(frame.frequency, distance) => frame.gated(),
+            _ => frame,
+        };
+
+        if !frame.voiced {
+            let bridge_samples = self.settings.bridge_time / 1000.0 * self.sample_rate;
+            let was_voiced = self.unvoiced_samples as f32 <= bridge_samples;
+            self.unvoiced_samples += hop_size;
+            if self.unvoiced_samples as f32 > bridge_samples {
+                if was_voiced {
+                    self.send_unvoiced_values(sink, timing);
+                }
+                if self.voiced {
+                    self.bus.publish(AnalysisEvent::Unvoiced { timing });
+                    self.voiced = false;
+                }
+                self.onset_samples = 0;
+                self.quantizer.reset();
+                self.note_hold.reset();
+                self.pitch_smoother.reset();
+                self.bend_smoother.reset();
+                self.vibrato.reset();
+                self.outputs.lanes.reset_smoothing();
+                self.target_deviation = None;
+            }
+        } else {
+            self.unvoiced_samples = 0;
+            if !self.voiced {
+                // The first windows of a note still mostly hold the silence before it, which
+                // throws the detectors off (often by an octave) until the newer half is filled
+                self.onset_samples += hop_size;
+                if self.onset_samples < self.onset_window() {
+                    self.dispatch(sink);
+                    return;
+                }
+                self.onset_samples = 0;
+                self.bus.publish(AnalysisEvent::Voiced { timing });
+                self.voiced = true;
+            }
+            let estimate = self.estimate_pitch(frame, hop_size);
+            self.bus.publish(AnalysisEvent::PitchChanged { timing, estimate });
+        }
+        self.dispatch(sink);
+    }
+
+    /// Whether the (raw) detected frequency is within `distance` cents of a note of the scale
+    fn in_scale(&self, frequency: f32, distance: f32) -> bool {
+        let pitch = freq_to_midi(frequency);
+        (pitch - self.scale().nearest(pitch)).abs() * 100.0 <= distance
+    }
+
+    /// In samples, how long the note hold lasts
+    fn hold_samples(&self) -> usize {
+        (self.settings.hold_time / 1000.0 * self.sample_rate) as usize
+    }
+
+    /// The pitch stage, for a newly detected (voiced) frame: smooths the pitch, follows the key
+    /// and quantizes
+    fn estimate_pitch(&mut self, frame: PitchFrame, hop_size: usize) -> PitchEstimate {
+        let settings = self.settings;
+        let accuracy = settings.speed_accuracy;
+        let smoothing_ms = MAX_SMOOTHING_MS * accuracy * accuracy;
+
+        let hop_ms = hop_size as f32 / self.sample_rate * 1000.0;
+        let raw_pitch = freq_to_midi(frame.frequency);
+        let (drift, vibrato) = match settings.vibrato_split {
+            Some(crossover) => self.vibrato.process(raw_pitch, hop_ms, crossover),
+            None            => (raw_pitch, 0.0),
+        };
+        let bend = self.bend_smoother.process(raw_pitch, hop_ms, settings.bend_smoothing);
+        let weight = if settings.confidence_smoothing { frame.confidence } else { 1.0 };
+        let pitch = self.pitch_smoother.process_weighted(drift, weight, hop_ms, smoothing_ms);
+        if let Some(auto_key) = settings.auto_key {
+            self.follow_key(pitch, hop_ms, auto_key);
+        }
+        let quantized = self.quantizer.quantize(pitch, settings.hysteresis / 100.0, self.scale());
+        // Partial correction: the continuous outputs are pulled towards the chosen semitone,
+        // which tightens the tuning while keeping what's left of the bends and vibrato
+        let correct = |pitch: f32| pitch + settings.correction * (quantized - pitch);
+        self.target_deviation = self.target.map(|target| 100.0 * (pitch - target as f32));
+        PitchEstimate {
+            frame,
+            pitch,
+            corrected: correct(pitch),
+            quantized: self.note_hold.process(quantized, self.hold_samples()),
+            bend: correct(bend - vibrato) + vibrato,
+            hop_ms,
+            richness: if settings.balance_analysis() { self.balance.richness(midi_to_freq(pitch)) } else { 1.0 },
+            formants: self.formants.formants().filter(|_| settings.formant_analysis()),
+            velocity: settings.velocity_source.velocity(frame.level, &self.flux),
+            deviation: self.target_deviation,
+        }
+    }
+
+    /// Hands what the analysis stages published over to the outputs: first the built-in
+    /// generators, then the ones that were added
+    fn dispatch(&mut self, sink: &mut impl EventSink) {
+        let mut context = OutputContext::new(&self.settings, self.sample_rate, &mut self.outputs);
+        while let Some(event) = self.bus.pop() {
+            for generator in self.builtins.generators() {
+                generator.handle(sink, &mut context, &event);
+            }
+            for generator in &mut self.generators {
+                generator.handle(sink, &mut context, &event);
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+/// A simple peak envelope follower, with separate attack and release times
+pub struct EnvelopeFollower {
+    level:         f32,
+    attack_coeff:  f32,
+    release_coeff: f32,
+}
+
+/// Coefficient of a one-pole filter reaching ~63% of its target after `time_ms`
+fn one_pole_coeff(sample_rate: f32, time_ms: f32) -> f32 {
+    if time_ms <= 0.0 {
+        0.0
+    } else {
+        (-1000.0 / (time_ms * sample_rate)).exp()
+    }
+}
+
+impl EnvelopeFollower {
+    pub fn new(sample_rate: f32, attack_ms: f32, release_ms: f32) -> Self {
+        let mut follower = Self {
+            level: 0.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+        };
+        follower.set_times(sample_rate, attack_ms, release_ms);
+        follower
+    }
+
+    pub fn set_times(&mut self, sample_rate: f32, attack_ms: f32, release_ms: f32) {
+        self.attack_coeff = one_pole_coeff(sample_rate, attack_ms);
+        self.release_coeff = one_pole_coeff(sample_rate, release_ms);
+    }
+
+    pub fn reset(&mut self) {
+        self.level = 0.0;
+    }
+
+    /// Feeds one sample to the follower, and returns the updated level
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let input = sample.abs();
+        let coeff = if input > self.level { self.attack_coeff } else { self.release_coeff };
+        self.level = input + coeff * (self.level - input);
+        self.level
+    }
+
+    /// The current level, as a linear gain
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+}
+
+/// Time constant over which the decay rate is averaged, which evens out the envelope's ripple
+const DECAY_AVERAGE_MS: f32 = 50.0;
+
+/// Follows how fast a level falls, in dB per second
+pub struct DecayRate {
+    sample_rate: f32,
+    coeff:       f32,
+    last_db:     f32,
+    rate:        f32,
+}
+
+impl DecayRate {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut decay = Self { sample_rate, coeff: 0.0, last_db: -180.0, rate: 0.0 };
+        decay.set_sample_rate(sample_rate);
+        decay
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.coeff = one_pole_coeff(sample_rate, DECAY_AVERAGE_MS);
+    }
+
+    pub fn reset(&mut self) {
+        self.last_db = -180.0;
+        self.rate = 0.0;
+    }
+
+    /// Feeds one sample's level (as a linear gain), and returns how fast it has been falling
+    /// lately. Rising levels count as no decay.
+    pub fn process(&mut self, level: f32) -> f32 {
+        let level_db = 20.0 * level.max(1e-9).log10();
+        let slope = (self.last_db - level_db) * self.sample_rate;
+        self.last_db = level_db;
+        self.rate = slope + self.coeff * (self.rate - slope);
+        self.rate.max(0.0)
+    }
+}
@@ -0,0 +1,138 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::*;
+#[cfg(feature = "plugin")]
+use std::marker::PhantomData;
+
+/// The events Aeolus produces, mirroring the subset of nih_plug's `NoteEvent` we use, so that the
+/// engine doesn't depend on a host. As in nih_plug, channels are 0-based and values normalized.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AeolusEvent {
+    NoteOn          { timing: u32, channel: u8, note: u8, velocity: f32 },
+    NoteOff         { timing: u32, channel: u8, note: u8, velocity: f32 },
+    ControlChange   { timing: u32, channel: u8, cc: u8, value: f32 },
+    PitchBend       { timing: u32, channel: u8, value: f32 },
+    ChannelPressure { timing: u32, channel: u8, pressure: f32 },
+    PolyPressure    { timing: u32, channel: u8, note: u8, pressure: f32 },
+    /// High-resolution per-note pitch offset (in semitones), for hosts with note expressions.
+    /// There's no MIDI 1.0 equivalent.
+    PolyTuning      { timing: u32, channel: u8, note: u8, tuning: f32 },
+    /// System realtime messages, for MIDI clock
+    Realtime        { timing: u32, message: RealtimeMessage },
+}
+
+/// The system realtime messages Aeolus sends
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RealtimeMessage {
+    Clock,
+    Start,
+    Stop,
+}
+
+impl RealtimeMessage {
+    pub fn status_byte(self) -> u8 {
+        match self {
+            RealtimeMessage::Clock => 0xF8,
+            RealtimeMessage::Start => 0xFA,
+            RealtimeMessage::Stop  => 0xFC,
+        }
+    }
+}
+
+/// nih_plug has no notion of realtime messages, but passes any `SysExMessage` through as raw
+/// bytes, which is enough to get them out. Some hosts drop anything that isn't a proper SysEx
+/// message though.
+#[cfg(feature = "plugin")]
+impl SysExMessage for RealtimeMessage {
+    type Buffer = [u8; 1];
+
+    fn from_buffer(buffer: &[u8]) -> Option<Self> {
+        match buffer {
+            [0xF8] => Some(RealtimeMessage::Clock),
+            [0xFA] => Some(RealtimeMessage::Start),
+            [0xFC] => Some(RealtimeMessage::Stop),
+            _ => None,
+        }
+    }
+
+    fn to_buffer(self) -> (Self::Buffer, usize) {
+        ([self.status_byte()], 1)
+    }
+}
+
+impl AeolusEvent {
+    /// Offset (in samples) from the start of the block the event was produced in
+    pub fn timing(&self) -> u32 {
+        match *self {
+            AeolusEvent::NoteOn { timing, .. }
+            | AeolusEvent::NoteOff { timing, .. }
+            | AeolusEvent::ControlChange { timing, .. }
+            | AeolusEvent::PitchBend { timing, .. }
+            | AeolusEvent::ChannelPressure { timing, .. }
+            | AeolusEvent::PolyPressure { timing, .. }
+            | AeolusEvent::PolyTuning { timing, .. }
+            | AeolusEvent::Realtime { timing, .. } => timing,
+        }
+    }
+
+    #[cfg(feature = "plugin")]
+    pub fn to_note_event(self) -> NoteEvent<RealtimeMessage> {
+        match self {
+            AeolusEvent::NoteOn { timing, channel, note, velocity } =>
+                NoteEvent::NoteOn { timing, voice_id: None, channel, note, velocity },
+            AeolusEvent::NoteOff { timing, channel, note, velocity } =>
+                NoteEvent::NoteOff { timing, voice_id: None, channel, note, velocity },
+            AeolusEvent::ControlChange { timing, channel, cc, value } =>
+                NoteEvent::MidiCC { timing, channel, cc, value },
+            AeolusEvent::PitchBend { timing, channel, value } =>
+                NoteEvent::MidiPitchBend { timing, channel, value },
+            AeolusEvent::ChannelPressure { timing, channel, pressure } =>
+                NoteEvent::MidiChannelPressure { timing, channel, pressure },
+            AeolusEvent::PolyPressure { timing, channel, note, pressure } =>
+                NoteEvent::PolyPressure { timing, voice_id: None, channel, note, pressure },
+            AeolusEvent::PolyTuning { timing, channel, note, tuning } =>
+                NoteEvent::PolyTuning { timing, voice_id: None, channel, note, tuning },
+            AeolusEvent::Realtime { timing, message } =>
+                NoteEvent::MidiSysEx { timing, message },
+        }
+    }
+}
+
+/// Wherever the engine's events go
+pub trait EventSink {
+    fn send(&mut self, event: AeolusEvent);
+}
+
+/// So that generic code can send to a `&mut dyn EventSink`, as output generators get
+impl<S: EventSink + ?Sized> EventSink for &mut S {
+    fn send(&mut self, event: AeolusEvent) {
+        (**self).send(event);
+    }
+}
+
+/// Collects events, for offline use. This allocates, so don't use it on the audio thread.
+impl EventSink for Vec<AeolusEvent> {
+    fn send(&mut self, event: AeolusEvent) {
+        self.push(event);
+    }
+}
+
+/// Forwards events to the host
+#[cfg(feature = "plugin")]
+pub struct HostSink<'a, P: Plugin<SysExMessage = RealtimeMessage>, C: ProcessContext<P>> {
+    context: &'a mut C,
+    plugin:  PhantomData<P>,
+}
+
+#[cfg(feature = "plugin")]
+impl<'a, P: Plugin<SysExMessage = RealtimeMessage>, C: ProcessContext<P>> HostSink<'a, P, C> {
+    pub fn new(context: &'a mut C) -> Self {
+        Self { context, plugin: PhantomData }
+    }
+}
+
+#[cfg(feature = "plugin")]
+impl<P: Plugin<SysExMessage = RealtimeMessage>, C: ProcessContext<P>> EventSink for HostSink<'_, P, C> {
+    fn send(&mut self, event: AeolusEvent) {
+        self.context.send_event(event.to_note_event());
+    }
+}
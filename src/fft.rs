@@ -0,0 +1,79 @@
+use std::f32::consts::PI;
+
+/// In-place radix-2 FFT, on separate real and imaginary parts. The tables are computed in
+/// `new()`, after which transforms don't allocate.
+pub struct Fft {
+    size:        usize,
+    /// cos and sin of -2πk/size, for k up to size / 2
+    twiddles:    Vec<(f32, f32)>,
+    bit_reverse: Vec<usize>,
+}
+
+impl Fft {
+    /// `size` must be a power of two
+    pub fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two(), "FFT sizes must be powers of two");
+        let bits = size.trailing_zeros();
+        Self {
+            size,
+            twiddles: (0..size / 2)
+                .map(|k| {
+                    let angle = -2.0 * PI * k as f32 / size as f32;
+                    (angle.cos(), angle.sin())
+                })
+                .collect(),
+            bit_reverse: (0..size)
+                .map(|index| if bits == 0 { 0 } else { index.reverse_bits() >> (usize::BITS - bits) })
+                .collect(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn forward(&self, real: &mut [f32], imaginary: &mut [f32]) {
+        self.transform(real, imaginary, false);
+    }
+
+    /// Scaled by 1 / size, so that `inverse()` undoes `forward()`
+    pub fn inverse(&self, real: &mut [f32], imaginary: &mut [f32]) {
+        self.transform(real, imaginary, true);
+        let scale = 1.0 / self.size as f32;
+        for (real, imaginary) in real.iter_mut().zip(imaginary.iter_mut()) {
+            *real *= scale;
+            *imaginary *= scale;
+        }
+    }
+
+    fn transform(&self, real: &mut [f32], imaginary: &mut [f32], inverse: bool) {
+        let size = self.size;
+        assert!(real.len() == size && imaginary.len() == size);
+        for (index, &reversed) in self.bit_reverse.iter().enumerate() {
+            if index < reversed {
+                real.swap(index, reversed);
+                imaginary.swap(index, reversed);
+            }
+        }
+
+        let mut length = 2;
+        while length <= size {
+            let half = length / 2;
+            let stride = size / length;
+            for start in (0..size).step_by(length) {
+                for offset in 0..half {
+                    let (cos, sin) = self.twiddles[offset * stride];
+                    let sin = if inverse { -sin } else { sin };
+                    let (even, odd) = (start + offset, start + offset + half);
+                    let odd_real = real[odd] * cos - imaginary[odd] * sin;
+                    let odd_imaginary = real[odd] * sin + imaginary[odd] * cos;
+                    real[odd] = real[even] - odd_real;
+                    imaginary[odd] = imaginary[even] - odd_imaginary;
+                    real[even] += odd_real;
+                    imaginary[even] += odd_imaginary;
+                }
+            }
+            length *= 2;
+        }
+    }
+}
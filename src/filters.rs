@@ -0,0 +1,65 @@
+use std::f32::consts::PI;
+
+/// Below this, the DC blocker's response drops off
+const DC_CUTOFF_HZ: f32 = 10.0;
+
+/// One-pole DC removal filter (a differentiator followed by a leaky integrator). DC offsets from
+/// some interfaces and DI boxes skew YIN's difference function, which shows as low confidence.
+pub struct DcBlocker {
+    coeff:       f32,
+    last_input:  f32,
+    last_output: f32,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut blocker = Self { coeff: 0.0, last_input: 0.0, last_output: 0.0 };
+        blocker.set_sample_rate(sample_rate);
+        blocker
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.coeff = 1.0 - 2.0 * PI * DC_CUTOFF_HZ / sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.last_input = 0.0;
+        self.last_output = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = input - self.last_input + self.coeff * self.last_output;
+        self.last_input = input;
+        self.last_output = output;
+        output
+    }
+}
+
+/// One-pole lowpass filter, gentle (6 dB per octave) but cheap. Subtracting its output from
+/// the input makes a highpass.
+pub struct OnePoleLowpass {
+    cutoff_hz: f32,
+    coeff:     f32,
+    state:     f32,
+}
+
+impl OnePoleLowpass {
+    pub fn new(sample_rate: f32, cutoff_hz: f32) -> Self {
+        let mut filter = Self { cutoff_hz, coeff: 0.0, state: 0.0 };
+        filter.set_sample_rate(sample_rate);
+        filter
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.coeff = (-2.0 * PI * self.cutoff_hz / sample_rate).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.state = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.state = input + self.coeff * (self.state - input);
+        self.state
+    }
+}
@@ -0,0 +1,138 @@
+use std::f32::consts::PI;
+
+/// Formants are looked for at (about) this rate, which covers the first two with room to spare
+const FORMANT_RATE:   f32 = 11025.0;
+/// About 23 ms at the formant rate
+const FRAME_SIZE:     usize = 256;
+/// Two poles per formant expected below the Nyquist frequency, plus a couple for the spectral
+/// tilt
+const LPC_ORDER:      usize = 10;
+/// How finely the LPC envelope is sampled when looking for its peaks, up to `MAX_FORMANT_HZ`
+const ENVELOPE_BINS:  usize = 128;
+const MAX_FORMANT_HZ: f32 = 4000.0;
+const PRE_EMPHASIS:   f32 = 0.97;
+
+/// Where the first two formants are looked for, and what their CC lanes span (in Hz)
+pub const F1_RANGE: (f32, f32) = (200.0, 1000.0);
+pub const F2_RANGE: (f32, f32) = (600.0, 3000.0);
+
+/// Tracks the first two formants with linear prediction: the input is decimated to
+/// `FORMANT_RATE`, an all-pole model is fitted to the last frame, and the formants are the
+/// peaks of the model's spectral envelope. Nothing here allocates.
+pub struct FormantTracker {
+    decimation:  usize,
+    /// Sum of the samples since the last decimated one, as a crude anti-aliasing filter
+    accumulator: f32,
+    accumulated: usize,
+    /// The last decimated samples, as a ring buffer
+    frame:       [f32; FRAME_SIZE],
+    frame_index: usize,
+    rate:        f32,
+}
+
+impl FormantTracker {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut tracker = Self {
+            decimation: 1,
+            accumulator: 0.0,
+            accumulated: 0,
+            frame: [0.0; FRAME_SIZE],
+            frame_index: 0,
+            rate: sample_rate,
+        };
+        tracker.set_sample_rate(sample_rate);
+        tracker
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.decimation = ((sample_rate / FORMANT_RATE).round() as usize).max(1);
+        self.rate = sample_rate / self.decimation as f32;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+        self.accumulated = 0;
+        self.frame = [0.0; FRAME_SIZE];
+        self.frame_index = 0;
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        self.accumulator += sample;
+        self.accumulated += 1;
+        if self.accumulated == self.decimation {
+            self.frame[self.frame_index] = self.accumulator / self.decimation as f32;
+            self.frame_index = (self.frame_index + 1) % FRAME_SIZE;
+            self.accumulator = 0.0;
+            self.accumulated = 0;
+        }
+    }
+
+    /// The first two formant frequencies (in Hz) of the last frame, if they could be found
+    pub fn formants(&self) -> Option<(f32, f32)> {
+        // Pre-emphasis flattens the voice's spectral tilt, so that the higher formants stand out
+        // as much as the first one. A Hann window then tames the frame's edges.
+        let mut windowed = [0.0; FRAME_SIZE];
+        let mut previous = 0.0;
+        for (index, value) in windowed.iter_mut().enumerate() {
+            let sample = self.frame[(self.frame_index + index) % FRAME_SIZE];
+            let window = 0.5 - 0.5 * (2.0 * PI * index as f32 / (FRAME_SIZE - 1) as f32).cos();
+            *value = window * (sample - PRE_EMPHASIS * previous);
+            previous = sample;
+        }
+
+        let mut autocorrelation = [0.0; LPC_ORDER + 1];
+        for (lag, value) in autocorrelation.iter_mut().enumerate() {
+            *value = windowed[..FRAME_SIZE - lag].iter().zip(&windowed[lag..]).map(|(a, b)| a * b).sum::<f32>();
+        }
+        let lpc = levinson_durbin(&autocorrelation)?;
+
+        // The envelope is 1 / |A(e^jw)|, so its peaks are the minima of |A|²
+        let mut inverse_envelope = [0.0; ENVELOPE_BINS];
+        for (bin, value) in inverse_envelope.iter_mut().enumerate() {
+            let omega = 2.0 * PI * bin_frequency(bin) / self.rate;
+            let (mut real, mut imaginary) = (0.0, 0.0);
+            for (order, coefficient) in lpc.iter().enumerate() {
+                real += coefficient * (omega * order as f32).cos();
+                imaginary -= coefficient * (omega * order as f32).sin();
+            }
+            *value = real * real + imaginary * imaginary;
+        }
+
+        let mut peaks = inverse_envelope.windows(3).enumerate()
+            .filter(|(_, values)| values[1] < values[0] && values[1] < values[2])
+            .map(|(bin, _)| bin_frequency(bin + 1));
+        let first = peaks.by_ref().find(|frequency| (F1_RANGE.0..=F1_RANGE.1).contains(frequency))?;
+        let second = peaks.find(|frequency| (F2_RANGE.0..=F2_RANGE.1).contains(frequency))?;
+        Some((first, second))
+    }
+}
+
+fn bin_frequency(bin: usize) -> f32 {
+    MAX_FORMANT_HZ * bin as f32 / ENVELOPE_BINS as f32
+}
+
+/// The prediction polynomial's coefficients (the first one being 1), or `None` for silence
+fn levinson_durbin(autocorrelation: &[f32; LPC_ORDER + 1]) -> Option<[f32; LPC_ORDER + 1]> {
+    let mut coefficients = [0.0; LPC_ORDER + 1];
+    coefficients[0] = 1.0;
+    let mut error = autocorrelation[0];
+    if error <= 0.0 {
+        return None;
+    }
+    for (order, &correlation) in autocorrelation.iter().enumerate().skip(1) {
+        let sum = correlation
+            + (1..order).map(|index| coefficients[index] * autocorrelation[order - index]).sum::<f32>();
+        let reflection = -sum / error;
+        let previous = coefficients;
+        for (index, coefficient) in coefficients.iter_mut().enumerate().take(order).skip(1) {
+            *coefficient = previous[index] + reflection * previous[order - index];
+        }
+        coefficients[order] = reflection;
+        error *= 1.0 - reflection * reflection;
+        if error <= 0.0 {
+            break;
+        }
+    }
+    Some(coefficients)
+}
@@ -0,0 +1,50 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+/// How fast the noise floor estimate rises, and falls. It falls faster, so that a burst of
+/// noise (or a note the detector missed) doesn't push the gate up for long.
+const RISE_MS: f32 = 2000.0;
+const FALL_MS: f32 = 200.0;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum GateMode {
+    #[cfg_attr(feature = "plugin", name = "Off")]
+    Off,
+    /// Input below the threshold is treated as silence
+    #[cfg_attr(feature = "plugin", name = "Fixed")]
+    Fixed,
+    /// The threshold follows the noise floor, staying the margin above it
+    #[cfg_attr(feature = "plugin", name = "Adaptive")]
+    Adaptive,
+}
+
+/// Estimates the level of the background noise, from the input level during unvoiced periods
+#[derive(Default)]
+pub struct NoiseFloor {
+    /// In dB
+    level: Option<f32>,
+}
+
+impl NoiseFloor {
+    pub fn reset(&mut self) {
+        self.level = None;
+    }
+
+    /// In dB, if anything was learned yet
+    pub fn level(&self) -> Option<f32> {
+        self.level
+    }
+
+    /// Feeds the level (in dB) measured while the input was unvoiced, `elapsed_ms` after the
+    /// previous call
+    pub fn update(&mut self, level: f32, elapsed_ms: f32) {
+        self.level = Some(match self.level {
+            Some(floor) => {
+                let time_ms = if level > floor { RISE_MS } else { FALL_MS };
+                level + (-elapsed_ms / time_ms).exp() * (floor - level)
+            }
+            None => level,
+        });
+    }
+}
@@ -0,0 +1,83 @@
+/// Pitch changes larger than this (in semitones) from one hop to the next are note changes,
+/// not slides
+const MAX_SLIDE_STEP:     f32 = 0.8;
+/// Slower movements (in semitones per second) are drift, not slides
+const MIN_SLIDE_RATE:     f32 = 2.0;
+/// A slide has to cover at least this much (in semitones) before it counts, which keeps vibrato
+/// from being taken for a glide
+const MIN_SLIDE_DISTANCE: f32 = 1.0;
+const MIN_SLIDE_MS:       f32 = 30.0;
+/// How long the pitch has to stay put after a slide before the glide is considered over
+const SETTLE_MS:          f32 = 40.0;
+/// The portamento time (per semitone) that is mapped to the top of CC5's range
+pub const MAX_PORTAMENTO_MS: f32 = 500.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GlideEvent {
+    /// With the estimated portamento time, in milliseconds per semitone
+    Started(f32),
+    Stopped,
+}
+
+/// Tells continuous slides from discrete note changes, so that portamento can be switched on
+/// exactly when the performer glides
+#[derive(Default)]
+pub struct GlideDetector {
+    previous: Option<f32>,
+    /// Distance (in semitones, signed) and duration of the current monotonic movement
+    distance: f32,
+    duration: f32,
+    /// How long (in milliseconds) the pitch hasn't been sliding
+    still:    f32,
+    gliding:  bool,
+}
+
+impl GlideDetector {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Feeds one hop's (smoothed) pitch, in semitones
+    pub fn process(&mut self, pitch: f32, hop_ms: f32) -> Option<GlideEvent> {
+        let previous = self.previous.replace(pitch)?;
+        let delta = pitch - previous;
+        let rate = delta.abs() / hop_ms * 1000.0;
+
+        if delta.abs() > MAX_SLIDE_STEP {
+            self.restart(0.0);
+            return self.stop();
+        }
+        let same_direction = self.distance == 0.0 || delta.signum() == self.distance.signum();
+        if rate >= MIN_SLIDE_RATE && same_direction {
+            self.distance += delta;
+            self.duration += hop_ms;
+            self.still = 0.0;
+        } else {
+            self.still += hop_ms;
+            // Turning around starts a new movement
+            self.restart(if rate >= MIN_SLIDE_RATE { delta } else { 0.0 });
+            if self.still > SETTLE_MS {
+                return self.stop();
+            }
+        }
+
+        if !self.gliding && self.distance.abs() >= MIN_SLIDE_DISTANCE && self.duration >= MIN_SLIDE_MS {
+            self.gliding = true;
+            return Some(GlideEvent::Started(self.duration / self.distance.abs()));
+        }
+        None
+    }
+
+    fn restart(&mut self, distance: f32) {
+        self.distance = distance;
+        self.duration = 0.0;
+    }
+
+    fn stop(&mut self) -> Option<GlideEvent> {
+        if std::mem::take(&mut self.gliding) {
+            Some(GlideEvent::Stopped)
+        } else {
+            None
+        }
+    }
+}
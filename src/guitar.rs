@@ -0,0 +1,65 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+use crate::events::{AeolusEvent, EventSink};
+use crate::midi::*;
+use crate::notes::ChannelAssignment;
+use crate::mapping::Range;
+
+/// What happens to the bend when the pitch moves on to another note
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum BendReset {
+    /// Each new note gets a NoteOn, and the bend starts over from it
+    #[cfg_attr(feature = "plugin", name = "New note")]
+    NewNote,
+    /// The sounding note is bent all the way to the new pitch, and only replaced once the pitch
+    /// leaves the bend range. Slides and bends then come out as a single bent note.
+    #[cfg_attr(feature = "plugin", name = "Glide")]
+    Glide,
+}
+
+/// "MIDI guitar" mode, the way hardware guitar-to-MIDI converters work: quantized notes, with
+/// the channel pitch bend following the deviation from the note within it
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GuitarConfig {
+    /// In semitones
+    pub bend_range: u8,
+    pub reset:      BendReset,
+}
+
+impl GuitarConfig {
+    /// Normalized pitch bend value for a deviation (in semitones) from the note being bent
+    pub fn bend_value(&self, semitones: f32) -> f32 {
+        Range::UNIT.clamp(0.5 + semitones / (2.0 * self.bend_range as f32))
+    }
+
+    /// Whether the sounding `note` can be bent to `pitch` (both as MIDI notes) in glide mode
+    pub fn glides_to(&self, note: u8, pitch: f32) -> bool {
+        self.reset == BendReset::Glide && (pitch - note as f32).abs() <= self.bend_range as f32
+    }
+
+    /// Sets the bend range (RPN 0) on every channel notes may go to
+    pub fn send_configuration(&self, sink: &mut impl EventSink, timing: u32, channels: ChannelAssignment) {
+        for channel in note_channels(channels) {
+            send_rpn(sink, timing, channel, RPN_PITCH_BEND_SENSITIVITY, self.bend_range, 0);
+        }
+    }
+}
+
+/// Centers the pitch bend on every channel notes may have gone to, once the mode is left
+pub fn center_bends(sink: &mut impl EventSink, timing: u32, channels: ChannelAssignment) {
+    for channel in note_channels(channels) {
+        sink.send(AeolusEvent::PitchBend { timing, channel, value: 0.5 });
+    }
+}
+
+fn note_channels(channels: ChannelAssignment) -> impl Iterator<Item = u8> {
+    // A split only uses the two channels at the ends
+    let (first, last, ends_only) = match channels {
+        ChannelAssignment::Fixed(channel) => (channel, channel, false),
+        ChannelAssignment::RoundRobin(first, last) => (first.min(last), first.max(last), false),
+        ChannelAssignment::Split { lower, upper, .. } => (lower.min(upper), lower.max(upper), true),
+    };
+    (first..=last).filter(move |channel| !ends_only || *channel == first || *channel == last)
+}
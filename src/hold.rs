@@ -0,0 +1,84 @@
+/// Keeps a value stable for a minimum amount of time after each change, before accepting
+/// another change. This is a coarse but effective stabilizer for percussive sources.
+pub struct Hold<T> {
+    value:   Option<T>,
+    /// Samples elapsed since `value` last changed
+    elapsed: usize,
+}
+
+impl<T> Default for Hold<T> {
+    fn default() -> Self {
+        Self { value: None, elapsed: 0 }
+    }
+}
+
+impl<T: Copy + PartialEq> Hold<T> {
+    pub fn reset(&mut self) {
+        self.value = None;
+        self.elapsed = 0;
+    }
+
+    /// Lets time pass. This is separate from `process()` so that time still passes while no
+    /// values are coming in.
+    pub fn advance(&mut self, samples: usize) {
+        self.elapsed = self.elapsed.saturating_add(samples);
+    }
+
+    /// Returns `input` if the held value has been stable for at least `hold_samples`, and the
+    /// held value otherwise
+    pub fn process(&mut self, input: T, hold_samples: usize) -> T {
+        match self.value {
+            Some(value) if value == input => value,
+            Some(value) if self.elapsed < hold_samples => value,
+            _ => {
+                self.value = Some(input);
+                self.elapsed = 0;
+                input
+            }
+        }
+    }
+}
+
+/// Only lets a new value through once it has been seen continuously for some time. Until then,
+/// the previously confirmed value (if any) is kept.
+pub struct Confirmation<T> {
+    confirmed: Option<T>,
+    candidate: Option<T>,
+    /// Samples elapsed since `candidate` was first seen
+    elapsed:   usize,
+}
+
+impl<T> Default for Confirmation<T> {
+    fn default() -> Self {
+        Self { confirmed: None, candidate: None, elapsed: 0 }
+    }
+}
+
+impl<T: Copy + PartialEq> Confirmation<T> {
+    pub fn reset(&mut self) {
+        self.confirmed = None;
+        self.candidate = None;
+        self.elapsed = 0;
+    }
+
+    /// Lets time pass, see `Hold::advance()`
+    pub fn advance(&mut self, samples: usize) {
+        self.elapsed = self.elapsed.saturating_add(samples);
+    }
+
+    /// Returns the confirmed value, which becomes `input` once it has been seen for at least
+    /// `confirm_samples`
+    pub fn process(&mut self, input: T, confirm_samples: usize) -> Option<T> {
+        if self.confirmed == Some(input) {
+            self.candidate = None;
+        } else if self.candidate != Some(input) {
+            self.candidate = Some(input);
+            self.elapsed = 0;
+        }
+        if self.candidate == Some(input) && self.elapsed >= confirm_samples {
+            self.confirmed = Some(input);
+            self.candidate = None;
+        }
+        self.confirmed
+    }
+}
@@ -0,0 +1,85 @@
+use crate::spectrum::{is_silent, peak_offset, MagnitudeSpectrum};
+
+/// How many harmonics are multiplied together
+const HARMONICS:         usize = 5;
+/// The padded FFT is this many times longer than the window, for finer bins
+const PADDING:           usize = 4;
+/// In Hz, the lowest fundamental looked for
+const MIN_FREQUENCY:     f32 = 30.0;
+/// Candidates with less than this share of the spectrum's energy around their harmonics are
+/// taken for noise
+const VOICING_THRESHOLD: f32 = 0.3;
+
+/// The harmonic product spectrum: the magnitude spectrum is multiplied by copies of itself
+/// compressed by 2, 3, ..., which lines the harmonics of the fundamental up on it. Where YIN
+/// looks for the period, this looks for the series of harmonics, so it doesn't take a strong
+/// harmonic for the fundamental the same way, and makes a fallback for sources where YIN does.
+///
+/// See M. R. Schroeder, "Period histogram and product spectrum: new methods for
+/// fundamental-frequency measurement", 1968.
+pub struct HpsDetector {
+    spectrum:    MagnitudeSpectrum,
+    /// The log of the product, for each candidate bin
+    product:     Vec<f32>,
+    sample_rate: f32,
+    silence_db:  f32,
+    confidence:  f32,
+}
+
+impl HpsDetector {
+    /// This allocates
+    pub fn new(buffer_size: usize, sample_rate: f32, silence_db: f32) -> Self {
+        let fft_size = (buffer_size * PADDING).next_power_of_two();
+        Self {
+            spectrum: MagnitudeSpectrum::new(buffer_size, fft_size),
+            product: vec![0.0; fft_size / 2 / HARMONICS + 1],
+            sample_rate,
+            silence_db,
+            confidence: 0.0,
+        }
+    }
+
+    /// Between 0 and 1, for the last detection
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Returns the frequency (in Hz) detected in `window`, zero meaning unvoiced or silent
+    pub fn detect(&mut self, window: &[f32]) -> f32 {
+        self.confidence = 0.0;
+        if is_silent(window, self.silence_db) {
+            return 0.0;
+        }
+        let bin_width = self.spectrum.bin_width(self.sample_rate);
+        let magnitudes = self.spectrum.analyze(window);
+        let bins = self.product.len();
+        let min_bin = ((MIN_FREQUENCY / bin_width).ceil() as usize).max(1);
+        if min_bin + 2 > bins {
+            return 0.0;
+        }
+        // In the log domain, lest the product of small magnitudes underflow
+        for (bin, product) in self.product.iter_mut().enumerate() {
+            *product = (1..=HARMONICS).map(|harmonic| (magnitudes[bin * harmonic] + f32::EPSILON).ln()).sum();
+        }
+        let peak = (min_bin..bins - 1)
+            .max_by(|a, b| self.product[*a].total_cmp(&self.product[*b]))
+            .unwrap_or(min_bin);
+
+        // The share of the energy that's within a bin (of the unpadded spectrum) of the
+        // candidate's harmonics
+        let energy = |bins: &[f32]| bins.iter().map(|magnitude| magnitude * magnitude).sum::<f32>();
+        let total = energy(magnitudes);
+        let harmonic: f32 = (1..=HARMONICS)
+            .map(|harmonic| {
+                let center = peak * harmonic;
+                energy(&magnitudes[center.saturating_sub(PADDING)..(center + PADDING + 1).min(magnitudes.len())])
+            })
+            .sum();
+        self.confidence = if total > 0.0 { (harmonic / total).clamp(0.0, 1.0) } else { 0.0 };
+        if self.confidence < VOICING_THRESHOLD {
+            return 0.0;
+        }
+        let offset = peak_offset(self.product[peak - 1], self.product[peak], self.product[peak + 1]);
+        (peak as f32 + offset) * bin_width
+    }
+}
@@ -0,0 +1,94 @@
+use crate::scale::{RootNote, Scale, ScaleType};
+
+/// How long the detector remembers what was played, in milliseconds. Long enough not to be
+/// swayed by a passing chromatic run, short enough to follow a modulation within a few bars.
+const KEY_MEMORY_MS: f32 = 20_000.0;
+/// The detector doesn't commit to a key before this much voiced input, in milliseconds
+const MIN_EVIDENCE_MS: f32 = 2_000.0;
+
+/// Krumhansl and Kessler's key profiles: how well each degree (in semitones above the tonic)
+/// was judged to fit the key in their listening tests
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// How the quantizer follows the detected key
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AutoKey {
+    /// Between 0 and 1, how well the recent notes must fit a key for it to be adopted
+    pub confidence:   f32,
+    /// In seconds, how long a key is kept at least before moving on to another one
+    pub min_interval: f32,
+}
+
+/// A major or (natural) minor key
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Key {
+    pub root:  RootNote,
+    /// Either `ScaleType::Major` or `ScaleType::NaturalMinor`
+    pub scale: ScaleType,
+}
+
+impl Key {
+    pub fn scale(&self) -> Scale {
+        Scale::new(self.scale, self.root)
+    }
+}
+
+/// Estimates the key from how long each pitch class was played recently, the way
+/// Krumhansl-Schmuckler's algorithm does: the key whose profile correlates best with the
+/// pitch-class durations wins
+#[derive(Default)]
+pub struct KeyDetector {
+    /// Decaying time spent on each pitch class, in milliseconds
+    durations: [f32; 12],
+}
+
+impl KeyDetector {
+    pub fn reset(&mut self) {
+        self.durations = [0.0; 12];
+    }
+
+    /// Accounts for `milliseconds` of the input sitting at `pitch` (a fractional MIDI pitch)
+    pub fn push(&mut self, pitch: f32, milliseconds: f32) {
+        let decay = (-milliseconds / KEY_MEMORY_MS).exp();
+        for duration in &mut self.durations {
+            *duration *= decay;
+        }
+        self.durations[(pitch.round() as i32).rem_euclid(12) as usize] += milliseconds;
+    }
+
+    /// The best fitting key and its correlation with the recent notes (from -1 to 1), once
+    /// there's enough to go on
+    pub fn estimate(&self) -> Option<(Key, f32)> {
+        if self.durations.iter().sum::<f32>() < MIN_EVIDENCE_MS {
+            return None;
+        }
+        let keys = RootNote::ALL.iter().flat_map(|root| {
+            [(ScaleType::Major, &MAJOR_PROFILE), (ScaleType::NaturalMinor, &MINOR_PROFILE)]
+                .map(|(scale, profile)| (Key { root: *root, scale }, profile))
+        });
+        keys.map(|(key, profile)| {
+            let tonic = key.root.pitch_class() as usize;
+            let rotated: [f32; 12] = std::array::from_fn(|degree| self.durations[(tonic + degree) % 12]);
+            (key, correlation(&rotated, profile))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+/// Pearson's correlation coefficient
+fn correlation(a: &[f32; 12], b: &[f32; 12]) -> f32 {
+    let mean_a = a.iter().sum::<f32>() / 12.0;
+    let mean_b = b.iter().sum::<f32>() / 12.0;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (a, b) in a.iter().zip(b) {
+        covariance += (a - mean_a) * (b - mean_b);
+        variance_a += (a - mean_a).powi(2);
+        variance_b += (b - mean_b).powi(2);
+    }
+    if variance_a <= 0.0 {
+        0.0
+    } else {
+        covariance / (variance_a * variance_b).sqrt()
+    }
+}
@@ -0,0 +1,120 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::plugin::AeolusParams;
+
+/// For `ControllerInput::last`, until a CC moves
+const NO_CC: u8 = u8::MAX;
+
+/// The CCs coming in on the MIDI input, passed from the audio thread to the editor, which moves
+/// the parameters they're bound to. Only the editor can set parameters in a way hosts follow and
+/// record, so bindings only act while it's open.
+pub struct ControllerInput {
+    values: [AtomicF32; 128],
+    /// Which CCs moved since the editor last looked
+    moved:  [AtomicBool; 128],
+    /// The CC that moved last, for learning
+    last:   AtomicU8,
+}
+
+impl Default for ControllerInput {
+    fn default() -> Self {
+        Self {
+            values: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            moved: std::array::from_fn(|_| AtomicBool::new(false)),
+            last: AtomicU8::new(NO_CC),
+        }
+    }
+}
+
+impl ControllerInput {
+    /// From the audio thread. The value is normalized, as in nih_plug's `MidiCC` events.
+    pub fn push(&self, cc: u8, value: f32) {
+        let cc = cc & 127;
+        self.values[cc as usize].store(value, Ordering::Relaxed);
+        self.moved[cc as usize].store(true, Ordering::Relaxed);
+        self.last.store(cc, Ordering::Relaxed);
+    }
+
+    /// Forgets which CC moved last, so that learning waits for the next one
+    pub fn forget_last(&self) {
+        self.last.store(NO_CC, Ordering::Relaxed);
+    }
+
+    pub fn last_moved(&self) -> Option<u8> {
+        match self.last.load(Ordering::Relaxed) {
+            NO_CC => None,
+            cc    => Some(cc),
+        }
+    }
+
+    /// The CCs that moved since the last call, with their current values
+    pub fn take_moved(&self) -> impl Iterator<Item = (u8, f32)> + '_ {
+        (0..128u8)
+            .filter(move |cc| self.moved[*cc as usize].swap(false, Ordering::Relaxed))
+            .map(move |cc| (cc, self.values[cc as usize].load(Ordering::Relaxed)))
+    }
+}
+
+/// Which parameter (by ID) each CC moves, over the parameter's whole range. Saved with the
+/// session, but not in presets, as they're the controller's setup rather than the sound's.
+#[derive(Default, Serialize, Deserialize)]
+pub struct MidiBindings {
+    bindings: BTreeMap<u8, String>,
+}
+
+impl MidiBindings {
+    /// Binds `cc` to a parameter, replacing whatever either was bound to
+    pub fn bind(&mut self, cc: u8, id: String) {
+        self.bindings.retain(|_, bound| *bound != id);
+        self.bindings.insert(cc, id);
+    }
+
+    pub fn unbind(&mut self, cc: u8) {
+        self.bindings.remove(&cc);
+    }
+
+    pub fn get(&self, cc: u8) -> Option<&str> {
+        self.bindings.get(&cc).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &str)> {
+        self.bindings.iter().map(|(cc, id)| (*cc, id.as_str()))
+    }
+}
+
+/// Every parameter's ID and name, for picking the one to learn
+pub(crate) fn param_names(params: &AeolusParams) -> Vec<(String, String)> {
+    params
+        .param_map()
+        .into_iter()
+        // SAFETY: the pointers are to `params`' own fields, which we're borrowing
+        .map(|(id, param, _)| (id, unsafe { param.name() }.to_owned()))
+        .collect()
+}
+
+/// Moves the parameters bound to the CCs that moved, each as a single gesture
+pub(crate) fn apply(setter: &ParamSetter, params: &AeolusParams, input: &ControllerInput) {
+    let moved: Vec<(u8, f32)> = input.take_moved().collect();
+    if moved.is_empty() {
+        return;
+    }
+    let bindings = params.midi_bindings.lock().unwrap();
+    let param_map = params.param_map();
+    for (cc, value) in moved {
+        let Some(id) = bindings.get(cc) else {
+            continue;
+        };
+        if let Some((_, param, _)) = param_map.iter().find(|(own, _, _)| own == id) {
+            // SAFETY: as above
+            unsafe {
+                setter.raw_context.raw_begin_set_parameter(*param);
+                setter.raw_context.raw_set_parameter_normalized(*param, value);
+                setter.raw_context.raw_end_set_parameter(*param);
+            }
+        }
+    }
+}
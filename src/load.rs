@@ -0,0 +1,65 @@
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Time constant of the average load, in seconds
+const AVERAGE_SECONDS: f32 = 1.0;
+/// How long (in seconds) the worst load takes to decay to about a third, so that a spike stays
+/// readable for a while
+const WORST_SECONDS:   f32 = 5.0;
+
+/// Loads above this leave too little headroom for the rest of the host's work
+pub const RISKY_LOAD: f32 = 0.7;
+
+/// How much of the available time a thread spends processing, passed from that thread to the
+/// editor. The load is the time spent processing a block divided by the block's duration, so
+/// anything over 1 means the thread didn't keep up.
+pub struct LoadMeter {
+    average:  AtomicF32,
+    worst:    AtomicF32,
+    /// Blocks that took longer than their duration
+    overruns: AtomicU32,
+}
+
+impl Default for LoadMeter {
+    fn default() -> Self {
+        Self { average: AtomicF32::new(0.0), worst: AtomicF32::new(0.0), overruns: AtomicU32::new(0) }
+    }
+}
+
+impl LoadMeter {
+    /// Records how long processing `budget`'s worth of audio took
+    pub fn record(&self, elapsed: Duration, budget: Duration) {
+        let budget = budget.as_secs_f32();
+        if budget <= 0.0 {
+            return;
+        }
+        let load = elapsed.as_secs_f32() / budget;
+        let average = self.average.load(Ordering::Relaxed);
+        let coeff = (-budget / AVERAGE_SECONDS).exp();
+        self.average.store(load + coeff * (average - load), Ordering::Relaxed);
+        let worst = self.worst.load(Ordering::Relaxed) * (-budget / WORST_SECONDS).exp();
+        self.worst.store(worst.max(load), Ordering::Relaxed);
+        if load > 1.0 {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn reset(&self) {
+        self.average.store(0.0, Ordering::Relaxed);
+        self.worst.store(0.0, Ordering::Relaxed);
+        self.overruns.store(0, Ordering::Relaxed);
+    }
+
+    pub fn average(&self) -> f32 {
+        self.average.load(Ordering::Relaxed)
+    }
+
+    pub fn worst(&self) -> f32 {
+        self.worst.load(Ordering::Relaxed)
+    }
+
+    pub fn overruns(&self) -> u32 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+}
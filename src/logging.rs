@@ -0,0 +1,112 @@
+use rtrb::{Producer, RingBuffer};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::analysis::Detection;
+
+/// How many rows can be waiting to be written before new ones get dropped
+const ROW_CAPACITY: usize = 1 << 14;
+/// How long the logging thread sleeps when there is nothing to write
+const IDLE_SLEEP: Duration = Duration::from_millis(20);
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogFormat {
+    Csv,
+    /// One JSON object per line (JSON Lines), so that the file is valid even if logging is
+    /// interrupted
+    Json,
+}
+
+/// One hop's worth of analysis
+#[derive(Debug, Clone, Copy)]
+struct PitchRow {
+    /// In seconds since logging started
+    time:       f64,
+    /// In Hz, or zero if unvoiced
+    frequency:  f32,
+    confidence: f32,
+    /// Linear input level
+    level:      f32,
+}
+
+fn write_row(writer: &mut impl Write, format: LogFormat, row: PitchRow) -> io::Result<()> {
+    let PitchRow { time, frequency, confidence, level } = row;
+    match format {
+        LogFormat::Csv => writeln!(writer, "{time:.6},{frequency:.3},{confidence:.4},{level:.6}"),
+        LogFormat::Json => writeln!(
+            writer,
+            r#"{{"time":{time:.6},"frequency":{frequency:.3},"confidence":{confidence:.4},"level":{level:.6}}}"#,
+        ),
+    }
+}
+
+/// Writes the pitch track to a file from a thread of its own, so that the audio thread never
+/// waits for the disk. `log()` doesn't block or allocate.
+pub struct PitchLogger {
+    rows:    Producer<PitchRow>,
+    /// Position of the first logged hop, which is time zero in the file
+    start:   Option<u64>,
+    running: Arc<AtomicBool>,
+    thread:  Option<JoinHandle<()>>,
+}
+
+impl PitchLogger {
+    /// Creates (or truncates) the file and spawns the logging thread
+    pub fn start(path: &Path, format: LogFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == LogFormat::Csv {
+            writeln!(writer, "time,frequency,confidence,level")?;
+        }
+        let (rows, mut logger_rows) = RingBuffer::new(ROW_CAPACITY);
+        let running = Arc::new(AtomicBool::new(true));
+
+        let logger_running = running.clone();
+        let thread = thread::spawn(move || {
+            loop {
+                // Checked before draining, so that the rows logged before stopping get written
+                let running = logger_running.load(Ordering::Relaxed);
+                while let Ok(row) = logger_rows.pop() {
+                    // There's nobody to report errors to, so we stop trying at the first one
+                    if write_row(&mut writer, format, row).is_err() {
+                        return;
+                    }
+                }
+                if !running {
+                    break;
+                }
+                thread::park_timeout(IDLE_SLEEP);
+            }
+            let _ = writer.flush();
+        });
+
+        Ok(Self { rows, start: None, running, thread: Some(thread) })
+    }
+
+    /// `position` is an absolute position in samples, used to timestamp the row
+    pub fn log(&mut self, position: u64, sample_rate: f32, detection: Detection, level: f32) {
+        let start = *self.start.get_or_insert(position);
+        let row = PitchRow {
+            time: position.saturating_sub(start) as f64 / sample_rate as f64,
+            frequency: detection.frequency.max(0.0),
+            confidence: detection.confidence,
+            level,
+        };
+        // If the disk doesn't keep up, there's nothing better to do than to drop rows
+        let _ = self.rows.push(row);
+    }
+}
+
+impl Drop for PitchLogger {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,92 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+/// A span of values, from `start` to `end`. Ranges may be inverted (`start` above `end`), which
+/// maps rising inputs to falling outputs.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Range {
+    pub start: f32,
+    pub end:   f32,
+}
+
+impl Range {
+    /// Normalized values, as sent in CCs and bends
+    pub const UNIT: Range = Range::new(0.0, 1.0);
+
+    pub const fn new(start: f32, end: f32) -> Self {
+        Self { start, end }
+    }
+
+    /// How far `value` is from `start` towards `end`, 0 being `start` and 1 `end`. Values outside
+    /// of the range give positions outside of [0, 1].
+    ///
+    /// A zero-width (or infinitely wide) range acts as a threshold: values above `start` are at
+    /// 1, the others at 0. NaN is at 0.
+    pub fn position(&self, value: f32) -> f32 {
+        let width = self.end - self.start;
+        if value.is_nan() {
+            0.0
+        } else if width == 0.0 || !width.is_finite() {
+            if value > self.start { 1.0 } else { 0.0 }
+        } else {
+            (value - self.start) / width
+        }
+    }
+
+    /// `position()`, clamped to [0, 1]
+    pub fn clamped_position(&self, value: f32) -> f32 {
+        Range::UNIT.clamp(self.position(value))
+    }
+
+    /// The value at `position` (0 being `start` and 1 `end`), the inverse of `position()`
+    pub fn value_at(&self, position: f32) -> f32 {
+        self.start + position * (self.end - self.start)
+    }
+
+    /// The nearest value within the range, whichever way it goes. NaN becomes `start`.
+    pub fn clamp(&self, value: f32) -> f32 {
+        let (low, high) = if self.start <= self.end { (self.start, self.end) } else { (self.end, self.start) };
+        if value.is_nan() {
+            self.start
+        } else {
+            value.max(low).min(high)
+        }
+    }
+
+    /// Maps `value` from this range to `target`, through `curve`. The result is always within
+    /// `target`, rounding errors included.
+    pub fn map(&self, value: f32, curve: MappingCurve, target: Range) -> f32 {
+        target.clamp(target.value_at(curve.apply(self.position(value))))
+    }
+}
+
+/// How a normalized value is bent on its way from one range to another
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum MappingCurve {
+    #[cfg_attr(feature = "plugin", name = "Linear")]
+    Linear,
+    /// Slow at first, for destinations that react mostly to the low values, like filter cutoffs
+    #[cfg_attr(feature = "plugin", name = "Exponential")]
+    Exponential,
+    /// Fast at first, the other way around
+    #[cfg_attr(feature = "plugin", name = "Logarithmic")]
+    Logarithmic,
+    /// Eases in and out of both ends
+    #[cfg_attr(feature = "plugin", name = "S-curve")]
+    SCurve,
+}
+
+impl MappingCurve {
+    /// Maps [0, 1] onto itself, keeping the order of values. Inputs outside of it (and NaN) are
+    /// clamped first.
+    pub fn apply(self, value: f32) -> f32 {
+        let value = Range::UNIT.clamp(value);
+        match self {
+            MappingCurve::Linear      => value,
+            MappingCurve::Exponential => value * value,
+            MappingCurve::Logarithmic => value.sqrt(),
+            MappingCurve::SCurve      => value * value * (3.0 - 2.0 * value),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::events::{AeolusEvent, EventSink};
+
+// Controller numbers used to build RPN sequences
+const CC_DATA_ENTRY_MSB: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_RPN_LSB:        u8 = 100;
+const CC_RPN_MSB:        u8 = 101;
+
+// Other controller numbers
+const CC_PORTAMENTO_TIME: u8 = 5;
+const CC_PORTAMENTO:      u8 = 65;
+const CC_ALL_NOTES_OFF:   u8 = 123;
+
+/// RPN 0: pitch bend sensitivity (in semitones, then cents)
+pub const RPN_PITCH_BEND_SENSITIVITY: u8 = 0;
+/// RPN 6: MPE Configuration Message (the data is the number of member channels)
+pub const RPN_MPE_CONFIGURATION:      u8 = 6;
+
+/// Events carry normalized CC values, whereas RPN data is made of raw 7-bit values
+fn raw_cc(sink: &mut impl EventSink, timing: u32, channel: u8, cc: u8, value: u8) {
+    sink.send(AeolusEvent::ControlChange {
+        timing,
+        channel,
+        cc,
+        value: value as f32 / 127.0,
+    });
+}
+
+/// Makes receivers release every note they have on the channel, including ones we don't know
+/// about
+pub fn send_all_notes_off(sink: &mut impl EventSink, timing: u32, channel: u8) {
+    raw_cc(sink, timing, channel, CC_ALL_NOTES_OFF, 0);
+}
+
+/// Portamento on (CC65), after its time (CC5) so that the glide starts at the right speed.
+/// `time` is normalized.
+pub fn send_portamento_on(sink: &mut impl EventSink, timing: u32, channel: u8, time: f32) {
+    raw_cc(sink, timing, channel, CC_PORTAMENTO_TIME, (time.clamp(0.0, 1.0) * 127.0).round() as u8);
+    raw_cc(sink, timing, channel, CC_PORTAMENTO, 127);
+}
+
+pub fn send_portamento_off(sink: &mut impl EventSink, timing: u32, channel: u8) {
+    raw_cc(sink, timing, channel, CC_PORTAMENTO, 0);
+}
+
+/// Sends a complete RPN sequence with the given 7-bit data, then resets the RPN selection to
+/// "null" so that later data entry messages don't accidentally change it.
+pub fn send_rpn(
+    sink:    &mut impl EventSink,
+    timing:  u32,
+    channel: u8,
+    rpn:     u8,
+    msb:     u8,
+    lsb:     u8,
+) {
+    raw_cc(sink, timing, channel, CC_RPN_MSB, 0);
+    raw_cc(sink, timing, channel, CC_RPN_LSB, rpn);
+    raw_cc(sink, timing, channel, CC_DATA_ENTRY_MSB, msb);
+    raw_cc(sink, timing, channel, CC_DATA_ENTRY_LSB, lsb);
+    raw_cc(sink, timing, channel, CC_RPN_MSB, 127);
+    raw_cc(sink, timing, channel, CC_RPN_LSB, 127);
+}
@@ -0,0 +1,63 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+use crate::events::EventSink;
+use crate::midi::*;
+use crate::mapping::Range;
+
+/// MPE's third dimension ("timbre"), which synths usually map to brightness
+pub const CC_BRIGHTNESS: u8 = 74;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum MpeZone {
+    /// Master channel 1, member channels going up from 2
+    #[cfg_attr(feature = "plugin", name = "Lower")]
+    Lower,
+    /// Master channel 16, member channels going down from 15
+    #[cfg_attr(feature = "plugin", name = "Upper")]
+    Upper,
+}
+
+/// Everything a receiver needs to know about our MPE setup
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MpeConfig {
+    pub zone: MpeZone,
+    /// Between 1 and 15
+    pub member_channels: u8,
+    /// Per-note pitch bend range, in semitones
+    pub bend_range: u8,
+}
+
+impl MpeConfig {
+    /// 0-based, like all channels in events
+    pub fn master_channel(&self) -> u8 {
+        match self.zone {
+            MpeZone::Lower => 0,
+            MpeZone::Upper => 15,
+        }
+    }
+
+    /// First and last member channels (inclusive, 0-based)
+    pub fn member_range(&self) -> (u8, u8) {
+        match self.zone {
+            MpeZone::Lower => (1, self.member_channels),
+            MpeZone::Upper => (15 - self.member_channels, 14),
+        }
+    }
+
+    /// Normalized pitch bend value for a deviation (in semitones) from the note being bent
+    pub fn bend_value(&self, semitones: f32) -> f32 {
+        Range::UNIT.clamp(0.5 + semitones / (2.0 * self.bend_range as f32))
+    }
+
+    /// Sends the MPE Configuration Message on the master channel, followed by the per-note
+    /// bend range on each member channel, so that receivers can configure themselves.
+    pub fn send_configuration(&self, sink: &mut impl EventSink, timing: u32) {
+        send_rpn(sink, timing, self.master_channel(), RPN_MPE_CONFIGURATION, self.member_channels, 0);
+        let (first, last) = self.member_range();
+        for channel in first..=last {
+            send_rpn(sink, timing, channel, RPN_PITCH_BEND_SENSITIVITY, self.bend_range, 0);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+use crate::events::{AeolusEvent, EventSink};
+
+/// How many distinct candidates the prioritizer keeps track of. The detector rarely wavers
+/// between more than two or three notes.
+const PRIORITY_CANDIDATES: usize = 8;
+
+/// Which MIDI channel(s) generated notes are sent on. Channels are zero-based, as in events.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChannelAssignment {
+    Fixed(u8),
+    /// Cycle through the channels between the two bounds (inclusive), moving on to the next
+    /// channel with each new note. That way a note's release doesn't get cut off by the next
+    /// note on synths that are monotimbral per channel.
+    RoundRobin(u8, u8),
+    /// A keyboard split: notes below `point` go to `lower`, the others to `upper`, so that one
+    /// source can play a bass patch and a lead patch
+    Split { point: u8, lower: u8, upper: u8 },
+}
+
+impl ChannelAssignment {
+    /// With a split, the channel the side of `pitch` (a MIDI note, maybe fractional) goes to
+    pub fn split_channel(&self, pitch: f32) -> Option<u8> {
+        match *self {
+            ChannelAssignment::Split { point, lower, upper } => Some(if pitch < point as f32 { lower } else { upper }),
+            _ => None,
+        }
+    }
+}
+
+/// Which note the mono output follows when several were detected within the priority window
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum NotePriority {
+    /// The most recently detected one, i.e. no priority at all
+    #[cfg_attr(feature = "plugin", name = "Last")]
+    Last,
+    #[cfg_attr(feature = "plugin", name = "Highest")]
+    Highest,
+    #[cfg_attr(feature = "plugin", name = "Lowest")]
+    Lowest,
+}
+
+/// Remembers the notes detected recently, and picks one of them according to the priority.
+/// That way, a detector wavering between two candidates (typically an octave apart) can be made
+/// to stick to one of them.
+#[derive(Default)]
+pub struct NotePrioritizer {
+    /// Candidate notes, with how many samples ago they were last seen
+    candidates: [Option<(f32, usize)>; PRIORITY_CANDIDATES],
+}
+
+impl NotePrioritizer {
+    pub fn reset(&mut self) {
+        self.candidates = [None; PRIORITY_CANDIDATES];
+    }
+
+    /// Lets time pass, see `Hold::advance()`
+    pub fn advance(&mut self, samples: usize) {
+        for (_, age) in self.candidates.iter_mut().flatten() {
+            *age = age.saturating_add(samples);
+        }
+    }
+
+    /// Returns the note to follow, among `note` and the candidates seen in the last
+    /// `window_samples`
+    pub fn process(&mut self, note: f32, priority: NotePriority, window_samples: usize) -> f32 {
+        for candidate in &mut self.candidates {
+            if matches!(candidate, Some((_, age)) if *age > window_samples) {
+                *candidate = None;
+            }
+        }
+        // The candidate's slot is reused if it was seen before, otherwise the oldest one goes.
+        // Empty slots count as the oldest.
+        let slot = self.candidates.iter().position(|candidate| matches!(candidate, Some((seen, _)) if *seen == note))
+            .or_else(|| self.candidates.iter().position(Option::is_none))
+            .unwrap_or_else(|| {
+                (0..PRIORITY_CANDIDATES)
+                    .max_by_key(|index| self.candidates[*index].map_or(usize::MAX, |(_, age)| age))
+                    .unwrap_or(0)
+            });
+        self.candidates[slot] = Some((note, 0));
+
+        let notes = self.candidates.iter().flatten().map(|(seen, _)| *seen);
+        match priority {
+            NotePriority::Last    => note,
+            NotePriority::Highest => notes.fold(note, f32::max),
+            NotePriority::Lowest  => notes.fold(note, f32::min),
+        }
+    }
+}
+
+/// Keeps track of the note currently sounding, so that note changes result in proper
+/// NoteOff/NoteOn pairs.
+#[derive(Default)]
+pub struct NoteOutput {
+    /// Note number and channel of the note currently sounding
+    current: Option<(u8, u8)>,
+    /// The channel the last note was sent on, used for round-robin rotation
+    last_channel: Option<u8>,
+    /// Sent with NoteOffs
+    release_velocity: f32,
+    /// Whether the sustain pedal is held, deferring NoteOffs until it's released
+    sustain:          bool,
+    /// The notes left sounding by the pedal, as one bit per note for each channel
+    sustained:        [u128; 16],
+}
+
+impl NoteOutput {
+    /// Forgets about the current note without sending anything. Only call this when the host
+    /// can't be sent events anyway, e.g. from `Plugin::reset()`.
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.last_channel = None;
+        self.sustain = false;
+        self.sustained = [0; 16];
+    }
+
+    /// Note number and channel of the note currently sounding, if any
+    pub fn current(&self) -> Option<(u8, u8)> {
+        self.current
+    }
+
+    /// The velocity the next NoteOffs are sent with, which is 0 unless told otherwise
+    pub fn set_release_velocity(&mut self, velocity: f32) {
+        self.release_velocity = velocity;
+    }
+
+    /// Like a keyboard's sustain pedal: while held, released notes keep sounding, and they all
+    /// get their NoteOffs when it's let go
+    pub fn set_sustain(&mut self, sink: &mut impl EventSink, timing: u32, held: bool) {
+        self.sustain = held;
+        if !held {
+            self.release_sustained(sink, timing);
+        }
+    }
+
+    /// Sends a NoteOff for the note currently sounding, if there is one, unless the sustain
+    /// pedal is held
+    pub fn release(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if let Some((note, channel)) = self.current.take() {
+            if self.sustain {
+                self.sustained[channel as usize & 15] |= 1 << note;
+            } else {
+                self.send_note_off(sink, timing, note, channel);
+            }
+        }
+    }
+
+    /// Sends NoteOffs for the notes the sustain pedal kept sounding, even if it is still held
+    pub fn release_sustained(&mut self, sink: &mut impl EventSink, timing: u32) {
+        for channel in 0..16 {
+            let mut notes = std::mem::take(&mut self.sustained[channel]);
+            while notes != 0 {
+                let note = notes.trailing_zeros() as u8;
+                notes &= notes - 1;
+                self.send_note_off(sink, timing, note, channel as u8);
+            }
+        }
+    }
+
+    fn send_note_off(&self, sink: &mut impl EventSink, timing: u32, note: u8, channel: u8) {
+        sink.send(AeolusEvent::NoteOff {
+            timing,
+            channel,
+            note,
+            velocity: self.release_velocity,
+        });
+    }
+
+    /// Makes sure `note` is the note currently sounding, releasing the previous one if needed
+    pub fn play(
+        &mut self,
+        sink:     &mut impl EventSink,
+        timing:   u32,
+        note:     u8,
+        velocity: f32,
+        channels: ChannelAssignment,
+    ) {
+        if matches!(self.current, Some((current, _)) if current == note) {
+            return;
+        }
+        self.release(sink, timing);
+        let channel = self.next_channel(channels, note);
+        // Striking a sustained note again restarts it, as on a piano
+        let sustained = &mut self.sustained[channel as usize & 15];
+        if *sustained & (1 << note) != 0 {
+            *sustained &= !(1 << note);
+            self.send_note_off(sink, timing, note, channel);
+        }
+        sink.send(AeolusEvent::NoteOn {
+            timing,
+            channel,
+            note,
+            velocity,
+        });
+        self.current = Some((note, channel));
+    }
+
+    fn next_channel(&mut self, channels: ChannelAssignment, note: u8) -> u8 {
+        let channel = match channels {
+            ChannelAssignment::Fixed(channel) => channel,
+            ChannelAssignment::Split { point, lower, upper } => if note < point { lower } else { upper },
+            ChannelAssignment::RoundRobin(first, last) => {
+                let (first, last) = (first.min(last), first.max(last));
+                match self.last_channel {
+                    Some(channel) if channel >= first && channel < last => channel + 1,
+                    _ => first,
+                }
+            }
+        };
+        self.last_channel = Some(channel);
+        channel
+    }
+}
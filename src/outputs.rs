@@ -0,0 +1,494 @@
+use crate::bus::{AnalysisEvent, OutputContext, OutputGenerator, PitchEstimate};
+use crate::dynamics::DynamicsZone;
+use crate::engine::{BendReturn, EngineSettings, PitchUnit};
+use crate::events::{AeolusEvent, EventSink};
+use crate::formants::{F1_RANGE, F2_RANGE};
+use crate::glide::{GlideDetector, GlideEvent, MAX_PORTAMENTO_MS};
+use crate::hold::{Confirmation, Hold};
+use crate::mapping::Range;
+use crate::midi::{send_portamento_off, send_portamento_on};
+use crate::mpe::CC_BRIGHTNESS;
+use crate::notes::{ChannelAssignment, NoteOutput, NotePrioritizer};
+use crate::register::VoiceRegister;
+use crate::routing::{CcLane, LaneStates};
+use crate::trill::{TrillDetector, TrillMode, MAX_TRILL_NOTE_MS};
+use crate::utils::*;
+
+/// Levels below this are sent as zero pressure
+const PRESSURE_FLOOR_DB:        f32 = -60.0;
+/// MPE brightness (CC74) follows the harmonic richness on a logarithmic scale, from a pure sine
+/// (1) up to this
+const MAX_BRIGHTNESS_RICHNESS:  f32 = 16.0;
+/// Note confirmation time at the "accurate" end of the Fast/Accurate macro
+const MAX_CONFIRMATION_MS:      f32 = 40.0;
+/// Register changes have to last this long before being sent
+const REGISTER_CONFIRMATION_MS: f32 = 80.0;
+
+/// What the outputs share with each other and with the engine, which also sends some of these
+/// itself (the confidence lane, snapshots, panics)
+#[derive(Default)]
+pub struct OutputState {
+    pub notes:          NoteOutput,
+    /// Smoothing and rate limiting of the CC lanes
+    pub lanes:          LaneStates,
+    /// The channel the sounding note was last bent on, and the value it was bent to
+    pub last_bend:      Option<(u8, f32)>,
+    /// While a bend returns to the center: its channel, the value it started from and how many
+    /// samples it has been going for
+    pub bend_ramp:      Option<(u8, f32, usize)>,
+    /// Whether the note segmenter confirmed a note for the last pitch, for the expressions to
+    /// follow
+    pub note_confirmed: bool,
+}
+
+impl OutputState {
+    pub fn reset(&mut self) {
+        self.notes.reset();
+        self.lanes.reset();
+        self.last_bend = None;
+        self.bend_ramp = None;
+        self.note_confirmed = false;
+    }
+
+    /// The channel the sounding note is on, or the one the next note will likely be on
+    pub fn note_channel(&self, settings: &EngineSettings) -> u8 {
+        match (self.notes.current(), settings.channel_assignment()) {
+            (Some((_, channel)), _) => channel,
+            (None, ChannelAssignment::Fixed(channel) | ChannelAssignment::RoundRobin(channel, _)) => channel,
+            (None, ChannelAssignment::Split { lower, .. }) => lower,
+        }
+    }
+
+    /// Bends the sounding note's channel
+    fn send_bend(&mut self, sink: &mut impl EventSink, timing: u32, channel: u8, value: f32) {
+        sink.send(AeolusEvent::PitchBend { timing, channel, value });
+        self.last_bend = Some((channel, value));
+    }
+
+    /// Once the bent note has ended, or the sounding one moved to another channel, applies the
+    /// bend return setting to the channel it was bent on
+    fn return_bend(&mut self, sink: &mut impl EventSink, settings: &EngineSettings, timing: u32) {
+        let Some((channel, value)) = self.last_bend else {
+            return;
+        };
+        if self.notes.current().map(|(_, channel)| channel) == Some(channel) {
+            return;
+        }
+        self.last_bend = None;
+        match settings.bend_return {
+            BendReturn::Hold => (),
+            BendReturn::Snap => sink.send(AeolusEvent::PitchBend { timing, channel, value: 0.5 }),
+            BendReturn::Ramp => self.bend_ramp = Some((channel, value, 0)),
+        }
+    }
+
+    /// Moves the returning bend (if any) on by a hop, unless a new note took its channel over
+    pub fn advance_bend_ramp(
+        &mut self,
+        sink:        &mut impl EventSink,
+        settings:    &EngineSettings,
+        sample_rate: f32,
+        timing:      u32,
+        hop_size:    usize,
+    ) {
+        let Some((channel, from, elapsed)) = &mut self.bend_ramp else {
+            return;
+        };
+        if self.notes.current().map(|(_, channel)| channel) == Some(*channel) {
+            self.bend_ramp = None;
+            return;
+        }
+        *elapsed += hop_size;
+        let ramp_samples = (settings.bend_return_time / 1000.0 * sample_rate).max(1.0);
+        let progress = (*elapsed as f32 / ramp_samples).min(1.0);
+        let (channel, value) = (*channel, *from + progress * (0.5 - *from));
+        sink.send(AeolusEvent::PitchBend { timing, channel, value });
+        if progress >= 1.0 {
+            self.bend_ramp = None;
+        }
+    }
+}
+
+/// The engine's own outputs, in the order they get the events
+#[derive(Default)]
+pub struct BuiltinOutputs {
+    pub lanes:       LaneOutput,
+    pub notes:       NoteSegmenter,
+    pub expressions: ExpressionOutput,
+}
+
+impl BuiltinOutputs {
+    pub fn generators(&mut self) -> [&mut dyn OutputGenerator; 3] {
+        [&mut self.lanes, &mut self.notes, &mut self.expressions]
+    }
+}
+
+/// The CC lanes following the pitch: the main and cents lanes, the register (which can be sent
+/// as keyswitches too), the formants and practice mode's accuracy. The confidence lane is the
+/// engine's, as it follows the detector even when unvoiced.
+#[derive(Default)]
+pub struct LaneOutput {
+    /// Holds the main lane's value after each change
+    hold:                  Hold<f32>,
+    register_confirmation: Confirmation<VoiceRegister>,
+    /// The register that was last sent, if any
+    sent_register:         Option<VoiceRegister>,
+}
+
+impl LaneOutput {
+    fn send_lanes(
+        &mut self,
+        sink:     &mut impl EventSink,
+        context:  &mut OutputContext,
+        timing:   u32,
+        estimate: PitchEstimate,
+    ) {
+        let settings = *context.settings;
+        let PitchEstimate { pitch, corrected, quantized, hop_ms, .. } = estimate;
+        let cents_range = settings.cents_range;
+        let hold_samples = context.samples(settings.hold_time);
+        let register_samples = context.samples(REGISTER_CONFIRMATION_MS);
+        // With a keyboard split, the pitch lanes go with the notes, to the channel of their side
+        let split_channel = settings.channel_assignment().split_channel(quantized);
+        let pitch_lane = |lane: CcLane| CcLane { channel: split_channel.unwrap_or(lane.channel), ..lane };
+
+        if settings.cc_lane.enabled {
+            let cc_pitch = if settings.cc_stepped { quantized } else { corrected };
+            let cc_frequency = midi_to_freq(cc_pitch);
+            let value = match settings.pitch_unit {
+                PitchUnit::MidiNote => settings.out_of_range.map(
+                    cc_pitch,
+                    settings.min_note,
+                    settings.max_note,
+                    12.0,
+                ),
+                PitchUnit::Frequency => settings.out_of_range.map(
+                    cc_frequency.log2(),
+                    settings.min_freq.log2(),
+                    settings.max_freq.log2(),
+                    1.0,
+                ),
+                PitchUnit::Cents => bipolar_cc_value(cents_deviation(corrected), cents_range),
+            };
+            let value = self.hold.process(value, hold_samples);
+            pitch_lane(settings.cc_lane).send_smoothed(&mut context.state.lanes.pitch, sink, timing, value, hop_ms);
+        }
+
+        if settings.cents_lane.enabled {
+            pitch_lane(settings.cents_lane).send_smoothed(
+                &mut context.state.lanes.cents,
+                sink,
+                timing,
+                bipolar_cc_value(cents_deviation(corrected), cents_range),
+                hop_ms,
+            );
+        }
+
+        if settings.register_analysis() {
+            let register = VoiceRegister::estimate(pitch, estimate.richness);
+            let register = self.register_confirmation.process(register, register_samples);
+            if register.is_some() && register != self.sent_register {
+                self.sent_register = register;
+                self.send_register(sink, context, timing);
+            }
+        }
+
+        if let Some((f1, f2)) = estimate.formants {
+            for (lane, state, formant, (low, high)) in [
+                (settings.f1_lane, &mut context.state.lanes.f1, f1, F1_RANGE),
+                (settings.f2_lane, &mut context.state.lanes.f2, f2, F2_RANGE),
+            ] {
+                if lane.enabled {
+                    let value = Range::new(low.log2(), high.log2()).clamped_position(formant.log2());
+                    lane.send_smoothed(state, sink, timing, value, hop_ms);
+                }
+            }
+        }
+
+        if let (true, Some(deviation)) = (settings.accuracy_lane.enabled, estimate.deviation) {
+            let accuracy = 1.0 - Range::new(0.0, settings.accuracy_range).clamped_position(deviation.abs());
+            settings.accuracy_lane.send_smoothed(&mut context.state.lanes.accuracy, sink, timing, accuracy, hop_ms);
+        }
+    }
+
+    /// Sends the register on the lane, and as a (momentary) keyswitch
+    fn send_register(&mut self, sink: &mut impl EventSink, context: &mut OutputContext, timing: u32) {
+        let Some(register) = self.sent_register else {
+            return;
+        };
+        let settings = context.settings;
+        if settings.register_lane.enabled {
+            let value = register.index() as f32 / 3.0;
+            settings.register_lane.send(&mut context.state.lanes.register, sink, timing, value);
+        }
+        if let Some(lowest) = settings.register_keyswitches {
+            let note = lowest.saturating_add(register.index()).min(127);
+            let channel = context.state.note_channel(settings);
+            sink.send(AeolusEvent::NoteOn { timing, channel, note, velocity: 0.5 });
+            sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+        }
+    }
+}
+
+impl OutputGenerator for LaneOutput {
+    fn handle(&mut self, mut sink: &mut dyn EventSink, context: &mut OutputContext, event: &AnalysisEvent) {
+        match *event {
+            AnalysisEvent::Hop { hop_size, .. } => {
+                self.hold.advance(hop_size);
+                self.register_confirmation.advance(hop_size);
+            }
+            AnalysisEvent::PitchChanged { timing, estimate } => self.send_lanes(&mut sink, context, timing, estimate),
+            AnalysisEvent::Unvoiced { .. } => self.hold.reset(),
+            _ => (),
+        }
+    }
+
+    fn release(&mut self, _sink: &mut dyn EventSink, _context: &mut OutputContext, _timing: u32) {
+        self.hold.reset();
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The note segmenter: turns the quantized pitch into notes, through the priority,
+/// confirmation and trill stages, with portamento following the slides. The dynamics zones
+/// pick the notes' channel and velocity.
+#[derive(Default)]
+pub struct NoteSegmenter {
+    /// Picks among the notes detected recently, before confirmation
+    prioritizer:        NotePrioritizer,
+    /// New notes have to last a little while before being sent
+    confirmation:       Confirmation<f32>,
+    glide:              GlideDetector,
+    /// Where portamento was switched on, if it is
+    portamento_channel: Option<u8>,
+    trill:              TrillDetector,
+    /// The trill keyswitch note and its channel, while it is held
+    keyswitch:          Option<(u8, u8)>,
+    /// The dynamics zone the level is in, while the zones are enabled
+    dynamics_zone:      Option<DynamicsZone>,
+}
+
+impl NoteSegmenter {
+    /// Closes the sounding note, and the glide towards it if there was one
+    pub fn release_note(&mut self, sink: &mut impl EventSink, context: &mut OutputContext, timing: u32) {
+        self.glide.reset();
+        self.stop_portamento(sink, timing);
+        self.trill.reset();
+        self.release_keyswitch(sink, timing);
+        context.state.notes.release(sink, timing);
+        context.state.return_bend(sink, context.settings, timing);
+    }
+
+    /// Closes the sounding note, and starts confirming the next one over
+    pub fn flush(&mut self, sink: &mut impl EventSink, context: &mut OutputContext, timing: u32) {
+        self.release_note(sink, context, timing);
+        self.confirmation.reset();
+    }
+
+    fn hold_keyswitch(&mut self, sink: &mut impl EventSink, context: &OutputContext, timing: u32) {
+        if self.keyswitch.is_none() {
+            let (note, channel) = (context.settings.trill_keyswitch, context.state.note_channel(context.settings));
+            sink.send(AeolusEvent::NoteOn { timing, channel, note, velocity: 0.5 });
+            self.keyswitch = Some((note, channel));
+        }
+    }
+
+    fn release_keyswitch(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if let Some((note, channel)) = self.keyswitch.take() {
+            sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+        }
+    }
+
+    fn stop_portamento(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if let Some(channel) = self.portamento_channel.take() {
+            send_portamento_off(sink, timing, channel);
+        }
+    }
+
+    /// Follows the zone the level is in, sending its keyswitch when it changes
+    fn track_dynamics(
+        &mut self,
+        sink:     &mut impl EventSink,
+        context:  &OutputContext,
+        timing:   u32,
+        estimate: PitchEstimate,
+    ) {
+        let settings = context.settings;
+        let Some(config) = settings.dynamics else {
+            self.dynamics_zone = None;
+            return;
+        };
+        let zone = config.zone(estimate.frame.level_db(), self.dynamics_zone);
+        if self.dynamics_zone.replace(zone) == Some(zone) {
+            return;
+        }
+        if let Some(lowest) = config.keyswitches {
+            let note = lowest.saturating_add(zone.index() as u8).min(127);
+            // On the channel the zone's notes go to
+            let channel = config
+                .channel(zone)
+                .filter(|_| settings.mpe.is_none())
+                .unwrap_or(context.state.note_channel(settings));
+            sink.send(AeolusEvent::NoteOn { timing, channel, note, velocity: 0.5 });
+            sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+        }
+    }
+
+    /// Returns whether a note was confirmed, for its expressions to follow
+    fn segment_notes(
+        &mut self,
+        sink:     &mut impl EventSink,
+        context:  &mut OutputContext,
+        timing:   u32,
+        estimate: PitchEstimate,
+    ) -> bool {
+        let settings = *context.settings;
+        let accuracy = settings.speed_accuracy;
+        let confirmation_samples = context.samples(MAX_CONFIRMATION_MS * accuracy * accuracy);
+
+        if !settings.note_enabled || settings.drums.is_some() {
+            self.release_note(sink, context, timing);
+            return false;
+        }
+        // Out of the filter's window, the hop is skipped altogether, so that a stray octave error
+        // neither replaces the sounding note nor becomes a candidate for the next one
+        if let Some((low, high)) = settings.note_filter {
+            if !(low as f32..=high as f32).contains(&estimate.quantized) {
+                return false;
+            }
+        }
+        // Before the notes, so that synths glide into the new one
+        match (settings.portamento, self.glide.process(estimate.pitch, estimate.hop_ms)) {
+            (true, Some(GlideEvent::Started(ms_per_semitone))) => {
+                let channel = context.state.note_channel(&settings);
+                send_portamento_on(sink, timing, channel, ms_per_semitone / MAX_PORTAMENTO_MS);
+                self.portamento_channel = Some(channel);
+            }
+            (false, _) | (_, Some(GlideEvent::Stopped)) => self.stop_portamento(sink, timing),
+            (true, None) => (),
+        }
+        let window_samples = context.samples(settings.priority_window);
+        let prioritized_pitch = self.prioritizer.process(estimate.quantized, settings.note_priority, window_samples);
+        let confirmed_pitch = match self.confirmation.process(prioritized_pitch, confirmation_samples) {
+            Some(confirmed_pitch) => confirmed_pitch,
+            None                  => return false, // nothing confirmed yet
+        };
+        let note = (confirmed_pitch as u8).min(127);
+        let note = match (settings.trill_mode, self.trill.process(note, context.samples(MAX_TRILL_NOTE_MS))) {
+            (TrillMode::Collapse, Some(principal)) => {
+                self.hold_keyswitch(sink, context, timing);
+                principal
+            }
+            _ => {
+                self.release_keyswitch(sink, timing);
+                note
+            }
+        };
+        // In glide mode, the sounding note is bent to the new pitch rather than replaced
+        let note = match (settings.guitar_config(), context.state.notes.current()) {
+            (Some(config), Some((current, _))) if config.glides_to(current, estimate.bend) => current,
+            _ => note,
+        };
+        let mut velocity = estimate.velocity;
+        let mut channels = settings.channel_assignment();
+        if let (Some(config), Some(zone)) = (settings.dynamics, self.dynamics_zone) {
+            velocity = config.velocity(zone, velocity);
+            if let (None, Some(channel)) = (settings.mpe, config.channel(zone)) {
+                channels = ChannelAssignment::Fixed(channel);
+            }
+        }
+        context.state.notes.play(sink, timing, note, velocity, channels);
+        context.state.return_bend(sink, &settings, timing);
+        true
+    }
+}
+
+impl OutputGenerator for NoteSegmenter {
+    fn handle(&mut self, mut sink: &mut dyn EventSink, context: &mut OutputContext, event: &AnalysisEvent) {
+        let sink = &mut sink;
+        match *event {
+            AnalysisEvent::Hop { hop_size, .. } => {
+                self.prioritizer.advance(hop_size);
+                self.trill.advance(hop_size);
+                self.confirmation.advance(hop_size);
+            }
+            AnalysisEvent::PitchChanged { timing, estimate } => {
+                self.track_dynamics(sink, context, timing, estimate);
+                context.state.note_confirmed = self.segment_notes(sink, context, timing, estimate);
+            }
+            AnalysisEvent::Unvoiced { timing } => {
+                self.prioritizer.reset();
+                self.confirmation.reset();
+                self.release_note(sink, context, timing);
+            }
+            _ => (),
+        }
+    }
+
+    fn release(&mut self, mut sink: &mut dyn EventSink, context: &mut OutputContext, timing: u32) {
+        self.release_note(&mut sink, context, timing);
+        self.prioritizer.reset();
+        self.confirmation.reset();
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The bends and expressions of the sounding note, once the note segmenter confirmed it: guitar
+/// mode's bend, the other two MPE dimensions, note expressions and polyphonic pressure
+#[derive(Default)]
+pub struct ExpressionOutput;
+
+impl ExpressionOutput {
+    fn send_expressions(
+        &mut self,
+        sink:     &mut impl EventSink,
+        context:  &mut OutputContext,
+        timing:   u32,
+        estimate: PitchEstimate,
+    ) {
+        let settings = context.settings;
+        let state = &mut *context.state;
+        let PitchEstimate { frame, bend: bend_pitch, .. } = estimate;
+        if let (Some(config), Some((note, channel))) = (settings.guitar_config(), state.notes.current()) {
+            state.send_bend(sink, timing, channel, config.bend_value(bend_pitch - note as f32));
+        }
+        if let (Some(config), Some((note, channel))) = (settings.mpe, state.notes.current()) {
+            state.send_bend(sink, timing, channel, config.bend_value(bend_pitch - note as f32));
+            // The other two dimensions, on the same channel
+            if settings.mpe_pressure {
+                let pressure = level_to_unit(frame.level, PRESSURE_FLOOR_DB);
+                sink.send(AeolusEvent::ChannelPressure { timing, channel, pressure });
+            }
+            if settings.mpe_brightness {
+                let richness = estimate.richness.max(1.0);
+                let brightness = Range::new(0.0, MAX_BRIGHTNESS_RICHNESS.log2()).clamped_position(richness.log2());
+                sink.send(AeolusEvent::ControlChange { timing, channel, cc: CC_BRIGHTNESS, value: brightness });
+            }
+        }
+        if let (true, Some((note, channel))) = (settings.high_resolution, state.notes.current()) {
+            sink.send(AeolusEvent::PolyTuning { timing, channel, note, tuning: bend_pitch - note as f32 });
+        }
+        if let (true, Some((note, channel))) = (settings.poly_pressure, state.notes.current()) {
+            sink.send(AeolusEvent::PolyPressure {
+                timing,
+                channel,
+                note,
+                pressure: level_to_unit(frame.level, PRESSURE_FLOOR_DB),
+            });
+        }
+    }
+}
+
+impl OutputGenerator for ExpressionOutput {
+    fn handle(&mut self, mut sink: &mut dyn EventSink, context: &mut OutputContext, event: &AnalysisEvent) {
+        if let (AnalysisEvent::PitchChanged { timing, estimate }, true) = (*event, context.state.note_confirmed) {
+            self.send_expressions(&mut sink, context, timing, estimate);
+        }
+    }
+}
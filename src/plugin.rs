@@ -0,0 +1,1415 @@
+use nih_plug::prelude::*;
+use nih_plug_egui::EguiState;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::analysis::{AnalysisQuality, PitchMethod, TrackingRange};
+use crate::calibration::LiveReading;
+use crate::capture::{Capture, Recorder, RecordingSink};
+use crate::delay::OutputDelay;
+use crate::drums::DrumNotes;
+use crate::dynamics::DynamicsConfig;
+use crate::editor;
+use crate::engine::{BendReturn, Engine, EngineSettings, EventTiming, OutOfRange, PitchUnit};
+use crate::gate::GateMode;
+use crate::guitar::{BendReset, GuitarConfig};
+use crate::events::{HostSink, RealtimeMessage};
+use crate::key::AutoKey;
+use crate::learn::{ControllerInput, MidiBindings};
+use crate::load::LoadMeter;
+use crate::logging::PitchLogger;
+use crate::practice::PracticeDisplay;
+use crate::presets::AbSlots;
+use crate::mpe::{MpeConfig, MpeZone};
+use crate::notes::{ChannelAssignment, NotePriority};
+use crate::pulse::BeatNotes;
+use crate::routing::CcLaneParams;
+use crate::scale::{pitch_classes, RootNote, Scale, ScaleType};
+use crate::smf::TimedEvent;
+use crate::state::{self, STATE_VERSION};
+use crate::statistics::{IntonationStats, StatsRecorder};
+use crate::trill::TrillMode;
+use crate::variants::{CcOnly, Full, NoteOnly, Variant};
+use crate::velocity::VelocitySource;
+use crate::worker::{AnalysisWorker, WorkerDetection};
+
+/// More events than this in a single block are sent, but not captured
+const BLOCK_EVENTS_CAPACITY: usize = 4096;
+/// The sustain pedal's controller number
+const SUSTAIN_CC:            u8 = 64;
+
+// This is a shortened version of the gain example with most comments removed, check out
+// https://github.com/robbert-vdh/nih-plug/blob/master/plugins/examples/gain/src/lib.rs to get
+// started
+
+/// Generic over the variant, so that single-purpose plugins can be exported from this crate too
+struct Aeolus<V: Variant = Full> {
+    params: Arc<AeolusParams>,
+    engine: Engine,
+    /// Used instead of the engine's own analyzers when analysis runs in the background
+    worker: Option<AnalysisWorker>,
+    /// How many samples went by the worker so far, including the ones it skipped
+    worker_position: u64,
+    /// Detections from the worker that aren't due before a later block. Allocated in
+    /// `initialize()`.
+    worker_pending: VecDeque<WorkerDetection>,
+    /// The latency that was last reported to the host
+    reported_latency: Option<u32>,
+    /// Whether the host transport was playing during the previous block
+    was_playing: bool,
+    /// Where the host's playhead should be at the start of the next block, if it keeps playing
+    expected_position: Option<i64>,
+    /// Whether the previous block was bypassed
+    was_bypassed: bool,
+    /// The notes held on the MIDI input, as one bit per note
+    held_notes: u128,
+    /// The pitch classes captured so far, while scale capture is on
+    captured_scale: Option<u16>,
+    /// The analyzed channel, and whether analysis ran in the background, in the previous block.
+    /// Changing either makes the detections jump, so the sounding note is closed then.
+    analyzed_input: Option<(i32, bool)>,
+    /// The host's maximum block size, as communicated in `initialize()`
+    max_buffer_size: u32,
+    /// Shared with the editor, which exports it
+    capture: Arc<Mutex<Capture>>,
+    recorder: Recorder,
+    /// The current block's events, on their way to the recorder
+    block_events: Vec<TimedEvent>,
+    /// Capture position used while the host's transport isn't playing
+    free_running_position: u64,
+    /// Started and stopped from the editor
+    pitch_logger: Arc<Mutex<Option<PitchLogger>>>,
+    /// What the editor shows about practice mode
+    practice: Arc<PracticeDisplay>,
+    /// Shared with the editor, which shows and exports them
+    statistics: Arc<Mutex<IntonationStats>>,
+    stats_recorder: StatsRecorder,
+    /// The last detection, which the editor's calibration wizard follows
+    live: Arc<LiveReading>,
+    /// Set by the editor's panic button, cleared once handled
+    panic_requested: Arc<AtomicBool>,
+    /// The CCs from the MIDI input, which the editor applies to the parameters they're bound to
+    controllers: Arc<ControllerInput>,
+    /// How long `process()` takes, and how long the worker takes, relative to real time
+    audio_load: Arc<LoadMeter>,
+    worker_load: Arc<LoadMeter>,
+    /// Allocated in `initialize()`, once the layout is known
+    output_delay: OutputDelay,
+    variant: PhantomData<V>,
+}
+
+#[derive(Params)]
+pub(crate) struct AeolusParams {
+    #[persist = "editor-state"]
+    editor_state: Arc<EguiState>,
+    /// The layout version of the saved state, under `state::VERSION_FIELD`. It's brought up to
+    /// date before anything is restored, see `Plugin::filter_state()`.
+    #[persist = "state-version"]
+    state_version: Arc<AtomicU32>,
+    /// The A/B comparison slots, see `presets.rs`
+    #[persist = "ab-slots"]
+    pub ab_slots: Mutex<AbSlots>,
+    /// MIDI learn's bindings, see `learn.rs`
+    #[persist = "midi-bindings"]
+    pub midi_bindings: Mutex<MidiBindings>,
+
+    /// Passes audio through untouched and stops sending MIDI, after closing the sounding note.
+    /// Hosts use this instead of their own bypass, which would leave notes hanging.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+
+    /// The parameter's ID is used to identify the parameter in the wrappred plugin API. As long as
+    /// these IDs remain constant, you can rename and reorder these fields as you wish. The
+    /// parameters are exposed to the host in the same order they were defined. In this case, this
+    /// gain parameter is stored as linear gain while the values are displayed in decibels. It trims
+    /// the input before analysis.
+    #[id = "gain"]
+    pub gain: FloatParam,
+
+    /// Applied to the audio leaving the plugin. Unlike the gain above, which only trims the
+    /// analysis input, this one is heard.
+    #[id = "output_gain"]
+    pub output_gain: FloatParam,
+
+    /// Delays the audio leaving the plugin by the latency we report. Hosts compensate for that
+    /// latency by moving our output earlier, which lines up the MIDI with the rest of the
+    /// session, but leaves the undelayed audio ahead of it. With this on, the source and the
+    /// synth it drives line up when layered.
+    #[id = "delay_audio"]
+    pub delay_audio: BoolParam,
+
+    /// The main CC lane, encoding the pitch in the unit below
+    #[nested(id_prefix = "cc", group = "Main CC")]
+    pub cc_lane: CcLaneParams,
+
+    /// What the main lane encodes. Each unit has its own range bounds below.
+    #[id = "pitch_unit"]
+    pub pitch_unit: EnumParam<PitchUnit>,
+    /// What becomes of pitches outside of the main lane's range bounds
+    #[id = "out_of_range"]
+    pub out_of_range: EnumParam<OutOfRange>,
+
+    /// A single macro going from fast (short windows, no smoothing, notes sent as soon as they
+    /// are detected) to accurate (long windows, smoothing, and notes only sent once confirmed)
+    #[id = "speed_accuracy"]
+    pub speed_accuracy: FloatParam,
+    /// Whether that smoothing weights each estimate by the detector's confidence: doubtful ones
+    /// barely move the pitch, confident ones update it quickly
+    #[id = "confidence_smoothing"]
+    pub confidence_smoothing: BoolParam,
+    /// The smoothing time (in milliseconds) of pitch bends and note expressions, which the macro
+    /// above doesn't affect, so that bends can stay tight while the CC lanes are smoothed
+    #[id = "bend_smoothing"]
+    pub bend_smoothing: FloatParam,
+    /// What a channel's bend does once its note ends, and how long it takes (in milliseconds)
+    /// to get back to the center when ramping
+    #[id = "bend_return"]
+    pub bend_return: EnumParam<BendReturn>,
+    #[id = "bend_return_time"]
+    pub bend_return_time: FloatParam,
+
+    /// Selects the analysis window and hop. Lower ranges track lower notes, at the cost of latency.
+    #[id = "tracking_range"]
+    pub tracking_range: EnumParam<TrackingRange>,
+    /// The detection method. YIN suits most sources, the others are fallbacks for those it
+    /// struggles with.
+    #[id = "pitch_method"]
+    pub pitch_method: EnumParam<PitchMethod>,
+
+    /// Whether events are sent when the analysis completes, or back-dated towards when the
+    /// sound they're about actually happened
+    #[id = "event_timing"]
+    pub event_timing: EnumParam<EventTiming>,
+
+    /// Which input channel is analyzed, 0 meaning the downmix of all of them. Channels the
+    /// current layout doesn't have give silence.
+    #[id = "analysis_channel"]
+    pub analysis_channel: IntParam,
+
+    /// Whether to run pitch analysis on a thread of its own, so that expensive detectors can't
+    /// cause audio dropouts. Detections then come one block later, which is reported as latency.
+    /// Offline bounces ignore this, so that rendering the same input twice gives the same MIDI.
+    #[id = "background"]
+    pub background_analysis: BoolParam,
+
+    /// Whether to detect pitch at all. The other analyses only run when something uses them, but
+    /// pitch detection feeds most outputs, so it gets a switch of its own, for rhythm-only setups.
+    #[id = "pitch_analysis"]
+    pub pitch_analysis: BoolParam,
+
+    /// Input quieter than the gate threshold (in dB) is treated as silence. In adaptive mode, the
+    /// threshold follows the noise floor measured during unvoiced periods, staying the margin
+    /// above it.
+    #[id = "gate_mode"]
+    pub gate_mode: EnumParam<GateMode>,
+    #[id = "gate_threshold"]
+    pub gate_threshold: FloatParam,
+    #[id = "gate_margin"]
+    pub gate_margin: FloatParam,
+
+    /// Whether to subtract the background noise from the analysis input. The noise is learned
+    /// whenever there's nothing to track, so constant hum and hiss stop degrading tracking.
+    #[id = "denoise"]
+    pub denoise: BoolParam,
+
+    /// Whether to refine the detector's estimate around the detected period, for sub-cent
+    /// accuracy. This matters most for the cents outputs.
+    #[id = "refinement"]
+    pub refinement: BoolParam,
+
+    /// Whether to correct detections an octave below the pitch, which distorted or chorused
+    /// guitar is prone to. The guitar preset turns this on.
+    #[id = "subharmonic_suppression"]
+    pub subharmonic_suppression: BoolParam,
+
+    /// Only analyzes one hop in this many, holding the output in between, to save CPU when there
+    /// are many instances. The resulting update rate is shown in the editor.
+    #[id = "decimation"]
+    pub decimation: IntParam,
+
+    /// Whether to quantize the main lane to semitone steps (with the hysteresis below applied),
+    /// instead of sending a continuous sweep. This has no effect when encoding cents.
+    #[id = "cc_stepped"]
+    pub cc_stepped: BoolParam,
+
+    /// How far (in cents) past the halfway point between two semitones the pitch needs to go
+    /// before the quantized note changes. Used both by note output and by stepped CC.
+    #[id = "hysteresis"]
+    pub hysteresis: FloatParam,
+
+    /// Partial pitch correction: how far the continuous pitch outputs (CC lanes and bends) are
+    /// pulled towards the nearest semitone, from 0 (untouched) to 100% (snapped)
+    #[id = "correction"]
+    pub correction: FloatParam,
+
+    /// Splits the pitch track at the crossover frequency (in Hz): only the slower drift moves the
+    /// notes and CC lanes, the faster vibrato goes to the bends (MPE, guitar mode and note
+    /// expressions) on top of it
+    #[id = "vibrato_split"]
+    pub vibrato_split: BoolParam,
+    #[id = "vibrato_crossover"]
+    pub vibrato_crossover: FloatParam,
+
+    /// The scale notes, stepped CC and pitch correction snap to
+    #[id = "scale_type"]
+    pub scale_type: EnumParam<ScaleType>,
+    #[id = "scale_root"]
+    pub scale_root: EnumParam<RootNote>,
+    /// Treats pitches too far from the scale as unvoiced, rather than snapping them to it
+    #[id = "scale_gate"]
+    pub scale_gate: BoolParam,
+    /// In cents, how far from the nearest note of the scale a pitch may be when gating
+    #[id = "scale_gate_distance"]
+    pub scale_gate_distance: FloatParam,
+    /// Makes the scale follow the key detected from the notes played recently
+    #[id = "auto_key"]
+    pub auto_key: BoolParam,
+    /// How well the notes must fit a key for auto-key mode to adopt it
+    #[id = "auto_key_confidence"]
+    pub auto_key_confidence: FloatParam,
+    /// In seconds, how long auto-key mode keeps a key at least before changing
+    #[id = "auto_key_interval"]
+    pub auto_key_interval: FloatParam,
+    /// While on, the pitch classes of the notes held on the MIDI input (and of those played
+    /// while it stays on) become the learned scale. Being a parameter, it can be automated to
+    /// follow the song's chords.
+    #[id = "capture_scale"]
+    pub capture_scale: BoolParam,
+    /// The pitch classes captured last, as a 12-bit mask
+    #[persist = "learned-scale"]
+    pub learned_scale: Arc<AtomicU16>,
+
+    /// Unvoiced gaps shorter than this (in milliseconds) are bridged: the last pitch is held and
+    /// no NoteOff is sent. This avoids machine-gun retriggers on slightly breathy notes.
+    #[id = "bridge_time"]
+    pub bridge_time: FloatParam,
+
+    /// After each change of the note or main CC value, the new value is kept for at least this
+    /// long (in milliseconds) before another change is accepted
+    #[id = "hold_time"]
+    pub hold_time: FloatParam,
+
+    /// Range bounds used when the main lane encodes MIDI notes
+    #[id = "min_note"]
+    pub min_note: FloatParam,
+    #[id = "max_note"]
+    pub max_note: FloatParam,
+
+    /// Range bounds used when the main lane encodes frequencies
+    #[id = "min_freq"]
+    pub min_freq: FloatParam,
+    #[id = "max_freq"]
+    pub max_freq: FloatParam,
+
+    /// The deviation (in cents) that is mapped to the ends of the CC range when encoding cents.
+    /// This is shared with the dedicated cents output below.
+    #[id = "cents_range"]
+    pub cents_range: FloatParam,
+
+    /// The deviation from the nearest semitone, sent as a bipolar CC centered at 64. This turns
+    /// intonation wobble into a modulation source of its own.
+    #[nested(id_prefix = "cents", group = "Cents CC")]
+    pub cents_lane: CcLaneParams,
+
+    /// The detector's confidence, so that tracking quality can be recorded or used to gate
+    /// effects downstream
+    #[nested(id_prefix = "confidence", group = "Confidence CC")]
+    pub confidence_lane: CcLaneParams,
+
+    /// Whether to send a note for each (quantized) semitone the input goes through
+    #[id = "note_enabled"]
+    pub note_enabled: BoolParam,
+    /// Drops the notes detected outside of this window, e.g. octave errors below a bass's lowest
+    /// string. Unlike the main lane's range bounds, this only applies to notes.
+    #[id = "note_filter"]
+    pub note_filter: BoolParam,
+    #[id = "note_filter_low"]
+    pub note_filter_low: IntParam,
+    #[id = "note_filter_high"]
+    pub note_filter_high: IntParam,
+
+    /// The MIDI channel notes are sent on, unless channel rotation is enabled
+    #[id = "note_channel"]
+    pub note_channel: IntParam,
+    /// What the velocity of notes follows. Spectral flux suits bowed and blown instruments,
+    /// whose level rises too slowly to say anything at the start of a note.
+    #[id = "velocity_source"]
+    pub velocity_source: EnumParam<VelocitySource>,
+    /// Whether NoteOffs get a velocity following how fast the input was fading, for the few
+    /// instruments that respond to release velocity
+    #[id = "release_velocity"]
+    pub release_velocity: BoolParam,
+    /// Whether a sustain pedal (CC64) on the MIDI input defers NoteOffs while held, for
+    /// keyboard-style sustain when converting melodic audio
+    #[id = "sustain_pedal"]
+    pub sustain_pedal: BoolParam,
+
+    /// Whether to cycle generated notes across the channel range below
+    #[id = "channel_rotation"]
+    pub channel_rotation: BoolParam,
+    #[id = "rotation_first"]
+    pub rotation_first: IntParam,
+    #[id = "rotation_last"]
+    pub rotation_last: IntParam,
+
+    /// A keyboard split: notes below the split point (and the main and cents CC lanes, while
+    /// the pitch is below it) go to the note channel, the others to the upper channel. This
+    /// takes over from channel rotation.
+    #[id = "keyboard_split"]
+    pub keyboard_split: BoolParam,
+    #[id = "split_point"]
+    pub split_point: IntParam,
+    #[id = "split_upper_channel"]
+    pub split_upper_channel: IntParam,
+
+    /// In MPE mode, notes are spread over the member channels of the zone and each of them gets
+    /// its own pitch bend. Channel rotation settings are ignored.
+    #[id = "mpe_enabled"]
+    pub mpe_enabled: BoolParam,
+    #[id = "mpe_zone"]
+    pub mpe_zone: EnumParam<MpeZone>,
+    #[id = "mpe_members"]
+    pub mpe_member_channels: IntParam,
+    /// The per-note pitch bend range, in semitones
+    #[id = "mpe_bend_range"]
+    pub mpe_bend_range: IntParam,
+    /// The level and harmonic richness of the input, as the sounding note's pressure and
+    /// brightness (CC74), for the full three-dimensional MPE stream
+    #[id = "mpe_pressure"]
+    pub mpe_pressure: BoolParam,
+    #[id = "mpe_brightness"]
+    pub mpe_brightness: BoolParam,
+
+    /// "MIDI guitar" mode: notes as usual, plus the channel pitch bend following the deviation
+    /// from the sounding note, within the bend range (in semitones). Ignored in MPE mode.
+    #[id = "guitar_mode"]
+    pub guitar_mode: BoolParam,
+    #[id = "guitar_bend_range"]
+    pub guitar_bend_range: IntParam,
+    #[id = "guitar_bend_reset"]
+    pub guitar_bend_reset: EnumParam<BendReset>,
+
+    /// Which note the output follows when the detector wavers between several of them within
+    /// the priority window (in milliseconds)
+    #[id = "note_priority"]
+    pub note_priority: EnumParam<NotePriority>,
+    #[id = "priority_window"]
+    pub priority_window: FloatParam,
+
+    /// Whether to switch portamento on (CC65, with its time in CC5) while the performer slides
+    /// from one note to another, and off for discrete note changes
+    #[id = "portamento"]
+    pub portamento: BoolParam,
+
+    /// Whether to send MIDI clock, start and stop following the beats detected in the input, so
+    /// that a drummer can clock external gear. nih_plug sends these as SysEx, which not every
+    /// host passes on.
+    #[id = "clock_output"]
+    pub clock_output: BoolParam,
+
+    /// Whether to send a short note on each detected beat, with its velocity following the
+    /// beat's strength, and a different note on the first beat of each bar
+    #[id = "beat_notes"]
+    pub beat_notes: BoolParam,
+    #[id = "beat_channel"]
+    pub beat_channel: IntParam,
+    #[id = "beat_note"]
+    pub beat_note: IntParam,
+    #[id = "downbeat_note"]
+    pub downbeat_note: IntParam,
+    #[id = "beats_per_bar"]
+    pub beats_per_bar: IntParam,
+
+    /// Percussive mode: transients are sorted into low, mid and high bands, each sending its own
+    /// drum note, and the detected pitch no longer makes notes
+    #[id = "drums"]
+    pub drums: BoolParam,
+    #[id = "drum_channel"]
+    pub drum_channel: IntParam,
+    #[id = "kick_note"]
+    pub kick_note: IntParam,
+    #[id = "snare_note"]
+    pub snare_note: IntParam,
+    #[id = "hat_note"]
+    pub hat_note: IntParam,
+
+    /// Trills can either be sent note for note, or collapsed to their principal note with the
+    /// keyswitch below held, for cleaner transcriptions
+    #[id = "trill_mode"]
+    pub trill_mode: EnumParam<TrillMode>,
+    #[id = "trill_keyswitch"]
+    pub trill_keyswitch: IntParam,
+
+    /// Whether to send polyphonic key pressure for the sounding note, following the input level
+    #[id = "poly_pressure"]
+    pub poly_pressure: BoolParam,
+
+    /// Dynamics zones: the level (in dB) the medium and loud zones start at. Each zone can send
+    /// its notes to a channel of its own (but in MPE mode), have their velocity offset (down for
+    /// the soft zone, up for the loud one), and send a keyswitch when it's entered, starting at
+    /// the base note for the soft zone.
+    #[id = "dynamics_zones"]
+    pub dynamics_zones: BoolParam,
+    #[id = "zone_medium_threshold"]
+    pub zone_medium_threshold: FloatParam,
+    #[id = "zone_loud_threshold"]
+    pub zone_loud_threshold: FloatParam,
+    #[id = "zone_channels"]
+    pub zone_channels: BoolParam,
+    #[id = "zone_soft_channel"]
+    pub zone_soft_channel: IntParam,
+    #[id = "zone_medium_channel"]
+    pub zone_medium_channel: IntParam,
+    #[id = "zone_loud_channel"]
+    pub zone_loud_channel: IntParam,
+    #[id = "zone_velocity_offset"]
+    pub zone_velocity_offset: FloatParam,
+    #[id = "zone_keyswitches"]
+    pub zone_keyswitches: BoolParam,
+    #[id = "zone_keyswitch_base"]
+    pub zone_keyswitch_base: IntParam,
+
+    /// Whether to also send the sounding note's exact pitch as a high-resolution note
+    /// expression. Only CLAP hosts support those, so this is ignored in other formats.
+    #[id = "high_resolution"]
+    pub high_resolution: BoolParam,
+
+    /// The estimated vocal register, from chest (0) to falsetto (127) in four steps, and as
+    /// keyswitches starting at the note below, so that sample libraries can switch articulations
+    #[nested(id_prefix = "register", group = "Register CC")]
+    pub register_lane: CcLaneParams,
+    #[id = "register_keyswitches"]
+    pub register_keyswitches: BoolParam,
+    #[id = "register_keyswitch_base"]
+    pub register_keyswitch_base: IntParam,
+
+    /// The first two formants, tracked by linear prediction, so that vowels can drive a synth's
+    /// filters
+    #[nested(id_prefix = "f1", group = "F1 CC")]
+    pub f1_lane: CcLaneParams,
+    #[nested(id_prefix = "f2", group = "F2 CC")]
+    pub f2_lane: CcLaneParams,
+
+    /// Practice mode: while a note is held on the MIDI input, the detected pitch is compared to
+    /// it, and this lane goes from 1 (spot on) to 0 (off by the accuracy range or more)
+    #[nested(id_prefix = "accuracy", group = "Accuracy CC")]
+    pub accuracy_lane: CcLaneParams,
+    /// In cents
+    #[id = "accuracy_range"]
+    pub accuracy_range: FloatParam,
+
+    /// The detected pitch and input level, for hosts that record automation rather than MIDI.
+    /// nih_plug gives the audio thread no way of changing parameters, so the editor updates
+    /// these from the live reading, which means they only move while it is open. They're meant
+    /// to be recorded, not played back: automation playing on them fights the editor's updates.
+    /// Presets and A/B slots leave them out.
+    #[id = "pitch_output"]
+    pub pitch_output: FloatParam,
+    #[id = "level_output"]
+    pub level_output: FloatParam,
+}
+
+impl<V: Variant> Default for Aeolus<V> {
+    fn default() -> Self {
+        let capture = Arc::new(Mutex::new(Capture::default()));
+        let statistics = Arc::new(Mutex::new(IntonationStats::default()));
+        Self {
+            params: Arc::new(AeolusParams::default()),
+            engine: Engine::default(),
+            worker: None,
+            worker_position: 0,
+            worker_pending: VecDeque::new(),
+            reported_latency: None,
+            was_playing: false,
+            expected_position: None,
+            was_bypassed: false,
+            held_notes: 0,
+            captured_scale: None,
+            analyzed_input: None,
+            max_buffer_size: 0,
+            capture: capture.clone(),
+            recorder: Recorder::new(capture),
+            block_events: Vec::with_capacity(BLOCK_EVENTS_CAPACITY),
+            free_running_position: 0,
+            pitch_logger: Arc::new(Mutex::new(None)),
+            practice: Arc::new(PracticeDisplay::default()),
+            statistics: statistics.clone(),
+            stats_recorder: StatsRecorder::new(statistics),
+            live: Arc::new(LiveReading::default()),
+            panic_requested: Arc::new(AtomicBool::new(false)),
+            controllers: Arc::new(ControllerInput::default()),
+            audio_load: Arc::new(LoadMeter::default()),
+            worker_load: Arc::new(LoadMeter::default()),
+            output_delay: OutputDelay::default(),
+            variant: PhantomData,
+        }
+    }
+}
+
+impl Default for AeolusParams {
+    fn default() -> Self {
+        Self {
+            editor_state: editor::default_state(),
+            state_version: Arc::new(AtomicU32::new(STATE_VERSION)),
+            ab_slots: Mutex::new(AbSlots::default()),
+            midi_bindings: Mutex::new(MidiBindings::default()),
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            // This gain is stored as linear gain. NIH-plug comes with useful conversion functions
+            // to treat these kinds of parameters as if we were dealing with decibels. Storing this
+            // as decibels is easier to work with, but requires a conversion for every sample.
+            gain: FloatParam::new(
+                "Input gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(30.0),
+                    // This makes the range appear as if it was linear when displaying the values as
+                    // decibels
+                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                },
+            )
+            // Because the gain parameter is stored as linear gain instead of storing the value as
+            // decibels, we need logarithmic smoothing
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            // There are many predefined formatters we can use here. If the gain was stored as
+            // decibels instead of as a linear gain value, we could have also used the
+            // `.with_step_size(0.1)` function to get internal rounding.
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            output_gain: FloatParam::new(
+                "Output gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-30.0),
+                    max: util::db_to_gain(30.0),
+                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" dB")
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            delay_audio: BoolParam::new("Delay audio", false),
+
+            cc_lane: CcLaneParams::new("Main CC", true, 1),
+            pitch_unit: EnumParam::new("Pitch unit", PitchUnit::MidiNote),
+            out_of_range: EnumParam::new("Out of range", OutOfRange::Clamp),
+            speed_accuracy: FloatParam::new("Fast/Accurate", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            confidence_smoothing: BoolParam::new("Confidence-weighted smoothing", true),
+            bend_smoothing: FloatParam::new("Bend smoothing", 5.0, FloatRange::Skewed {
+                min: 0.0,
+                max: 200.0,
+                factor: FloatRange::skew_factor(-2.0),
+            })
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            bend_return: EnumParam::new("Bend return", BendReturn::Hold),
+            bend_return_time: FloatParam::new("Bend return time", 50.0, FloatRange::Linear { min: 1.0, max: 500.0 })
+                .with_unit(" ms")
+                .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            tracking_range: EnumParam::new("Tracking range", TrackingRange::Normal),
+            pitch_method: EnumParam::new("Detector", PitchMethod::Yin),
+            event_timing: EnumParam::new("Event timing", EventTiming::HopEnd),
+            analysis_channel: IntParam::new("Analyzed channel", 0, IntRange::Linear { min: 0, max: 8 })
+                .with_value_to_string(Arc::new(|channel| match channel {
+                    0 => String::from("Downmix"),
+                    channel => channel.to_string(),
+                }))
+                .with_string_to_value(Arc::new(|string| match string.trim() {
+                    "Downmix" => Some(0),
+                    string => string.parse().ok(),
+                })),
+            background_analysis: BoolParam::new("Background analysis", false),
+            pitch_analysis: BoolParam::new("Pitch analysis", true),
+            gate_mode: EnumParam::new("Gate", GateMode::Off),
+            gate_threshold: FloatParam::new("Gate threshold", -50.0, FloatRange::Linear { min: -90.0, max: 0.0 })
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            gate_margin: FloatParam::new("Gate margin", 10.0, FloatRange::Linear { min: 0.0, max: 40.0 })
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            denoise: BoolParam::new("Denoise", false),
+            refinement: BoolParam::new("Fine refinement", true),
+            subharmonic_suppression: BoolParam::new("Subharmonic suppression", false),
+            decimation: IntParam::new("Analyze every", 1, IntRange::Linear { min: 1, max: 16 })
+                .with_unit(" hops"),
+            cc_stepped: BoolParam::new("Stepped CC", false),
+            hysteresis: FloatParam::new("Hysteresis", 15.0, FloatRange::Linear { min: 0.0, max: 50.0 })
+                .with_unit(" ct")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            correction: FloatParam::new("Pitch correction", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            vibrato_split: BoolParam::new("Vibrato split", false),
+            vibrato_crossover: FloatParam::new("Vibrato crossover", 3.0, FloatRange::Skewed {
+                min: 0.5,
+                max: 10.0,
+                factor: FloatRange::skew_factor(-1.0),
+            })
+                .with_unit(" Hz")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            scale_type: EnumParam::new("Scale", ScaleType::Chromatic),
+            scale_root: EnumParam::new("Scale root", RootNote::C),
+            scale_gate: BoolParam::new("Scale gate", false),
+            scale_gate_distance: FloatParam::new("Scale gate distance", 25.0, FloatRange::Linear { min: 5.0, max: 50.0 })
+                .with_unit(" ct")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            auto_key: BoolParam::new("Auto key", false),
+            auto_key_confidence: FloatParam::new("Auto key confidence", 0.7, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            auto_key_interval: FloatParam::new("Key change interval", 8.0, FloatRange::Linear { min: 0.0, max: 60.0 })
+                .with_unit(" s")
+                .with_step_size(0.5),
+            capture_scale: BoolParam::new("Capture scale", false),
+            learned_scale: Arc::new(AtomicU16::new(0)),
+            bridge_time: FloatParam::new("Bridge time", 30.0, FloatRange::Linear { min: 0.0, max: 500.0 })
+                .with_unit(" ms")
+                .with_step_size(1.0),
+            hold_time: FloatParam::new("Hold time", 0.0, FloatRange::Linear { min: 0.0, max: 1000.0 })
+                .with_unit(" ms")
+                .with_step_size(1.0),
+
+            min_note: FloatParam::new("Min note", 57.0, FloatRange::Linear { min: 0.0, max: 127.0 })
+                .with_step_size(0.01)
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            max_note: FloatParam::new("Max note", 81.0, FloatRange::Linear { min: 0.0, max: 127.0 })
+                .with_step_size(0.01)
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            // Skewed so that the range feels roughly logarithmic, like the pitch it encodes
+            min_freq: FloatParam::new(
+                "Min frequency",
+                220.0,
+                FloatRange::Skewed { min: 20.0, max: 5000.0, factor: FloatRange::skew_factor(-2.0) },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            max_freq: FloatParam::new(
+                "Max frequency",
+                880.0,
+                FloatRange::Skewed { min: 20.0, max: 5000.0, factor: FloatRange::skew_factor(-2.0) },
+            )
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            cents_range: FloatParam::new("Cents range", 50.0, FloatRange::Linear { min: 1.0, max: 50.0 })
+                .with_unit(" ct")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            cents_lane: CcLaneParams::new("Cents CC", false, 2),
+            confidence_lane: CcLaneParams::new("Confidence CC", false, 3),
+
+            note_enabled: BoolParam::new("Note output", true),
+            note_filter: BoolParam::new("Note filter", false),
+            note_filter_low: IntParam::new("Lowest note", 28, IntRange::Linear { min: 0, max: 127 }),
+            note_filter_high: IntParam::new("Highest note", 96, IntRange::Linear { min: 0, max: 127 }),
+            // Channels are displayed 1-based, as users are used to, but nih_plug wants them
+            // 0-based, hence the `- 1` wherever they're used
+            note_channel: IntParam::new("Note channel", 1, IntRange::Linear { min: 1, max: 16 }),
+            velocity_source: EnumParam::new("Velocity", VelocitySource::Fixed),
+            release_velocity: BoolParam::new("Release velocity", false),
+            sustain_pedal: BoolParam::new("Sustain pedal", true),
+            channel_rotation: BoolParam::new("Channel rotation", false),
+            rotation_first: IntParam::new("Rotation first channel", 2, IntRange::Linear { min: 1, max: 16 }),
+            rotation_last: IntParam::new("Rotation last channel", 8, IntRange::Linear { min: 1, max: 16 }),
+            keyboard_split: BoolParam::new("Keyboard split", false),
+            split_point: IntParam::new("Split point", 60, IntRange::Linear { min: 0, max: 127 }),
+            split_upper_channel: IntParam::new("Upper channel", 2, IntRange::Linear { min: 1, max: 16 }),
+
+            mpe_enabled: BoolParam::new("MPE", false),
+            mpe_zone: EnumParam::new("MPE zone", MpeZone::Lower),
+            mpe_member_channels: IntParam::new("MPE member channels", 15, IntRange::Linear { min: 1, max: 15 }),
+            // 48 semitones is the default per-note bend range in the MPE specification
+            mpe_bend_range: IntParam::new("MPE bend range", 48, IntRange::Linear { min: 1, max: 96 })
+                .with_unit(" st"),
+            mpe_pressure: BoolParam::new("MPE pressure", true),
+            mpe_brightness: BoolParam::new("MPE brightness", false),
+
+            guitar_mode: BoolParam::new("Guitar mode", false),
+            guitar_bend_range: IntParam::new("Guitar bend range", 12, IntRange::Linear { min: 1, max: 48 })
+                .with_unit(" st"),
+            guitar_bend_reset: EnumParam::new("Guitar bend reset", BendReset::NewNote),
+
+            note_priority: EnumParam::new("Note priority", NotePriority::Last),
+            priority_window: FloatParam::new("Priority window", 100.0, FloatRange::Linear { min: 0.0, max: 1000.0 })
+                .with_unit(" ms")
+                .with_step_size(1.0),
+
+            portamento: BoolParam::new("Portamento CCs", false),
+
+            clock_output: BoolParam::new("MIDI clock", false),
+
+            beat_notes: BoolParam::new("Beat notes", false),
+            // The General MIDI drum channel, with a side stick on beats and a kick on downbeats
+            beat_channel: IntParam::new("Beat channel", 10, IntRange::Linear { min: 1, max: 16 }),
+            beat_note: IntParam::new("Beat note", 37, IntRange::Linear { min: 0, max: 127 }),
+            downbeat_note: IntParam::new("Downbeat note", 36, IntRange::Linear { min: 0, max: 127 }),
+            beats_per_bar: IntParam::new("Beats per bar", 4, IntRange::Linear { min: 1, max: 16 }),
+
+            drums: BoolParam::new("Drum mode", false),
+            // General MIDI's kick, snare and closed hi-hat
+            drum_channel: IntParam::new("Drum channel", 10, IntRange::Linear { min: 1, max: 16 }),
+            kick_note: IntParam::new("Kick note", 36, IntRange::Linear { min: 0, max: 127 }),
+            snare_note: IntParam::new("Snare note", 38, IntRange::Linear { min: 0, max: 127 }),
+            hat_note: IntParam::new("Hi-hat note", 42, IntRange::Linear { min: 0, max: 127 }),
+
+            trill_mode: EnumParam::new("Trills", TrillMode::Faithful),
+            // C1, where sample libraries usually put their keyswitches
+            trill_keyswitch: IntParam::new("Trill keyswitch", 24, IntRange::Linear { min: 0, max: 127 }),
+
+            poly_pressure: BoolParam::new("Poly pressure", false),
+            high_resolution: BoolParam::new("High-resolution pitch", false),
+
+            register_lane: CcLaneParams::new("Register CC", false, 9),
+            dynamics_zones: BoolParam::new("Dynamics zones", false),
+            zone_medium_threshold: FloatParam::new("Medium zone threshold", -35.0, FloatRange::Linear { min: -90.0, max: 0.0 })
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            zone_loud_threshold: FloatParam::new("Loud zone threshold", -15.0, FloatRange::Linear { min: -90.0, max: 0.0 })
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            zone_channels: BoolParam::new("Zone channels", false),
+            zone_soft_channel: IntParam::new("Soft zone channel", 1, IntRange::Linear { min: 1, max: 16 }),
+            zone_medium_channel: IntParam::new("Medium zone channel", 2, IntRange::Linear { min: 1, max: 16 }),
+            zone_loud_channel: IntParam::new("Loud zone channel", 3, IntRange::Linear { min: 1, max: 16 }),
+            zone_velocity_offset: FloatParam::new("Zone velocity offset", 0.0, FloatRange::Linear { min: 0.0, max: 0.5 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            zone_keyswitches: BoolParam::new("Zone keyswitches", false),
+            zone_keyswitch_base: IntParam::new("Zone keyswitch base", 0, IntRange::Linear { min: 0, max: 125 }),
+            register_keyswitches: BoolParam::new("Register keyswitches", false),
+            register_keyswitch_base: IntParam::new("Register keyswitch base", 12, IntRange::Linear { min: 0, max: 124 }),
+
+            f1_lane: CcLaneParams::new("F1 CC", false, 14),
+            f2_lane: CcLaneParams::new("F2 CC", false, 15),
+
+            accuracy_lane: CcLaneParams::new("Accuracy CC", false, 4),
+            accuracy_range: FloatParam::new("Accuracy range", 50.0, FloatRange::Linear { min: 5.0, max: 100.0 })
+                .with_unit(" ct")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            pitch_output: FloatParam::new("Detected pitch", 60.0, FloatRange::Linear { min: 0.0, max: 127.0 })
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            level_output: FloatParam::new("Detected level", -60.0, FloatRange::Linear { min: -60.0, max: 0.0 })
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_rounded(1)),
+        }
+    }
+}
+
+
+
+impl AeolusParams {
+    fn mpe_config(&self) -> Option<MpeConfig> {
+        if self.mpe_enabled.value() {
+            Some(MpeConfig {
+                zone: self.mpe_zone.value(),
+                member_channels: self.mpe_member_channels.value() as u8,
+                bend_range: self.mpe_bend_range.value() as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn guitar_config(&self) -> Option<GuitarConfig> {
+        if self.guitar_mode.value() {
+            Some(GuitarConfig {
+                bend_range: self.guitar_bend_range.value() as u8,
+                reset: self.guitar_bend_reset.value(),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn beat_notes(&self) -> Option<BeatNotes> {
+        if self.beat_notes.value() {
+            Some(BeatNotes {
+                channel: (self.beat_channel.value() - 1) as u8,
+                beat: self.beat_note.value() as u8,
+                downbeat: self.downbeat_note.value() as u8,
+                beats_per_bar: self.beats_per_bar.value() as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn drum_notes(&self) -> Option<DrumNotes> {
+        if self.drums.value() {
+            Some(DrumNotes {
+                channel: (self.drum_channel.value() - 1) as u8,
+                kick: self.kick_note.value() as u8,
+                snare: self.snare_note.value() as u8,
+                hat: self.hat_note.value() as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn auto_key(&self) -> Option<AutoKey> {
+        if self.auto_key.value() {
+            Some(AutoKey {
+                confidence: self.auto_key_confidence.value(),
+                min_interval: self.auto_key_interval.value(),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn dynamics_config(&self) -> Option<DynamicsConfig> {
+        let channels = [&self.zone_soft_channel, &self.zone_medium_channel, &self.zone_loud_channel]
+            .map(|channel| (channel.value() - 1) as u8);
+        let offset = self.zone_velocity_offset.value();
+        self.dynamics_zones.value().then(|| DynamicsConfig {
+            medium_threshold: self.zone_medium_threshold.value(),
+            loud_threshold: self.zone_loud_threshold.value(),
+            channels: self.zone_channels.value().then_some(channels),
+            velocity_offsets: [-offset, 0.0, offset],
+            keyswitches: self.zone_keyswitches.value().then(|| self.zone_keyswitch_base.value() as u8),
+        })
+    }
+
+    pub(crate) fn scale(&self) -> Scale {
+        match self.scale_type.value() {
+            ScaleType::Learned => Scale::from_mask(self.learned_scale.load(Ordering::Relaxed)),
+            scale_type => Scale::new(scale_type, self.scale_root.value()),
+        }
+    }
+
+    fn engine_settings(&self) -> EngineSettings {
+        let note_channels = if self.keyboard_split.value() {
+            ChannelAssignment::Split {
+                point: self.split_point.value() as u8,
+                lower: (self.note_channel.value() - 1) as u8,
+                upper: (self.split_upper_channel.value() - 1) as u8,
+            }
+        } else if self.channel_rotation.value() {
+            ChannelAssignment::RoundRobin(
+                (self.rotation_first.value() - 1) as u8,
+                (self.rotation_last.value() - 1) as u8,
+            )
+        } else {
+            ChannelAssignment::Fixed((self.note_channel.value() - 1) as u8)
+        };
+        EngineSettings {
+            cc_lane: self.cc_lane.lane(),
+            pitch_unit: self.pitch_unit.value(),
+            out_of_range: self.out_of_range.value(),
+            speed_accuracy: self.speed_accuracy.value(),
+            confidence_smoothing: self.confidence_smoothing.value(),
+            bend_smoothing: self.bend_smoothing.value(),
+            bend_return: self.bend_return.value(),
+            bend_return_time: self.bend_return_time.value(),
+            tracking_range: self.tracking_range.value(),
+            pitch_method: self.pitch_method.value(),
+            refinement: self.refinement.value(),
+            subharmonic_suppression: self.subharmonic_suppression.value(),
+            decimation: self.decimation.value() as usize,
+            cc_stepped: self.cc_stepped.value(),
+            hysteresis: self.hysteresis.value(),
+            correction: self.correction.value(),
+            vibrato_split: self.vibrato_split.value().then(|| self.vibrato_crossover.value()),
+            scale_gate: self.scale_gate.value().then(|| self.scale_gate_distance.value()),
+            scale: self.scale(),
+            auto_key: self.auto_key(),
+            bridge_time: self.bridge_time.value(),
+            hold_time: self.hold_time.value(),
+            min_note: self.min_note.value(),
+            max_note: self.max_note.value(),
+            min_freq: self.min_freq.value(),
+            max_freq: self.max_freq.value(),
+            cents_range: self.cents_range.value(),
+            cents_lane: self.cents_lane.lane(),
+            confidence_lane: self.confidence_lane.lane(),
+            note_enabled: self.note_enabled.value(),
+            note_filter: self.note_filter.value()
+                .then(|| (self.note_filter_low.value() as u8, self.note_filter_high.value() as u8)),
+            note_channels,
+            velocity_source: self.velocity_source.value(),
+            release_velocity: self.release_velocity.value(),
+            sustain_pedal: self.sustain_pedal.value(),
+            mpe: self.mpe_config(),
+            guitar: self.guitar_config(),
+            mpe_pressure: self.mpe_pressure.value(),
+            mpe_brightness: self.mpe_brightness.value(),
+            poly_pressure: self.poly_pressure.value(),
+            dynamics: self.dynamics_config(),
+            accuracy_lane: self.accuracy_lane.lane(),
+            accuracy_range: self.accuracy_range.value(),
+            event_timing: self.event_timing.value(),
+            note_priority: self.note_priority.value(),
+            priority_window: self.priority_window.value(),
+            portamento: self.portamento.value(),
+            clock_output: self.clock_output.value(),
+            beat_notes: self.beat_notes(),
+            drums: self.drum_notes(),
+            pitch_analysis: self.pitch_analysis.value(),
+            trill_mode: self.trill_mode.value(),
+            trill_keyswitch: self.trill_keyswitch.value() as u8,
+            register_lane: self.register_lane.lane(),
+            f1_lane: self.f1_lane.lane(),
+            f2_lane: self.f2_lane.lane(),
+            denoise: self.denoise.value(),
+            gate_mode: self.gate_mode.value(),
+            gate_threshold: self.gate_threshold.value(),
+            gate_margin: self.gate_margin.value(),
+            register_keyswitches: self.register_keyswitches.value()
+                .then(|| self.register_keyswitch_base.value() as u8),
+            // Only known once processing, see `Aeolus::process()`
+            high_resolution: false,
+        }
+    }
+}
+
+
+
+impl<V: Variant> Aeolus<V> {
+    fn engine_settings(&self) -> EngineSettings {
+        let mut settings = self.params.engine_settings();
+        V::restrict(&mut settings);
+        settings
+    }
+
+    fn latency(&self) -> u32 {
+        let window = self.engine.latency();
+        if self.params.background_analysis.value() && self.worker.is_some() {
+            window + self.max_buffer_size
+        } else {
+            window
+        }
+    }
+
+    /// The delay is still applied (as zero) while switched off, so that switching it on or off
+    /// crossfades instead of clicking
+    fn delay_output(&mut self, buffer: &mut Buffer) {
+        let delay = if self.params.delay_audio.value() { self.latency() as usize } else { 0 };
+        self.output_delay.process(buffer.as_slice(), delay);
+    }
+}
+
+
+
+impl<V: Variant> Plugin for Aeolus<V> {
+    const NAME: &'static str = V::NAME;
+    const VENDOR: &'static str = "Grégoire Locqueville";
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "gregoireloc@gmail.com";
+
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    // The first audio IO layout is used as the default. The other layouts may be selected either
+    // explicitly or automatically by the host or the user depending on the plugin API/backend.
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+
+            aux_input_ports: &[],
+            aux_output_ports: &[],
+
+            // Individual ports and the layout as a whole can be named here. By default these names
+            // are generated as needed. This layout will be called 'Mono', while the one below
+            // will be called 'Stereo'.
+            names: PortNames::const_default(),
+        },
+        // Stereo input is downmixed for analysis (or one channel is picked, see
+        // `analysis_channel`), and passed through as is
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+        // Quad, 5.1 and 7.1, for multichannel stems and buses
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(4),
+            main_output_channels: NonZeroU32::new(4),
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(8),
+            main_output_channels: NonZeroU32::new(8),
+            ..AudioIOLayout::const_default()
+        },
+    ];
+
+
+    // CCs for the sustain pedal
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const MIDI_OUTPUT: MidiConfig = MidiConfig::MidiCCs;
+
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    // Only used for MIDI clock, see `RealtimeMessage`
+    type SysExMessage = RealtimeMessage;
+    // More advanced plugins can use this to run expensive background tasks. See the field's
+    // documentation for more information. `()` means that the plugin does not have any background
+    // tasks.
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    // Sessions and presets saved by older versions are migrated here, before they're restored
+    fn filter_state(state: &mut PluginState) {
+        let version = state::migrate(state);
+        if version > STATE_VERSION {
+            nih_log!("State saved by a newer Aeolus (layout version {version}), some settings may be lost");
+        }
+    }
+
+    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(self.params.clone(), self.params.editor_state.clone(), editor::Shared {
+            capture: self.capture.clone(),
+            pitch_logger: self.pitch_logger.clone(),
+            practice: self.practice.clone(),
+            statistics: self.statistics.clone(),
+            live: self.live.clone(),
+            controllers: self.controllers.clone(),
+            panic: self.panic_requested.clone(),
+            audio_load: self.audio_load.clone(),
+            worker_load: self.worker_load.clone(),
+        })
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        // Resize buffers and perform other potentially expensive initialization operations here.
+        // The `reset()` function is always called right after this function. You can remove this
+        // function if you do not need it.
+        self.max_buffer_size = buffer_config.max_buffer_size;
+        // Offline bounces have no deadline to meet, so we can afford heavier analysis
+        let quality = if buffer_config.process_mode == ProcessMode::Offline {
+            AnalysisQuality::Offline
+        } else {
+            AnalysisQuality::Realtime
+        };
+        self.engine.set_settings(self.engine_settings());
+        self.engine.initialize(buffer_config.sample_rate, quality);
+        // Hosts initialize us again after loading a session
+        self.engine.request_snapshot();
+        self.capture.lock().unwrap().set_sample_rate(buffer_config.sample_rate);
+        // The previous worker (if any) is joined when dropped here, off the audio thread.
+        // Offline bounces analyze synchronously instead: how far the worker got by the end of
+        // each block depends on thread scheduling, and so would the output.
+        self.worker = (quality == AnalysisQuality::Realtime).then(|| {
+            AnalysisWorker::new(
+                buffer_config.sample_rate,
+                4 * buffer_config.max_buffer_size as usize,
+                quality,
+                self.worker_load.clone(),
+            )
+        });
+        // The new worker counts the samples it gets from zero again. Waiting detections span
+        // one maximum block size, plus the block they come in, so even a detection on every
+        // sample would fit.
+        self.worker_position = 0;
+        self.worker_pending = VecDeque::with_capacity(2 * self.max_buffer_size as usize + 1);
+
+        let channels = audio_io_layout.main_output_channels.map_or(0, NonZeroU32::get) as usize;
+        let max_delay = (self.engine.max_latency() + self.max_buffer_size) as usize;
+        self.output_delay = OutputDelay::new(channels, max_delay, buffer_config.sample_rate);
+
+        let latency = self.latency();
+        context.set_latency_samples(latency);
+        self.reported_latency = Some(latency);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.engine.reset();
+        self.output_delay.reset();
+        self.was_playing = false;
+        self.expected_position = None;
+        self.was_bypassed = false;
+        self.analyzed_input = None;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // Nothing in here may allocate. In debug builds, nih_plug's `assert_process_allocs`
+        // feature aborts if something does, and `tests/no_alloc.rs` covers the analysis path.
+        // Everything that needs memory is allocated in `initialize()` instead.
+        let started = Instant::now();
+
+        let mut settings = self.engine_settings();
+        settings.high_resolution =
+            self.params.high_resolution.value() && context.plugin_api() == PluginApi::Clap;
+        self.engine.set_settings(settings);
+
+        // Targets and the sustain pedal are only updated once per block, which is plenty for
+        // practicing, and keeps our events in order
+        let mut sustain = None;
+        // A capture starts from the notes already held when it is switched on
+        self.captured_scale = match self.captured_scale {
+            _ if !self.params.capture_scale.value() => None,
+            Some(captured) => Some(captured),
+            None => Some(pitch_classes(self.held_notes)),
+        };
+        while let Some(event) = context.next_event() {
+            match event {
+                NoteEvent::NoteOn { note, .. } => {
+                    self.engine.set_target(Some(note));
+                    self.held_notes |= 1 << (note & 127);
+                    if let Some(captured) = &mut self.captured_scale {
+                        *captured |= 1 << (note % 12);
+                    }
+                }
+                NoteEvent::NoteOff { note, .. } => {
+                    self.held_notes &= !(1 << (note & 127));
+                    if self.engine.target() == Some(note) {
+                        self.engine.set_target(None)
+                    }
+                }
+                NoteEvent::MidiCC { cc: SUSTAIN_CC, value, .. } => sustain = Some(value >= 0.5),
+                NoteEvent::MidiCC { cc, value, .. } => self.controllers.push(cc, value),
+                _ => (),
+            }
+        }
+        // Nothing held yet isn't much of a scale, so the previous one stays until a note comes
+        if let Some(captured) = self.captured_scale.filter(|captured| *captured != 0) {
+            self.params.learned_scale.store(captured, Ordering::Relaxed);
+        }
+
+        let transport = context.transport();
+        let playing = transport.playing;
+        let tempo = transport.tempo;
+        // Captured events are stamped with the host's timeline position while it plays, so that
+        // exported files line up with the project
+        let block_position = self.free_running_position;
+        let capture_position = match transport.pos_samples() {
+            Some(position) if playing => position.max(0) as u64,
+            _ => self.free_running_position,
+        };
+        self.free_running_position += buffer.samples() as u64;
+
+        // Hardware synths may have been switched on or reconfigured since we last told them about
+        // our bend range and controllers, so we tell them again each time the transport starts
+        if playing && !self.was_playing {
+            self.engine.request_snapshot();
+        }
+        // The playhead jumping while playing means a loop wrapped around (or the user moved it).
+        // Whatever was sounding before makes no sense at the new position.
+        let position = transport.pos_samples();
+        let relocated = playing
+            && self.was_playing
+            && matches!((position, self.expected_position), (Some(position), Some(expected)) if position != expected);
+        self.expected_position = position.map(|position| position + buffer.samples() as i64);
+        self.was_playing = playing;
+
+        let latency = self.latency();
+        if self.reported_latency != Some(latency) {
+            context.set_latency_samples(latency);
+            self.reported_latency = Some(latency);
+        }
+
+        // Without pitch analysis, there's nothing for the worker to do
+        let background = self.params.background_analysis.value()
+            && self.params.pitch_analysis.value()
+            && self.worker.is_some();
+        if let Some(worker) = &self.worker {
+            let settings = self.engine.settings();
+            worker.set_range(settings.tracking_range);
+            worker.set_speed(settings.speed());
+            worker.set_method(settings.pitch_method);
+            worker.set_refinement(settings.refinement);
+            worker.set_subharmonic_suppression(settings.subharmonic_suppression);
+            worker.set_decimation(settings.decimation);
+        }
+        let block_start = self.worker_position;
+
+        let mut host_sink = HostSink::<Self, _>::new(context);
+        self.block_events.clear();
+        let mut sink = RecordingSink::new(&mut host_sink, capture_position, &mut self.block_events);
+
+        // Hosts split blocks at loop points, so the start of this one is where the loop wrapped
+        if relocated {
+            self.engine.flush(&mut sink, 0);
+            self.engine.request_snapshot();
+        }
+
+        // This works while bypassed too, since hanging notes are what it's for
+        if self.panic_requested.swap(false, Ordering::Relaxed) {
+            self.engine.panic(&mut sink, 0);
+        }
+
+        // Followed while bypassed too, so that the pedal isn't stuck afterwards
+        if let Some(held) = sustain {
+            self.engine.set_sustain(&mut sink, 0, held);
+        }
+
+        if self.params.bypass.value() {
+            if !self.was_bypassed {
+                self.engine.release(&mut sink, 0);
+            }
+            self.was_bypassed = true;
+            // Detections the worker makes in the meantime would be stale anyway
+            while self.worker.as_mut().and_then(AnalysisWorker::pop).is_some() {}
+            self.worker_pending.clear();
+            self.recorder.record(&self.block_events, tempo);
+            // Latency doesn't go away when bypassed, so neither does the delay
+            self.delay_output(buffer);
+            return ProcessStatus::Normal;
+        }
+        self.was_bypassed = false;
+
+        // This also closes the sounding note if the settings changed where it goes
+        self.engine.send_configuration(&mut sink, 0);
+        let analyzed_input = (self.params.analysis_channel.value(), background);
+        if self.analyzed_input.replace(analyzed_input).is_some_and(|previous| previous != analyzed_input) {
+            self.engine.flush(&mut sink, 0);
+            self.worker_pending.clear();
+        }
+
+        // Only the editor locks this, when starting or stopping the log, so this hardly ever fails
+        let mut pitch_logger = self.pitch_logger.try_lock().ok();
+        let mut pitch_logger = pitch_logger.as_mut().and_then(|logger| logger.as_mut());
+        let sample_rate = self.engine.sample_rate();
+
+        let analysis_channel = self.params.analysis_channel.value() as usize;
+        let mut sample_index = 0; // will be incremented at each new sample in the buffer
+        for channel_samples in buffer.iter_samples() {
+            // Analysis works on the trimmed mono downmix (or the selected channel), the audio
+            // itself only goes through the output gain
+            let input_gain = self.params.gain.smoothed.next();
+            let output_gain = self.params.output_gain.smoothed.next();
+            let mut sum = 0.0;
+            let mut channel_count = 0;
+            let mut selected = 0.0;
+            for (index, channel_sample) in channel_samples.into_iter().enumerate() {
+                sum += *channel_sample;
+                channel_count += 1;
+                if index + 1 == analysis_channel {
+                    selected = *channel_sample;
+                }
+                *channel_sample *= output_gain;
+            }
+            let sample = if analysis_channel == 0 { sum / channel_count.max(1) as f32 } else { selected };
+            let sample = input_gain * sample;
+
+            if background {
+                let sample = self.engine.condition(sample);
+                self.engine.track_rhythm(&mut sink, sample_index, sample);
+                // Skipped samples still count, or the worker's positions would lag behind ours
+                if let Some(worker) = &mut self.worker {
+                    if self.engine.is_idle() {
+                        worker.skip();
+                    } else {
+                        worker.push(sample);
+                    }
+                }
+                self.worker_position += 1;
+            } else if let Some((detection, hop_size)) = self.engine.process_sample(&mut sink, sample_index, sample) {
+                self.stats_recorder.add(detection.frequency, hop_size as f32 / sample_rate);
+                self.live.update(detection.frequency, self.engine.level());
+                if let Some(logger) = &mut pitch_logger {
+                    let position = block_position + sample_index as u64;
+                    logger.log(position, sample_rate, detection, self.engine.level());
+                }
+            }
+            sample_index += 1;
+        }
+
+        // Detections from the worker are delayed by one maximum block size, which is what we
+        // report as extra latency. Those due in a later block wait for it, so as long as the
+        // worker keeps up, their timing is exact. Late ones go at the start of the block.
+        if background {
+            while let Some(detection) = self.worker.as_mut().and_then(AnalysisWorker::pop) {
+                if self.worker_pending.len() < self.worker_pending.capacity() {
+                    self.worker_pending.push_back(detection);
+                }
+            }
+            let block_end = block_start + buffer.samples() as u64;
+            while let Some(&WorkerDetection { detection, hop_size, position }) = self.worker_pending.front() {
+                let due = position + self.max_buffer_size as u64;
+                if due >= block_end {
+                    break;
+                }
+                self.worker_pending.pop_front();
+                let timing = due.saturating_sub(block_start) as u32;
+                self.engine.handle_detection(&mut sink, timing, detection, hop_size);
+                self.stats_recorder.add(detection.frequency, hop_size as f32 / sample_rate);
+                self.live.update(detection.frequency, self.engine.level());
+                if let Some(logger) = &mut pitch_logger {
+                    logger.log(block_position + timing as u64, sample_rate, detection, self.engine.level());
+                }
+            }
+        }
+        self.recorder.record(&self.block_events, tempo);
+        self.stats_recorder.flush();
+        self.practice.update(self.engine.target(), self.engine.target_deviation());
+        self.live.set_update_rate(self.engine.update_rate());
+        self.live.set_key(self.engine.detected_key());
+        self.delay_output(buffer);
+        // Bypassed blocks aren't measured, as they say nothing about what analysis costs
+        let duration = Duration::from_secs_f32(buffer.samples() as f32 / sample_rate);
+        self.audio_load.record(started.elapsed(), duration);
+        // Hosts may stop calling us on silent input unless told otherwise, which is only safe
+        // once nothing is sounding anymore
+        if self.engine.is_idle() {
+            ProcessStatus::Normal
+        } else {
+            ProcessStatus::KeepAlive
+        }
+    }
+}
+
+impl<V: Variant> ClapPlugin for Aeolus<V> {
+    const CLAP_ID: &'static str = V::CLAP_ID;
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("to MIDI");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    // No CLAP voice-info or note-name here. nih_plug only implements voice-info along with
+    // polyphonic modulation (through `CLAP_POLY_MODULATION_CONFIG`), which we don't support and
+    // mustn't claim to, and it doesn't expose note-name at all, so both wait for nih_plug.
+
+    // TODO Don't forget to change these features
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::NoteDetector,
+        ClapFeature::Analyzer,
+        ClapFeature::Utility,
+    ];
+}
+
+#[cfg(feature = "vst3")]
+impl<V: Variant> Vst3Plugin for Aeolus<V> {
+    const VST3_CLASS_ID: [u8; 16] = V::VST3_CLASS_ID;
+
+    // TODO And also don't forget to change these categories
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(Aeolus<Full>, Aeolus<CcOnly>, Aeolus<NoteOnly>);
+#[cfg(feature = "vst3")]
+nih_export_vst3!(Aeolus<Full>, Aeolus<CcOnly>, Aeolus<NoteOnly>);
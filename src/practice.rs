@@ -0,0 +1,35 @@
+use atomic_float::AtomicF32;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Deviations (in cents) within this are shown as close, those within `IN_TUNE_CENTS` as in tune
+pub const CLOSE_CENTS: f32 = 25.0;
+
+/// How close the performer is to practice mode's target, passed from the audio thread to the
+/// editor
+pub struct PracticeDisplay {
+    /// The target note, or -1 if there is none
+    target:    AtomicI32,
+    /// In cents, NaN if the input is unvoiced
+    deviation: AtomicF32,
+}
+
+impl Default for PracticeDisplay {
+    fn default() -> Self {
+        Self { target: AtomicI32::new(-1), deviation: AtomicF32::new(f32::NAN) }
+    }
+}
+
+impl PracticeDisplay {
+    pub fn update(&self, target: Option<u8>, deviation: Option<f32>) {
+        self.target.store(target.map_or(-1, i32::from), Ordering::Relaxed);
+        self.deviation.store(deviation.unwrap_or(f32::NAN), Ordering::Relaxed);
+    }
+
+    pub fn target(&self) -> Option<u8> {
+        u8::try_from(self.target.load(Ordering::Relaxed)).ok()
+    }
+
+    pub fn deviation(&self) -> Option<f32> {
+        Some(self.deviation.load(Ordering::Relaxed)).filter(|deviation| !deviation.is_nan())
+    }
+}
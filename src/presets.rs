@@ -0,0 +1,250 @@
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::plugin::AeolusParams;
+use crate::state;
+
+const EXTENSION: &str = "json";
+/// Neither saved in presets (or A/B slots) nor changed by them: bypass, the output parameters
+/// (which are readings rather than settings), the editor's size, the slots themselves and the
+/// MIDI learn bindings
+const EXCLUDED_PARAMS: [&str; 3] = ["bypass", "pitch_output", "level_output"];
+const EXCLUDED_FIELDS: [&str; 3] = ["editor-state", "ab-slots", "midi-bindings"];
+
+/// The instrument tags presets can have, for filtering the browser
+pub const TAGS: [&str; 7] = ["Voice", "Guitar", "Bass", "Wind", "Strings", "Keys", "Drums"];
+
+/// Shipped with the plugin, in `presets/`
+const FACTORY: [&str; 4] = [
+    include_str!("../presets/voice.json"),
+    include_str!("../presets/guitar.json"),
+    include_str!("../presets/bass.json"),
+    include_str!("../presets/beatbox.json"),
+];
+
+/// A whole configuration, with what the browser shows about it. Presets are JSON files, with the
+/// state in the format nih_plug saves sessions in, so that they go through the same migrations
+/// (see `state.rs`). They don't need to have every parameter, the others are set to their
+/// defaults.
+#[derive(Serialize, Deserialize)]
+pub struct Preset {
+    pub name:  String,
+    #[serde(default)]
+    pub tags:  Vec<String>,
+    pub state: PluginState,
+}
+
+/// Where a preset is read from
+#[derive(Debug, PartialEq, Clone)]
+pub enum PresetSource {
+    /// Index in `FACTORY`
+    Factory(usize),
+    User(PathBuf),
+}
+
+/// What the browser lists. The state itself is only read again when the preset is loaded.
+#[derive(Debug, Clone)]
+pub struct PresetEntry {
+    pub name:   String,
+    pub tags:   Vec<String>,
+    /// The user folder the preset is in, relative to the presets directory. Empty for the
+    /// presets at its root, and the factory ones.
+    pub folder: String,
+    pub source: PresetSource,
+}
+
+impl PresetEntry {
+    pub fn is_factory(&self) -> bool {
+        matches!(self.source, PresetSource::Factory(_))
+    }
+
+    /// Whether the preset has `tag` (if one is given), and `search` in its name, ignoring case
+    pub fn matches(&self, search: &str, tag: Option<&str>) -> bool {
+        let tagged = match tag {
+            Some(tag) => self.tags.iter().any(|own| own == tag),
+            None      => true,
+        };
+        tagged && self.name.to_lowercase().contains(&search.to_lowercase())
+    }
+
+    /// Reads the preset, and brings its state up to date
+    pub fn load(&self) -> Result<Preset, String> {
+        let json = match &self.source {
+            PresetSource::Factory(index) => FACTORY[*index].to_owned(),
+            PresetSource::User(path) => std::fs::read_to_string(path).map_err(|error| error.to_string())?,
+        };
+        let mut preset: Preset = serde_json::from_str(&json).map_err(|error| error.to_string())?;
+        state::migrate(&mut preset.state);
+        Ok(preset)
+    }
+}
+
+/// The factory presets, then the ones in the user's presets directory, folders included
+pub struct PresetLibrary {
+    directory: PathBuf,
+    entries:   Vec<PresetEntry>,
+    /// Every folder under the directory, empty ones included, sorted
+    folders:   Vec<String>,
+}
+
+impl PresetLibrary {
+    /// Lists the presets. Files that can't be read as presets are left out.
+    pub fn scan(directory: PathBuf) -> Self {
+        let mut entries: Vec<PresetEntry> = FACTORY
+            .iter()
+            .enumerate()
+            .filter_map(|(index, json)| {
+                let preset: Preset = serde_json::from_str(json).ok()?;
+                let source = PresetSource::Factory(index);
+                Some(PresetEntry { name: preset.name, tags: preset.tags, folder: String::new(), source })
+            })
+            .collect();
+        let (mut user, mut folders) = (Vec::new(), Vec::new());
+        scan_folder(&directory, &directory, &mut user, &mut folders);
+        user.sort_by(|a: &PresetEntry, b| (&a.folder, &a.name).cmp(&(&b.folder, &b.name)));
+        folders.sort();
+        entries.extend(user);
+        Self { directory, entries, folders }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn entries(&self) -> &[PresetEntry] {
+        &self.entries
+    }
+
+    pub fn folders(&self) -> &[String] {
+        &self.folders
+    }
+
+    /// Writes a preset in `folder` (relative to the presets directory, and created if needed),
+    /// named after the preset. A preset of the same name is replaced.
+    pub fn save(&self, folder: &str, preset: &Preset) -> Result<PathBuf, String> {
+        let directory = self.directory.join(folder.trim());
+        std::fs::create_dir_all(&directory).map_err(|error| error.to_string())?;
+        let path = directory.join(format!("{}.{EXTENSION}", file_name(&preset.name)));
+        let json = serde_json::to_string_pretty(preset).map_err(|error| error.to_string())?;
+        std::fs::write(&path, json).map_err(|error| error.to_string())?;
+        Ok(path)
+    }
+}
+
+fn scan_folder(root: &Path, directory: &Path, entries: &mut Vec<PresetEntry>, folders: &mut Vec<String>) {
+    let Ok(read_dir) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let folder = directory.strip_prefix(root).map(|folder| folder.display().to_string()).unwrap_or_default();
+    for path in read_dir.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            if let Ok(subfolder) = path.strip_prefix(root) {
+                folders.push(subfolder.display().to_string());
+            }
+            scan_folder(root, &path, entries, folders);
+        } else if path.extension().is_some_and(|extension| extension == EXTENSION) {
+            let preset = std::fs::read_to_string(&path).ok().and_then(|json| serde_json::from_str::<Preset>(&json).ok());
+            if let Some(preset) = preset {
+                let (name, tags, folder) = (preset.name, preset.tags, folder.clone());
+                entries.push(PresetEntry { name, tags, folder, source: PresetSource::User(path) });
+            }
+        }
+    }
+}
+
+/// The preset's name, with whatever file systems may not like replaced
+fn file_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || " -_".contains(c) { c } else { '_' })
+        .collect()
+}
+
+/// The current configuration, as a preset
+pub fn capture(context: &dyn GuiContext, name: String, tags: Vec<String>) -> Preset {
+    Preset { name, tags, state: current_state(context) }
+}
+
+/// The current configuration, without what `EXCLUDED_PARAMS` and `EXCLUDED_FIELDS` leave out
+pub fn current_state(context: &dyn GuiContext) -> PluginState {
+    let mut state = context.get_state();
+    strip(&mut state);
+    state
+}
+
+/// Restores a configuration. The parameters it doesn't have go back to their defaults, so that
+/// nothing of the previous configuration lingers.
+pub(crate) fn restore(context: &dyn GuiContext, params: &AeolusParams, mut state: PluginState) {
+    for (id, value) in default_values(params) {
+        state.params.entry(id).or_insert(value);
+    }
+    strip(&mut state);
+    context.set_state(state);
+}
+
+fn strip(state: &mut PluginState) {
+    for id in EXCLUDED_PARAMS {
+        state.params.remove(id);
+    }
+    for key in EXCLUDED_FIELDS {
+        state.fields.remove(key);
+    }
+}
+
+/// Every parameter's default value, as nih_plug saves them
+fn default_values(params: &AeolusParams) -> BTreeMap<String, ParamValue> {
+    params
+        .param_map()
+        .into_iter()
+        .map(|(id, param, _)| {
+            // SAFETY: the pointers are to `params`' own fields, which we're borrowing
+            let value = unsafe {
+                match param {
+                    ParamPtr::FloatParam(param) => ParamValue::F32((*param).default_plain_value()),
+                    ParamPtr::IntParam(param)   => ParamValue::I32((*param).default_plain_value()),
+                    ParamPtr::BoolParam(param)  => ParamValue::Bool((*param).default_plain_value()),
+                    // Enums are saved by variant index, unless their variants have IDs, which ours don't
+                    ParamPtr::EnumParam(param)  => ParamValue::I32((*param).default_plain_value()),
+                }
+            };
+            (id, value)
+        })
+        .collect()
+}
+
+/// Two configurations to compare on the same material. The active slot's is the plugin's own,
+/// the other one is kept here (and saved with the session) until it's switched to.
+#[derive(Default, Serialize, Deserialize)]
+pub struct AbSlots {
+    /// 0 for A, 1 for B
+    pub active:   usize,
+    /// The other slot's configuration, once there is one
+    pub inactive: Option<PluginState>,
+}
+
+// The slots are locked whenever the host (or we) read the state, so they mustn't be locked then
+
+/// Switches to the other slot, which keeps the current configuration in exchange. The first
+/// time, the other slot starts as a copy of the current one.
+pub(crate) fn switch_slot(context: &dyn GuiContext, params: &AeolusParams) {
+    let current = current_state(context);
+    let other = {
+        let mut slots = params.ab_slots.lock().unwrap();
+        slots.active = 1 - slots.active;
+        slots.inactive.replace(current)
+    };
+    // It may have been saved with the session, by an older version
+    if let Some(mut other) = other {
+        state::migrate(&mut other);
+        restore(context, params, other);
+    }
+}
+
+/// Copies the current configuration over the other slot's
+pub(crate) fn copy_to_other_slot(context: &dyn GuiContext, params: &AeolusParams) {
+    let current = current_state(context);
+    params.ab_slots.lock().unwrap().inactive = Some(current);
+}
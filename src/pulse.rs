@@ -0,0 +1,82 @@
+use crate::events::{AeolusEvent, EventSink};
+use crate::tempo::Beat;
+
+/// How long each beat note lasts. Samplers usually play one-shots through anyway.
+const BEAT_NOTE_MS: f32 = 50.0;
+/// Beats are never quieter than this, so that weak ones still trigger something
+const MIN_VELOCITY: f32 = 0.1;
+/// The bar starts over when no beat was heard for this long
+const BAR_RESET_MS: f32 = 2000.0;
+
+/// Which notes the beats trigger
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BeatNotes {
+    /// 0-based
+    pub channel:       u8,
+    pub beat:          u8,
+    pub downbeat:      u8,
+    pub beats_per_bar: u8,
+}
+
+/// Sends a short note on each beat, with a velocity following the beat's strength. The tracker
+/// doesn't know where bars start, so the first beat heard (after a pause) is taken to be a
+/// downbeat, and every `beats_per_bar`-th one after it.
+#[derive(Default)]
+pub struct BeatTrigger {
+    /// Beats since the last downbeat, once the bar started
+    count:      Option<u8>,
+    /// The note sounding and its channel, if any
+    sounding:   Option<(u8, u8)>,
+    /// Samples until the sounding note is released
+    remaining:  usize,
+    /// Samples since the last beat
+    since_beat: usize,
+}
+
+impl BeatTrigger {
+    /// Closes the sounding note, and starts the bar over
+    pub fn release(&mut self, sink: &mut impl EventSink, timing: u32) {
+        if let Some((note, channel)) = self.sounding.take() {
+            sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+        }
+        *self = Self::default();
+    }
+
+    /// Advances by one sample, with the beat this sample revealed (if any)
+    pub fn process(
+        &mut self,
+        sink:        &mut impl EventSink,
+        timing:      u32,
+        beat:        Option<Beat>,
+        notes:       BeatNotes,
+        sample_rate: f32,
+    ) {
+        self.since_beat += 1;
+        if self.since_beat as f32 / sample_rate * 1000.0 > BAR_RESET_MS {
+            self.count = None;
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            if let (0, Some((note, channel))) = (self.remaining, self.sounding) {
+                sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+                self.sounding = None;
+            }
+        }
+
+        let Some(Beat { delay, strength }) = beat else {
+            return;
+        };
+        let timing = timing.saturating_sub(delay as u32);
+        if let Some((note, channel)) = self.sounding.take() {
+            sink.send(AeolusEvent::NoteOff { timing, channel, note, velocity: 0.0 });
+        }
+        let count = self.count.map_or(0, |count| (count + 1) % notes.beats_per_bar.max(1));
+        let note = if count == 0 { notes.downbeat } else { notes.beat };
+        let velocity = strength.max(MIN_VELOCITY);
+        sink.send(AeolusEvent::NoteOn { timing, channel: notes.channel, note, velocity });
+        self.count = Some(count);
+        self.sounding = Some((note, notes.channel));
+        self.remaining = (BEAT_NOTE_MS / 1000.0 * sample_rate) as usize;
+        self.since_beat = delay;
+    }
+}
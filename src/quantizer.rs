@@ -0,0 +1,30 @@
+use crate::scale::Scale;
+
+/// Rounds pitches to the nearest note of a scale, but with some hysteresis: once a note has
+/// been chosen, it is kept until the pitch moves further than halfway to another note plus the
+/// hysteresis. This avoids flickering between two neighbouring notes when the input sits right
+/// in between them (or wobbles around a quarter tone).
+#[derive(Default)]
+pub struct SemitoneQuantizer {
+    current: Option<f32>,
+}
+
+impl SemitoneQuantizer {
+    /// Forgets the current note, so the next pitch is rounded without hysteresis
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+
+    /// `pitch` is a (fractional) MIDI pitch, `hysteresis` is in semitones
+    pub fn quantize(&mut self, pitch: f32, hysteresis: f32, scale: Scale) -> f32 {
+        let nearest = scale.nearest(pitch);
+        // Going halfway plus the hysteresis towards another note puts us twice the hysteresis
+        // further from the current note than from the other one
+        let kept = self.current.filter(|&note| {
+            scale.contains(note) && (pitch - note).abs() - (pitch - nearest).abs() <= 2.0 * hysteresis
+        });
+        let note = kept.unwrap_or(nearest);
+        self.current = Some(note);
+        note
+    }
+}
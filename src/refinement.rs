@@ -0,0 +1,52 @@
+use crate::simd::squared_difference_sum;
+
+/// Refines a coarse frequency estimate, by looking for the minimum of the difference function
+/// (as in YIN) in a narrow band of lags around the estimated period, then interpolating that
+/// minimum with a parabola to get sub-sample (hence sub-cent) accuracy.
+///
+/// `window` holds the most recent audio, in chronological order. The coarse estimate is returned
+/// as is when no clear minimum can be found.
+pub fn refine_frequency(window: &[f32], sample_rate: f32, coarse: f32) -> f32 {
+    if coarse <= 0.0 {
+        return coarse;
+    }
+    let period = sample_rate / coarse;
+    let spread = (period * 0.03).max(2.0);
+    let min_lag = ((period - spread).floor() as usize).max(1);
+    let max_lag = (period + spread).ceil() as usize;
+    // All lags are compared over the same number of samples, and we want that number to be at
+    // least a period long
+    if 2 * (max_lag + 1) > window.len() {
+        return coarse;
+    }
+    let length = window.len() - max_lag - 1;
+
+    let mut best_lag = min_lag;
+    let mut best_difference = f32::MAX;
+    for lag in min_lag..=max_lag {
+        let difference = difference(window, lag, length);
+        if difference < best_difference {
+            best_lag = lag;
+            best_difference = difference;
+        }
+    }
+    // A minimum on the edge of the band means the coarse estimate was off: better to trust it
+    // than to make things up
+    if best_lag == min_lag || best_lag == max_lag {
+        return coarse;
+    }
+
+    let before = difference(window, best_lag - 1, length);
+    let after = difference(window, best_lag + 1, length);
+    let curvature = before - 2.0 * best_difference + after;
+    if curvature <= 0.0 {
+        return coarse;
+    }
+    let offset = 0.5 * (before - after) / curvature;
+    sample_rate / (best_lag as f32 + offset)
+}
+
+/// The difference function at `lag`, computed over the first `length` samples of `window`
+fn difference(window: &[f32], lag: usize, length: usize) -> f32 {
+    squared_difference_sum(&window[..length], &window[lag..lag + length])
+}
@@ -0,0 +1,98 @@
+use std::f32::consts::PI;
+
+/// Time constant of the energy followers, roughly a hop's worth
+const BALANCE_TIME_MS:   f32 = 20.0;
+/// Below this harmonic richness (see `SpectralBalance::richness()`), high notes are falsetto
+const FALSETTO_RICHNESS: f32 = 1.6;
+/// Above this, the voice is still in chest register a few semitones above the usual break
+const CHEST_RICHNESS:    f32 = 4.0;
+const HEAD_RICHNESS:     f32 = 3.0;
+
+/// Approximate vocal register. The boundaries are those of an average voice, so this is a
+/// rough guess meant for switching articulations, not a diagnosis.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VoiceRegister {
+    Chest,
+    Mixed,
+    Head,
+    Falsetto,
+}
+
+impl VoiceRegister {
+    /// From 0 (chest) to 3 (falsetto)
+    pub fn index(self) -> u8 {
+        match self {
+            VoiceRegister::Chest    => 0,
+            VoiceRegister::Mixed    => 1,
+            VoiceRegister::Head     => 2,
+            VoiceRegister::Falsetto => 3,
+        }
+    }
+
+    /// Guesses the register from the pitch (as a MIDI note) and the harmonic richness
+    pub fn estimate(pitch: f32, richness: f32) -> Self {
+        if pitch >= 62.0 && richness < FALSETTO_RICHNESS {
+            VoiceRegister::Falsetto
+        } else if pitch < 60.0 || (pitch < 65.0 && richness > CHEST_RICHNESS) {
+            VoiceRegister::Chest
+        } else if pitch > 69.0 && richness < HEAD_RICHNESS {
+            VoiceRegister::Head
+        } else {
+            VoiceRegister::Mixed
+        }
+    }
+}
+
+/// Follows the balance between the energy of the signal and that of its derivative, which
+/// grows with the amount of energy in the upper harmonics. This is a cheap stand-in for a
+/// spectral tilt measurement.
+pub struct SpectralBalance {
+    coeff:        f32,
+    sample_rate:  f32,
+    last_input:   f32,
+    energy:       f32,
+    slope_energy: f32,
+}
+
+impl SpectralBalance {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut balance = Self {
+            coeff: 0.0,
+            sample_rate,
+            last_input: 0.0,
+            energy: 0.0,
+            slope_energy: 0.0,
+        };
+        balance.set_sample_rate(sample_rate);
+        balance
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.coeff = (-1000.0 / (BALANCE_TIME_MS * sample_rate)).exp();
+    }
+
+    pub fn reset(&mut self) {
+        self.last_input = 0.0;
+        self.energy = 0.0;
+        self.slope_energy = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) {
+        let slope = input - self.last_input;
+        self.last_input = input;
+        self.energy = input * input + self.coeff * (self.energy - input * input);
+        self.slope_energy = slope * slope + self.coeff * (self.slope_energy - slope * slope);
+    }
+
+    /// How much brighter the signal is than a pure tone at `frequency`: 1 for a sine, more for
+    /// richer sounds. Differentiating a sine of frequency f scales its energy by
+    /// (2 sin(πf / sample rate))², which is what the measured ratio is compared to.
+    pub fn richness(&self, frequency: f32) -> f32 {
+        let sine_ratio = (2.0 * (PI * frequency / self.sample_rate).sin()).powi(2);
+        if self.energy <= 0.0 || sine_ratio <= 0.0 {
+            return 1.0;
+        }
+        self.slope_energy / self.energy / sine_ratio
+    }
+}
@@ -0,0 +1,241 @@
+use std::f32::consts::PI;
+
+/// The largest oversampling factor `Resampler::process()` can output
+pub const MAX_OVERSAMPLING: usize = 4;
+
+/// How the analysis input is resampled before reaching the detector
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Resampling {
+    None,
+    /// Keep one sample out of N: cheaper, and longer effective windows for very low sources
+    Decimate(usize),
+    /// N samples per input sample: finer lag resolution for very high sources. At most
+    /// `MAX_OVERSAMPLING`.
+    Oversample(usize),
+}
+
+impl Resampling {
+    /// Analysis sample rate divided by host sample rate
+    pub fn rate_factor(self) -> f32 {
+        match self {
+            Resampling::None               => 1.0,
+            Resampling::Decimate(factor)   => 1.0 / factor as f32,
+            Resampling::Oversample(factor) => factor as f32,
+        }
+    }
+
+    /// Converts a number of samples at the analysis rate into a number of samples at the host rate
+    pub fn to_host_samples(self, samples: usize) -> usize {
+        match self {
+            Resampling::None               => samples,
+            Resampling::Decimate(factor)   => samples * factor,
+            Resampling::Oversample(factor) => samples / factor,
+        }
+    }
+}
+
+/// A biquad lowpass filter, as in the RBJ cookbook, in transposed direct form II
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f32, b1: f32, b2: f32,
+    a1: f32, a2: f32,
+    z1: f32, z2: f32,
+}
+
+impl Biquad {
+    fn lowpass(sample_rate: f32, cutoff: f32, q: f32) -> Self {
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+/// Integer-ratio resampler, used after the conversion to `ANALYSIS_RATE`, with a 4th order
+/// Butterworth lowpass against aliasing (when decimating) or imaging (when oversampling)
+pub struct Resampler {
+    resampling: Resampling,
+    filters:    [Biquad; 2],
+    /// Position within the current group of `factor` input samples, when decimating
+    phase:      usize,
+}
+
+impl Resampler {
+    /// `sample_rate` is the input's sample rate
+    pub fn new(resampling: Resampling, sample_rate: f32) -> Self {
+        // The filters run at whichever of the two rates is higher, and cut a bit below the
+        // Nyquist frequency of the lower one
+        let (filter_rate, cutoff) = match resampling {
+            Resampling::None               => (sample_rate, 0.45 * sample_rate),
+            Resampling::Decimate(factor)   => (sample_rate, 0.45 * sample_rate / factor as f32),
+            Resampling::Oversample(factor) => (sample_rate * factor as f32, 0.45 * sample_rate),
+        };
+        Self {
+            resampling,
+            filters: [
+                Biquad::lowpass(filter_rate, cutoff, 0.5412),
+                Biquad::lowpass(filter_rate, cutoff, 1.3066),
+            ],
+            phase: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.phase = 0;
+    }
+
+    fn filter(&mut self, input: f32) -> f32 {
+        self.filters.iter_mut().fold(input, |sample, filter| filter.process(sample))
+    }
+
+    /// Feeds one sample at the host rate, and writes the resulting samples at the analysis rate
+    /// into `output`. Returns how many samples were written.
+    pub fn process(&mut self, input: f32, output: &mut [f32; MAX_OVERSAMPLING]) -> usize {
+        match self.resampling {
+            Resampling::None => {
+                output[0] = input;
+                1
+            }
+            Resampling::Decimate(factor) => {
+                let filtered = self.filter(input);
+                self.phase += 1;
+                if self.phase >= factor {
+                    self.phase = 0;
+                    output[0] = filtered;
+                    1
+                } else {
+                    0
+                }
+            }
+            Resampling::Oversample(factor) => {
+                // Zero stuffing, with gain compensation for the zeroes
+                let factor = factor.min(MAX_OVERSAMPLING);
+                for (index, sample) in output.iter_mut().take(factor).enumerate() {
+                    let stuffed = if index == 0 { input * factor as f32 } else { 0.0 };
+                    *sample = self.filter(stuffed);
+                }
+                factor
+            }
+        }
+    }
+}
+
+/// The rate all detectors run at, whatever the host's sample rate. This keeps detection behavior
+/// consistent across sessions, and means analyzers never need rebuilding when the host's sample
+/// rate changes.
+pub const ANALYSIS_RATE: f32 = 44100.0;
+
+/// The largest number of samples `RateConverter::process()` can output for one input sample,
+/// which limits how low the host's sample rate can be
+pub const MAX_RATE_CONVERSION: usize = 8;
+
+/// Half the number of taps of the interpolation kernel
+const SINC_HALF_TAPS: usize = 8;
+
+/// How late (in samples at `input_rate`) a `RateConverter` to `ANALYSIS_RATE` outputs its
+/// samples, which adds to the analysis latency
+pub fn rate_conversion_latency(input_rate: f32) -> usize {
+    if input_rate == ANALYSIS_RATE {
+        0
+    } else {
+        SINC_HALF_TAPS
+    }
+}
+
+/// Arbitrary-ratio sample rate converter, using a Hann-windowed sinc kernel (with its cutoff
+/// lowered when downsampling, to avoid aliasing)
+pub struct RateConverter {
+    /// Input samples per output sample. Exactly 1 means there is nothing to do.
+    step:         f64,
+    /// Relative to the input's Nyquist frequency
+    cutoff:       f64,
+    /// The last `2 * SINC_HALF_TAPS` input samples, as a ring buffer
+    history:      [f32; 2 * SINC_HALF_TAPS],
+    /// How many input samples have been received
+    input_count:  u64,
+    /// Time of the next output sample, in input samples
+    next_output:  f64,
+}
+
+impl RateConverter {
+    pub fn new(input_rate: f32, output_rate: f32) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+        Self {
+            step,
+            cutoff: 0.95 * f64::min(1.0, 1.0 / step),
+            history: [0.0; 2 * SINC_HALF_TAPS],
+            input_count: 0,
+            next_output: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; 2 * SINC_HALF_TAPS];
+        self.input_count = 0;
+        self.next_output = 0.0;
+    }
+
+    fn kernel(&self, offset: f64) -> f64 {
+        let half_taps = SINC_HALF_TAPS as f64;
+        if offset.abs() >= half_taps {
+            return 0.0;
+        }
+        let x = std::f64::consts::PI * self.cutoff * offset;
+        let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+        let window = 0.5 + 0.5 * (std::f64::consts::PI * offset / half_taps).cos();
+        self.cutoff * sinc * window
+    }
+
+    /// Feeds one input sample, and writes the resulting output samples into `output`. Returns how
+    /// many samples were written.
+    pub fn process(&mut self, input: f32, output: &mut [f32; MAX_RATE_CONVERSION]) -> usize {
+        if self.step == 1.0 {
+            output[0] = input;
+            return 1;
+        }
+
+        let newest = self.input_count;
+        self.history[(newest % self.history.len() as u64) as usize] = input;
+        self.input_count += 1;
+
+        // An output sample needs `SINC_HALF_TAPS` input samples on each side, so we produce them
+        // `SINC_HALF_TAPS` samples late
+        let mut count = 0;
+        while count < MAX_RATE_CONVERSION && self.next_output.floor() as u64 + SINC_HALF_TAPS as u64 <= newest {
+            let center = self.next_output.floor() as u64;
+            let mut sum = 0.0;
+            for index in (center + 1).saturating_sub(SINC_HALF_TAPS as u64)..=center + SINC_HALF_TAPS as u64 {
+                let sample = self.history[(index % self.history.len() as u64) as usize] as f64;
+                sum += sample * self.kernel(self.next_output - index as f64);
+            }
+            output[count] = sum as f32;
+            count += 1;
+            self.next_output += self.step;
+        }
+        count
+    }
+}
@@ -0,0 +1,232 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::*;
+#[cfg(feature = "plugin")]
+use std::sync::Arc;
+
+use crate::events::{AeolusEvent, EventSink};
+use crate::mapping::{MappingCurve, Range};
+
+/// A CC output lane, as used by the engine. The channel is 0-based.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CcLane {
+    pub enabled:    bool,
+    pub channel:    u8,
+    pub number:     u8,
+    /// In milliseconds
+    pub smoothing:  f32,
+    /// The (normalized) CC values the lane's bottom and top are sent as. The lane is inverted
+    /// when it goes downwards.
+    pub output:     Range,
+    pub curve:      MappingCurve,
+    /// In milliseconds, how long the output takes at least to sweep the whole range, 0 meaning
+    /// it may jump
+    pub rate_limit: f32,
+    /// The (normalized) CC value sent as is once the input goes unvoiced, if any. Otherwise the
+    /// lane stays at its last value.
+    pub unvoiced:   Option<f32>,
+}
+
+/// Disabled, and passing values through unchanged
+impl Default for CcLane {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: 0,
+            number: 0,
+            smoothing: 0.0,
+            output: Range::UNIT,
+            curve: MappingCurve::Linear,
+            rate_limit: 0.0,
+            unvoiced: None,
+        }
+    }
+}
+
+impl CcLane {
+    /// Sends a (normalized) value on this lane, regardless of whether it is enabled. Only the
+    /// curve and output range are applied, see `send_smoothed()` for the rest.
+    pub fn send(&self, state: &mut LaneState, sink: &mut impl EventSink, timing: u32, value: f32) {
+        self.send_cc(state, sink, timing, Range::UNIT.map(value, self.curve, self.output));
+    }
+
+    /// Sends the lane's unvoiced value, if it has one, bypassing the curve and output range
+    pub fn send_unvoiced(&self, state: &mut LaneState, sink: &mut impl EventSink, timing: u32) {
+        if let Some(value) = self.unvoiced {
+            self.send_cc(state, sink, timing, value);
+        }
+    }
+
+    /// Sends the last value again, if one was sent
+    pub fn resend(&self, state: &mut LaneState, sink: &mut impl EventSink, timing: u32) {
+        if let Some(value) = state.sent {
+            self.send_cc(state, sink, timing, value);
+        }
+    }
+
+    fn send_cc(&self, state: &mut LaneState, sink: &mut impl EventSink, timing: u32, value: f32) {
+        state.sent = Some(value);
+        sink.send(AeolusEvent::ControlChange { timing, channel: self.channel, cc: self.number, value });
+    }
+
+    /// Like `send()`, with the lane's smoothing and rate limit applied first. This is for values
+    /// updated regularly, `elapsed_ms` being the time since the previous one.
+    pub fn send_smoothed(
+        &self,
+        state:      &mut LaneState,
+        sink:       &mut impl EventSink,
+        timing:     u32,
+        value:      f32,
+        elapsed_ms: f32,
+    ) {
+        let target = Range::UNIT.clamp(value);
+        let value = match state.value {
+            None => target,
+            Some(previous) => {
+                let smoothed = if self.smoothing > 0.0 {
+                    previous + (target - previous) * (1.0 - (-elapsed_ms / self.smoothing).exp())
+                } else {
+                    target
+                };
+                if self.rate_limit > 0.0 {
+                    let max_step = elapsed_ms / self.rate_limit;
+                    previous + Range::new(-max_step, max_step).clamp(smoothed - previous)
+                } else {
+                    smoothed
+                }
+            }
+        };
+        state.value = Some(value);
+        self.send(state, sink, timing, value);
+    }
+}
+
+/// What a lane remembers from one value to the next
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LaneState {
+    /// Where the smoothing and rate limit are at
+    value: Option<f32>,
+    /// The last CC value sent, normalized
+    sent:  Option<f32>,
+}
+
+/// The states of every lane
+#[derive(Debug, Default)]
+pub struct LaneStates {
+    pub pitch:      LaneState,
+    pub cents:      LaneState,
+    pub confidence: LaneState,
+    pub accuracy:   LaneState,
+    pub register:   LaneState,
+    pub f1:         LaneState,
+    pub f2:         LaneState,
+}
+
+impl LaneStates {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Makes the next value of every lane go through as is, rather than smoothed from the last
+    pub fn reset_smoothing(&mut self) {
+        for state in [
+            &mut self.pitch,
+            &mut self.cents,
+            &mut self.confidence,
+            &mut self.accuracy,
+            &mut self.register,
+            &mut self.f1,
+            &mut self.f2,
+        ] {
+            state.value = None;
+        }
+    }
+}
+
+/// Destination of a CC output lane. Every lane targets its own MIDI channel, so that features
+/// can be split across several devices (e.g. pitch on channel 1, cents deviation on channel 2).
+#[cfg(feature = "plugin")]
+#[derive(Params)]
+pub struct CcLaneParams {
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+
+    /// The MIDI channel the lane is sent on. Like all channels in the parameters, this one is
+    /// 1-based, and converted when sending.
+    #[id = "channel"]
+    pub channel: IntParam,
+
+    /// The CC number the lane is sent on
+    #[id = "number"]
+    pub number: IntParam,
+
+    /// In milliseconds. A bend-like destination wants none, a slow macro plenty.
+    #[id = "smoothing"]
+    pub smoothing: FloatParam,
+
+    /// The CC values the bottom and top of the lane are sent as
+    #[id = "min"]
+    pub min: IntParam,
+    #[id = "max"]
+    pub max: IntParam,
+
+    #[id = "curve"]
+    pub curve: EnumParam<MappingCurve>,
+
+    /// In milliseconds, the shortest time the output may take to sweep its whole range
+    #[id = "rate_limit"]
+    pub rate_limit: FloatParam,
+
+    /// The CC value sent once the input goes unvoiced, -1 to hold the last value instead
+    #[id = "unvoiced"]
+    pub unvoiced: IntParam,
+}
+
+#[cfg(feature = "plugin")]
+impl CcLaneParams {
+    pub fn new(name: &str, enabled: bool, number: i32) -> Self {
+        Self {
+            enabled: BoolParam::new(format!("{name} enabled"), enabled),
+            channel: IntParam::new(format!("{name} channel"), 1, IntRange::Linear { min: 1, max: 16 }),
+            number: IntParam::new(format!("{name} CC"), number, IntRange::Linear { min: 0, max: 127 }),
+            smoothing: FloatParam::new(
+                format!("{name} smoothing"),
+                0.0,
+                FloatRange::Skewed { min: 0.0, max: 1000.0, factor: FloatRange::skew_factor(-2.0) },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            min: IntParam::new(format!("{name} min"), 0, IntRange::Linear { min: 0, max: 127 }),
+            max: IntParam::new(format!("{name} max"), 127, IntRange::Linear { min: 0, max: 127 }),
+            curve: EnumParam::new(format!("{name} curve"), MappingCurve::Linear),
+            rate_limit: FloatParam::new(
+                format!("{name} rate limit"),
+                0.0,
+                FloatRange::Skewed { min: 0.0, max: 5000.0, factor: FloatRange::skew_factor(-2.0) },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            unvoiced: IntParam::new(format!("{name} when unvoiced"), -1, IntRange::Linear { min: -1, max: 127 })
+                .with_value_to_string(Arc::new(|value| match value {
+                    -1 => String::from("Hold"),
+                    value => value.to_string(),
+                }))
+                .with_string_to_value(Arc::new(|string| match string.trim() {
+                    "Hold" => Some(-1),
+                    string => string.parse().ok(),
+                })),
+        }
+    }
+
+    pub fn lane(&self) -> CcLane {
+        CcLane {
+            enabled: self.enabled.value(),
+            channel: (self.channel.value() - 1) as u8,
+            number: self.number.value() as u8,
+            smoothing: self.smoothing.value(),
+            output: Range::new(self.min.value() as f32 / 127.0, self.max.value() as f32 / 127.0),
+            curve: self.curve.value(),
+            rate_limit: self.rate_limit.value(),
+            unvoiced: (self.unvoiced.value() >= 0).then_some(self.unvoiced.value() as f32 / 127.0),
+        }
+    }
+}
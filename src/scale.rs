@@ -0,0 +1,147 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+/// The scales the quantizer can snap to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum ScaleType {
+    #[cfg_attr(feature = "plugin", name = "Chromatic")]
+    Chromatic,
+    #[cfg_attr(feature = "plugin", name = "Major")]
+    Major,
+    #[cfg_attr(feature = "plugin", name = "Natural minor")]
+    NaturalMinor,
+    #[cfg_attr(feature = "plugin", name = "Harmonic minor")]
+    HarmonicMinor,
+    #[cfg_attr(feature = "plugin", name = "Dorian")]
+    Dorian,
+    #[cfg_attr(feature = "plugin", name = "Major pentatonic")]
+    MajorPentatonic,
+    #[cfg_attr(feature = "plugin", name = "Minor pentatonic")]
+    MinorPentatonic,
+    #[cfg_attr(feature = "plugin", name = "Blues")]
+    Blues,
+    /// The pitch classes captured from a chord on the MIDI input. The root doesn't apply.
+    #[cfg_attr(feature = "plugin", name = "Learned")]
+    Learned,
+}
+
+impl ScaleType {
+    /// The degrees, in semitones above the root. The learned scale is only known at runtime, see
+    /// `Scale::from_mask()`.
+    fn intervals(self) -> &'static [u8] {
+        match self {
+            ScaleType::Chromatic       => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            ScaleType::Learned         => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            ScaleType::Major           => &[0, 2, 4, 5, 7, 9, 11],
+            ScaleType::NaturalMinor    => &[0, 2, 3, 5, 7, 8, 10],
+            ScaleType::HarmonicMinor   => &[0, 2, 3, 5, 7, 8, 11],
+            ScaleType::Dorian          => &[0, 2, 3, 5, 7, 9, 10],
+            ScaleType::MajorPentatonic => &[0, 2, 4, 7, 9],
+            ScaleType::MinorPentatonic => &[0, 3, 5, 7, 10],
+            ScaleType::Blues           => &[0, 3, 5, 6, 7, 10],
+        }
+    }
+}
+
+/// Pitch classes, for the scale's root
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum RootNote {
+    #[cfg_attr(feature = "plugin", name = "C")]
+    C,
+    #[cfg_attr(feature = "plugin", name = "C#")]
+    CSharp,
+    #[cfg_attr(feature = "plugin", name = "D")]
+    D,
+    #[cfg_attr(feature = "plugin", name = "D#")]
+    DSharp,
+    #[cfg_attr(feature = "plugin", name = "E")]
+    E,
+    #[cfg_attr(feature = "plugin", name = "F")]
+    F,
+    #[cfg_attr(feature = "plugin", name = "F#")]
+    FSharp,
+    #[cfg_attr(feature = "plugin", name = "G")]
+    G,
+    #[cfg_attr(feature = "plugin", name = "G#")]
+    GSharp,
+    #[cfg_attr(feature = "plugin", name = "A")]
+    A,
+    #[cfg_attr(feature = "plugin", name = "A#")]
+    ASharp,
+    #[cfg_attr(feature = "plugin", name = "B")]
+    B,
+}
+
+impl RootNote {
+    pub const ALL: [RootNote; 12] = [
+        RootNote::C, RootNote::CSharp, RootNote::D, RootNote::DSharp, RootNote::E, RootNote::F,
+        RootNote::FSharp, RootNote::G, RootNote::GSharp, RootNote::A, RootNote::ASharp, RootNote::B,
+    ];
+
+    /// From 0 (C) to 11 (B)
+    pub fn pitch_class(self) -> u8 {
+        self as u8
+    }
+}
+
+/// A set of pitch classes, as a 12-bit mask (bit 0 being C)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Scale {
+    mask: u16,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self::new(ScaleType::Chromatic, RootNote::C)
+    }
+}
+
+impl Scale {
+    pub fn new(scale_type: ScaleType, root: RootNote) -> Self {
+        let mask = scale_type
+            .intervals()
+            .iter()
+            .fold(0, |mask, interval| mask | (1 << ((root.pitch_class() + interval) % 12)));
+        Self { mask }
+    }
+
+    /// A scale made of the pitch classes set in `mask` (bit 0 being C). An empty mask makes a
+    /// chromatic scale, rather than one nothing can snap to.
+    pub fn from_mask(mask: u16) -> Self {
+        match mask & 0xfff {
+            0 => Self::default(),
+            mask => Self { mask },
+        }
+    }
+
+    /// The pitch classes of the scale, as in `from_mask()`
+    pub fn mask(&self) -> u16 {
+        self.mask
+    }
+
+    /// Whether the (whole) MIDI note's pitch class is part of the scale
+    pub fn contains(&self, note: f32) -> bool {
+        self.mask & (1 << (note as i32).rem_euclid(12)) != 0
+    }
+
+    /// The note of the scale closest to `pitch` (a fractional MIDI pitch)
+    pub fn nearest(&self, pitch: f32) -> f32 {
+        if self.mask == 0 {
+            return pitch.round();
+        }
+        let below = (0..12).map(|offset| pitch.floor() - offset as f32).find(|note| self.contains(*note));
+        let above = (0..12).map(|offset| pitch.ceil() + offset as f32).find(|note| self.contains(*note));
+        match (below, above) {
+            (Some(below), Some(above)) if above - pitch < pitch - below => above,
+            (Some(below), _) => below,
+            (None, above) => above.unwrap_or(pitch.round()),
+        }
+    }
+}
+
+/// The pitch classes of a set of MIDI notes (one bit per note), as a scale mask
+pub fn pitch_classes(notes: u128) -> u16 {
+    (0..128).filter(|note| notes & (1 << note) != 0).fold(0, |mask, note| mask | (1 << (note % 12)))
+}
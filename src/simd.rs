@@ -0,0 +1,55 @@
+// Vectorized building blocks for the pure-Rust detectors (difference functions,
+// autocorrelation, ...).
+//
+// `std::simd` is not stable yet, so instead of using it, the loops below accumulate into
+// `LANES` independent sums. Since those sums don't depend on each other, LLVM turns them into
+// SIMD instructions (SSE/AVX, NEON, or WASM SIMD128 depending on the target), which it can't do
+// with a naive sequential sum because floating point addition isn't associative.
+
+const LANES: usize = 8;
+
+/// Adds up the lanes of an accumulator
+fn horizontal_sum(lanes: [f32; LANES]) -> f32 {
+    lanes.iter().sum()
+}
+
+/// Sum of `(a[i] - b[i])²`, over the length of the shorter slice. This is YIN's difference
+/// function, when `b` is `a` shifted by the lag.
+pub fn squared_difference_sum(a: &[f32], b: &[f32]) -> f32 {
+    let length = a.len().min(b.len());
+    let (a, b) = (&a[..length], &b[..length]);
+
+    let mut lanes = [0.0; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let (a_rest, b_rest) = (a_chunks.remainder(), b_chunks.remainder());
+    for (a_chunk, b_chunk) in a_chunks.zip(b_chunks) {
+        for ((lane, a), b) in lanes.iter_mut().zip(a_chunk).zip(b_chunk) {
+            let difference = a - b;
+            *lane += difference * difference;
+        }
+    }
+
+    let rest: f32 = a_rest.iter().zip(b_rest).map(|(a, b)| (a - b) * (a - b)).sum();
+    horizontal_sum(lanes) + rest
+}
+
+/// Sum of `a[i] * b[i]`, over the length of the shorter slice. This is the autocorrelation,
+/// when `b` is `a` shifted by the lag.
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    let length = a.len().min(b.len());
+    let (a, b) = (&a[..length], &b[..length]);
+
+    let mut lanes = [0.0; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let (a_rest, b_rest) = (a_chunks.remainder(), b_chunks.remainder());
+    for (a_chunk, b_chunk) in a_chunks.zip(b_chunks) {
+        for ((lane, a), b) in lanes.iter_mut().zip(a_chunk).zip(b_chunk) {
+            *lane += a * b;
+        }
+    }
+
+    let rest: f32 = a_rest.iter().zip(b_rest).map(|(a, b)| a * b).sum();
+    horizontal_sum(lanes) + rest
+}
@@ -0,0 +1,101 @@
+use crate::events::AeolusEvent;
+
+/// Ticks per quarter note in the files we write
+const TICKS_PER_QUARTER: u16 = 960;
+
+/// MIDI status bytes, to be combined with a channel
+const NOTE_OFF:         u8 = 0x80;
+const NOTE_ON:          u8 = 0x90;
+const POLY_PRESSURE:    u8 = 0xA0;
+const CONTROL_CHANGE:   u8 = 0xB0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
+const PITCH_BEND:       u8 = 0xE0;
+
+/// An event together with its absolute position, in samples
+#[derive(Debug, Clone, Copy)]
+pub struct TimedEvent {
+    pub position: u64,
+    pub event:    AeolusEvent,
+}
+
+fn to_7_bits(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+/// The raw MIDI bytes for an event and how many of them there are (channel pressure only has
+/// two), if it has a MIDI 1.0 equivalent
+fn midi_bytes(event: &AeolusEvent) -> Option<([u8; 3], usize)> {
+    let bytes = match *event {
+        AeolusEvent::NoteOn { channel, note, velocity, .. } =>
+            [NOTE_ON | channel, note, to_7_bits(velocity).max(1)], // velocity 0 would be a NoteOff
+        AeolusEvent::NoteOff { channel, note, velocity, .. } =>
+            [NOTE_OFF | channel, note, to_7_bits(velocity)],
+        AeolusEvent::ControlChange { channel, cc, value, .. } =>
+            [CONTROL_CHANGE | channel, cc, to_7_bits(value)],
+        AeolusEvent::PitchBend { channel, value, .. } => {
+            let value = (value.clamp(0.0, 1.0) * 16383.0).round() as u16;
+            [PITCH_BEND | channel, (value & 0x7F) as u8, (value >> 7) as u8]
+        }
+        AeolusEvent::ChannelPressure { channel, pressure, .. } =>
+            return Some(([CHANNEL_PRESSURE | channel, to_7_bits(pressure), 0], 2)),
+        AeolusEvent::PolyPressure { channel, note, pressure, .. } =>
+            [POLY_PRESSURE | channel, note, to_7_bits(pressure)],
+        // Realtime messages aren't allowed in MIDI files
+        AeolusEvent::PolyTuning { .. } | AeolusEvent::Realtime { .. } => return None,
+    };
+    Some((bytes, 3))
+}
+
+fn write_variable_length(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = [0u8; 5];
+    let mut count = 0;
+    let mut remaining = value;
+    loop {
+        groups[count] = (remaining & 0x7F) as u8;
+        count += 1;
+        remaining >>= 7;
+        if remaining == 0 {
+            break;
+        }
+    }
+    for index in (0..count).rev() {
+        let continuation = if index > 0 { 0x80 } else { 0 };
+        bytes.push(groups[index] | continuation);
+    }
+}
+
+/// Builds a format 0 Standard MIDI File out of `events`, which must be sorted by position.
+/// Positions are converted to ticks at a constant `tempo` (in BPM), so that the file lines up
+/// with the host's timeline when starting at its beginning.
+pub fn write_smf(events: &[TimedEvent], sample_rate: f32, tempo: f64) -> Vec<u8> {
+    let ticks_per_sample = tempo / 60.0 * TICKS_PER_QUARTER as f64 / sample_rate as f64;
+    let microseconds_per_quarter = (60_000_000.0 / tempo).round() as u32;
+
+    let mut track = Vec::new();
+    // Tempo meta event
+    track.extend_from_slice(&[0x00, 0xFF, 0x51, 0x03]);
+    track.extend_from_slice(&microseconds_per_quarter.to_be_bytes()[1..]);
+    let mut last_tick = 0;
+    for TimedEvent { position, event } in events {
+        let Some((bytes, length)) = midi_bytes(event) else {
+            continue;
+        };
+        let tick = (*position as f64 * ticks_per_sample).round() as u32;
+        write_variable_length(&mut track, tick.saturating_sub(last_tick));
+        last_tick = tick.max(last_tick);
+        track.extend_from_slice(&bytes[..length]);
+    }
+    // End of track
+    track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut bytes = Vec::with_capacity(22 + track.len());
+    bytes.extend_from_slice(b"MThd");
+    bytes.extend_from_slice(&6u32.to_be_bytes());
+    bytes.extend_from_slice(&0u16.to_be_bytes()); // format 0
+    bytes.extend_from_slice(&1u16.to_be_bytes()); // one track
+    bytes.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    bytes.extend_from_slice(b"MTrk");
+    bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&track);
+    bytes
+}
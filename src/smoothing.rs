@@ -0,0 +1,47 @@
+/// One-pole smoothing of the pitch (in semitones), updated once per hop
+#[derive(Default)]
+pub struct PitchSmoother {
+    value:     Option<f32>,
+    /// How much of the current value comes from the pitches seen so far, between 0 and 1. The
+    /// rest stands for the history there isn't, and gets no say.
+    certainty: f32,
+}
+
+impl PitchSmoother {
+    /// Forgets the current pitch, so that the next one is passed through as is
+    pub fn reset(&mut self) {
+        self.value = None;
+        self.certainty = 0.0;
+    }
+
+    /// `elapsed_ms` is the time since the previous call, `time_ms` the smoothing time constant
+    pub fn process(&mut self, pitch: f32, elapsed_ms: f32, time_ms: f32) -> f32 {
+        if self.value.is_none() {
+            self.value = Some(pitch);
+            self.certainty = 1.0;
+        }
+        self.blend(pitch, elapsed_ms, time_ms)
+    }
+
+    /// Like `process()`, weighting the new pitch by `weight` (between 0 and 1), as if only that
+    /// fraction of the time had elapsed: at 1 it's the same, towards 0 the output hardly moves.
+    /// Unlike there, the first pitch only counts as much as its weight, so that a shaky estimate
+    /// at an onset is soon outweighed by the better ones that follow.
+    pub fn process_weighted(&mut self, pitch: f32, weight: f32, elapsed_ms: f32, time_ms: f32) -> f32 {
+        self.blend(pitch, elapsed_ms * weight.clamp(0.0, 1.0), time_ms)
+    }
+
+    fn blend(&mut self, pitch: f32, elapsed_ms: f32, time_ms: f32) -> f32 {
+        let coeff = if time_ms > 0.0 { (-elapsed_ms / time_ms).exp() } else { 0.0 };
+        let kept = coeff * self.certainty;
+        let certainty = kept + (1.0 - coeff);
+        // Nothing to go on yet
+        if certainty <= 0.0 {
+            return pitch;
+        }
+        let value = (kept * self.value.unwrap_or(pitch) + (1.0 - coeff) * pitch) / certainty;
+        self.value = Some(value);
+        self.certainty = certainty;
+        value
+    }
+}
@@ -0,0 +1,69 @@
+use std::f32::consts::PI;
+
+use crate::fft::Fft;
+
+/// The magnitude spectrum of a Hann-windowed analysis window, zero-padded for finer bins. This
+/// is what the spectral detectors start from. The buffers are allocated in `new()`.
+pub struct MagnitudeSpectrum {
+    fft:        Fft,
+    hann:       Vec<f32>,
+    real:       Vec<f32>,
+    imaginary:  Vec<f32>,
+    magnitudes: Vec<f32>,
+}
+
+impl MagnitudeSpectrum {
+    /// For windows of `window_size` samples, padded to `fft_size`, which must be a power of two
+    /// at least as long
+    pub fn new(window_size: usize, fft_size: usize) -> Self {
+        Self {
+            fft: Fft::new(fft_size),
+            hann: (0..window_size)
+                .map(|index| 0.5 - 0.5 * (2.0 * PI * index as f32 / window_size as f32).cos())
+                .collect(),
+            real: vec![0.0; fft_size],
+            imaginary: vec![0.0; fft_size],
+            magnitudes: vec![0.0; fft_size / 2 + 1],
+        }
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.fft.size()
+    }
+
+    /// In Hz, the spacing of the bins
+    pub fn bin_width(&self, sample_rate: f32) -> f32 {
+        sample_rate / self.fft.size() as f32
+    }
+
+    /// The magnitudes of `window`'s bins, from 0 Hz to Nyquist
+    pub fn analyze(&mut self, window: &[f32]) -> &[f32] {
+        self.real.fill(0.0);
+        self.imaginary.fill(0.0);
+        for ((real, sample), hann) in self.real.iter_mut().zip(window).zip(&self.hann) {
+            *real = sample * hann;
+        }
+        self.fft.forward(&mut self.real, &mut self.imaginary);
+        for ((magnitude, real), imaginary) in self.magnitudes.iter_mut().zip(&self.real).zip(&self.imaginary) {
+            *magnitude = (real * real + imaginary * imaginary).sqrt();
+        }
+        &self.magnitudes
+    }
+}
+
+/// Whether `window`'s power is below `silence_db`, which the detectors take as unvoiced
+pub fn is_silent(window: &[f32], silence_db: f32) -> bool {
+    let power = window.iter().map(|sample| sample * sample).sum::<f32>() / window.len().max(1) as f32;
+    10.0 * power.max(1e-20).log10() < silence_db
+}
+
+/// Where the top of the parabola through three neighbouring values is, relative to the middle
+/// one, for sub-bin (or sub-sample) accuracy. Between -0.5 and 0.5.
+pub fn peak_offset(before: f32, at: f32, after: f32) -> f32 {
+    let curvature = before - 2.0 * at + after;
+    if curvature < 0.0 {
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::utils::{cents_deviation, freq_to_midi, note_name};
+
+/// Deviations (in cents) within this count as in tune
+pub const IN_TUNE_CENTS: f32 = 10.0;
+
+/// Width of the deviation histograms' bins, in cents
+pub const BIN_CENTS: f32 = 5.0;
+/// Enough bins to cover -50 to 50 cents
+pub const BINS: usize = (100.0 / BIN_CENTS) as usize;
+/// Hops the audio thread keeps aside when the statistics are locked by the editor
+const PENDING_CAPACITY: usize = 1024;
+
+/// How long (in seconds) each note was sung or played, and how far from the tempered pitch
+pub struct IntonationStats {
+    /// Time spent in each deviation bin, for each note
+    histograms: [[f32; BINS]; 128],
+}
+
+impl Default for IntonationStats {
+    fn default() -> Self {
+        Self { histograms: [[0.0; BINS]; 128] }
+    }
+}
+
+fn bin_center(bin: usize) -> f32 {
+    -50.0 + (bin as f32 + 0.5) * BIN_CENTS
+}
+
+/// Summary for one note
+pub struct NoteStats {
+    pub note:    u8,
+    pub seconds: f32,
+    /// In cents
+    pub mean_deviation: f32,
+    /// Between 0 and 1
+    pub in_tune: f32,
+}
+
+impl IntonationStats {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Accounts for `seconds` of the input sitting at `pitch` (a fractional MIDI pitch)
+    pub fn add(&mut self, pitch: f32, seconds: f32) {
+        let note = pitch.round();
+        if !(0.0..=127.0).contains(&note) {
+            return;
+        }
+        let bin = ((cents_deviation(pitch) + 50.0) / BIN_CENTS) as usize;
+        self.histograms[note as usize][bin.min(BINS - 1)] += seconds;
+    }
+
+    pub fn histogram(&self, note: u8) -> &[f32; BINS] {
+        &self.histograms[note as usize & 127]
+    }
+
+    /// Voiced time, and the part of it that was in tune
+    fn totals(histogram: &[f32; BINS]) -> (f32, f32) {
+        let total = histogram.iter().sum();
+        let in_tune = histogram
+            .iter()
+            .enumerate()
+            .filter(|(bin, _)| bin_center(*bin).abs() <= IN_TUNE_CENTS)
+            .map(|(_, seconds)| seconds)
+            .sum();
+        (total, in_tune)
+    }
+
+    /// The proportion of voiced time spent in tune, if anything was voiced
+    pub fn in_tune_ratio(&self) -> Option<f32> {
+        let (total, in_tune) = self.histograms.iter().map(Self::totals).fold((0.0, 0.0), |sum, note| {
+            (sum.0 + note.0, sum.1 + note.1)
+        });
+        if total > 0.0 {
+            Some(in_tune / total)
+        } else {
+            None
+        }
+    }
+
+    /// Every note that was played, from lowest to highest
+    pub fn notes(&self) -> Vec<NoteStats> {
+        (0..128u8)
+            .filter_map(|note| {
+                let histogram = self.histogram(note);
+                let (seconds, in_tune) = Self::totals(histogram);
+                (seconds > 0.0).then(|| {
+                    let weighted: f32 = histogram
+                        .iter()
+                        .enumerate()
+                        .map(|(bin, seconds)| bin_center(bin) * seconds)
+                        .sum();
+                    NoteStats { note, seconds, mean_deviation: weighted / seconds, in_tune: in_tune / seconds }
+                })
+            })
+            .collect()
+    }
+
+    /// One row per note, with the time spent in each bin
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("note,name,seconds,mean_deviation,in_tune");
+        for bin in 0..BINS {
+            write!(csv, ",bin_{:+}", bin_center(bin)).unwrap();
+        }
+        csv.push('\n');
+        for stats in self.notes() {
+            write!(
+                csv,
+                "{},{},{:.3},{:.2},{:.4}",
+                stats.note,
+                note_name(stats.note),
+                stats.seconds,
+                stats.mean_deviation,
+                stats.in_tune,
+            )
+            .unwrap();
+            for seconds in self.histogram(stats.note) {
+                write!(csv, ",{seconds:.3}").unwrap();
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// The audio thread's side of the statistics, see `capture::Recorder`
+pub struct StatsRecorder {
+    stats:   Arc<Mutex<IntonationStats>>,
+    /// Pitches and durations of the hops that haven't been added to the statistics yet
+    pending: Vec<(f32, f32)>,
+}
+
+impl StatsRecorder {
+    pub fn new(stats: Arc<Mutex<IntonationStats>>) -> Self {
+        Self { stats, pending: Vec::with_capacity(PENDING_CAPACITY) }
+    }
+
+    /// Doesn't block or allocate. `frequency` is in Hz, and ignored if unvoiced.
+    pub fn add(&mut self, frequency: f32, seconds: f32) {
+        if frequency > 0.0 && self.pending.len() < self.pending.capacity() {
+            self.pending.push((freq_to_midi(frequency), seconds));
+        }
+    }
+
+    /// Adds the pending hops to the statistics, unless they're locked by the editor
+    pub fn flush(&mut self) {
+        if let Ok(mut stats) = self.stats.try_lock() {
+            for (pitch, seconds) in self.pending.drain(..) {
+                stats.add(pitch, seconds);
+            }
+        }
+    }
+}
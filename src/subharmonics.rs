@@ -0,0 +1,39 @@
+use std::f32::consts::PI;
+
+/// An estimate whose frequency has less than this share of the energy at twice it is taken for
+/// an undertone (about -10 dB)
+const UNDERTONE_RATIO: f32 = 0.1;
+
+/// Corrects undertone errors, where the detector lands an octave below the actual pitch. That
+/// happens on distorted or chorused guitar, whose intermodulation and beating make the waveform
+/// look like it repeats every other period. The waveform may, but there's next to no energy at
+/// the frequency that was found: compared with the energy at twice that, this tells undertones
+/// from notes that are truly that low, whose fundamental is rarely that weak.
+///
+/// `window` holds the most recent audio, in chronological order. The estimate is returned as is
+/// unless it's doubled.
+pub fn suppress_subharmonic(window: &[f32], sample_rate: f32, frequency: f32) -> f32 {
+    if frequency <= 0.0 || 2.0 * frequency >= sample_rate / 2.0 {
+        return frequency;
+    }
+    if band_energy(window, sample_rate, frequency) < UNDERTONE_RATIO * band_energy(window, sample_rate, 2.0 * frequency) {
+        2.0 * frequency
+    } else {
+        frequency
+    }
+}
+
+/// The energy of the Hann-windowed `window` at `frequency`, with the Goertzel algorithm: this
+/// only needs two frequencies, rather than a whole spectrum
+fn band_energy(window: &[f32], sample_rate: f32, frequency: f32) -> f32 {
+    let length = window.len() as f32;
+    let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate).cos();
+    let (mut previous, mut before) = (0.0, 0.0);
+    for (index, sample) in window.iter().enumerate() {
+        let hann = 0.5 - 0.5 * (2.0 * PI * index as f32 / length).cos();
+        let current = sample * hann + coefficient * previous - before;
+        before = previous;
+        previous = current;
+    }
+    previous * previous + before * before - coefficient * previous * before
+}
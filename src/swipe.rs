@@ -0,0 +1,123 @@
+use crate::spectrum::{is_silent, peak_offset, MagnitudeSpectrum};
+
+/// The harmonics the kernels look at: the first and the primes. Leaving the others out is what
+/// keeps the subharmonics of the pitch from scoring as well as it does (that's the "prime" in
+/// SWIPE').
+const PRIMES:            [f32; 7] = [1.0, 2.0, 3.0, 5.0, 7.0, 11.0, 13.0];
+/// Where around each harmonic the kernel samples the spectrum, in multiples of the candidate,
+/// and the kernel's value there: a cosine lobe on the harmonic, and negative lobes (halved, as
+/// in the paper) halfway to the next ones
+const KERNEL:            [(f32, f32); 9] = [
+    (-0.5, -0.5),
+    (-0.375, -0.354),
+    (-0.25, 0.0),
+    (-0.125, 0.707),
+    (0.0, 1.0),
+    (0.125, 0.707),
+    (0.25, 0.0),
+    (0.375, -0.354),
+    (0.5, -0.5),
+];
+/// Candidates per octave, the peak being interpolated between them
+const RESOLUTION:        f32 = 24.0;
+/// The padded FFT is this many times longer than the window, for finer bins
+const PADDING:           usize = 2;
+/// Candidates scoring less than this are taken for noise. Noise scores a little above zero,
+/// as the magnitudes are all positive.
+const VOICING_THRESHOLD: f32 = 0.3;
+
+/// SWIPE', which scores each candidate pitch by how well the spectrum matches a sawtooth-like
+/// kernel with peaks at its harmonics, the candidate matching best being the pitch. Comparing
+/// whole spectra makes it accurate on voice, whose harmonics move around with the formants.
+///
+/// This is the single-window version: the paper uses windows sized after each candidate, here
+/// they all share the analyzer's window, and the kernels only sample the spectrum around the
+/// harmonics rather than covering all of it.
+///
+/// See A. Camacho and J. G. Harris, "A sawtooth waveform inspired pitch estimator for speech
+/// and music", 2008.
+pub struct SwipeDetector {
+    spectrum:    MagnitudeSpectrum,
+    /// In Hz, spaced by `RESOLUTION`
+    candidates:  Vec<f32>,
+    scores:      Vec<f32>,
+    sample_rate: f32,
+    silence_db:  f32,
+    confidence:  f32,
+}
+
+impl SwipeDetector {
+    /// Candidates are looked for between `min_frequency` and `max_frequency`, as far as the
+    /// window allows. This allocates.
+    pub fn new(buffer_size: usize, sample_rate: f32, silence_db: f32, min_frequency: f32, max_frequency: f32) -> Self {
+        // At least two periods in the window, like YIN, and the second harmonic below Nyquist
+        let lowest = min_frequency.max(2.0 * sample_rate / buffer_size as f32);
+        let highest = max_frequency.min(sample_rate / 4.0);
+        let count = if highest > lowest { (RESOLUTION * (highest / lowest).log2()) as usize + 1 } else { 0 };
+        Self {
+            spectrum: MagnitudeSpectrum::new(buffer_size, (buffer_size * PADDING).next_power_of_two()),
+            candidates: (0..count).map(|index| lowest * (index as f32 / RESOLUTION).exp2()).collect(),
+            scores: vec![0.0; count],
+            sample_rate,
+            silence_db,
+            confidence: 0.0,
+        }
+    }
+
+    /// Between 0 and 1, for the last detection
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Returns the frequency (in Hz) detected in `window`, zero meaning unvoiced or silent
+    pub fn detect(&mut self, window: &[f32]) -> f32 {
+        self.confidence = 0.0;
+        if self.candidates.is_empty() || is_silent(window, self.silence_db) {
+            return 0.0;
+        }
+        let bin_width = self.spectrum.bin_width(self.sample_rate);
+        let magnitudes = self.spectrum.analyze(window);
+        // The square root of the magnitude, which is closer to loudness, as in the paper.
+        // Linearly interpolated between bins, `None` past Nyquist.
+        let loudness = |frequency: f32| {
+            let position = frequency / bin_width;
+            let index = position as usize;
+            let fraction = position - index as f32;
+            let next = magnitudes.get(index + 1)?;
+            Some((magnitudes[index] * (1.0 - fraction) + next * fraction).sqrt())
+        };
+
+        for (score, candidate) in self.scores.iter_mut().zip(&self.candidates) {
+            // The normalized correlation of the kernel with the spectrum, so between -1 and 1
+            let (mut correlation, mut kernel_energy, mut spectrum_energy) = (0.0, 0.0, 0.0);
+            for harmonic in PRIMES {
+                // Higher harmonics count less, as they do in a sawtooth
+                let weight = harmonic.sqrt().recip();
+                for (offset, lobe) in KERNEL {
+                    let Some(value) = loudness((harmonic + offset) * candidate) else {
+                        continue;
+                    };
+                    let kernel = lobe * weight;
+                    correlation += kernel * value;
+                    kernel_energy += kernel * kernel;
+                    spectrum_energy += value * value;
+                }
+            }
+            let norm = (kernel_energy * spectrum_energy).sqrt();
+            *score = if norm > 0.0 { correlation / norm } else { 0.0 };
+        }
+
+        let count = self.scores.len();
+        let peak = (0..count).max_by(|a, b| self.scores[*a].total_cmp(&self.scores[*b])).unwrap_or(0);
+        self.confidence = self.scores[peak].clamp(0.0, 1.0);
+        if self.confidence < VOICING_THRESHOLD {
+            return 0.0;
+        }
+        let offset = if peak > 0 && peak + 1 < count {
+            peak_offset(self.scores[peak - 1], self.scores[peak], self.scores[peak + 1])
+        } else {
+            0.0
+        };
+        self.candidates[peak] * (offset / RESOLUTION).exp2()
+    }
+}
@@ -0,0 +1,172 @@
+#[cfg(feature = "aubio")]
+use aubio::{OnsetMode, Tempo};
+
+/// Analysis sizes for tempo tracking, at the host's rate. Beats don't need the pitch detectors'
+/// precision, so these are fixed. The onset detector we fall back on has no window of its own.
+#[cfg(feature = "aubio")]
+const TEMPO_BUFFER_SIZE: usize = 1024;
+const TEMPO_HOP_SIZE:    usize = 512;
+/// Tempos outside of this range (in BPM) are folded back into it, by octaves
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 180.0;
+
+/// A detected beat
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Beat {
+    /// Offset (in samples) of the beat from the end of the hop it was detected in
+    pub delay:    usize,
+    /// Between 0 and 1
+    pub strength: f32,
+}
+
+/// Not `Send`, for the same reasons as (and with the same remedy as) `AubioPitch`, see
+/// `analysis.rs`
+#[cfg(feature = "aubio")]
+struct AubioTempo(Tempo);
+
+// SAFETY: see `AubioPitch`. `AubioTempo` is only ever owned by one `TempoTracker`.
+#[cfg(feature = "aubio")]
+unsafe impl Send for AubioTempo {}
+
+/// What finds the beats: aubio's tempo tracker when we have aubio, and a plain energy flux onset
+/// detector otherwise
+enum BeatDetector {
+    #[cfg(feature = "aubio")]
+    Aubio(AubioTempo),
+    Onsets(OnsetDetector),
+}
+
+/// Follows the tempo of the input, one sample at a time
+pub struct TempoTracker {
+    detector:    BeatDetector,
+    pending:     Vec<f32>,
+    sample_rate: f32,
+    /// In BPM, once known
+    bpm:         Option<f32>,
+}
+
+impl TempoTracker {
+    /// This allocates
+    pub fn new(sample_rate: f32) -> Self {
+        #[cfg(feature = "aubio")]
+        let detector = Tempo::new(OnsetMode::SpecFlux, TEMPO_BUFFER_SIZE, TEMPO_HOP_SIZE, sample_rate as u32)
+            .map(|tempo| BeatDetector::Aubio(AubioTempo(tempo)))
+            .unwrap_or_else(|_| BeatDetector::Onsets(OnsetDetector::default()));
+        #[cfg(not(feature = "aubio"))]
+        let detector = BeatDetector::Onsets(OnsetDetector::default());
+        Self {
+            detector,
+            pending: Vec::with_capacity(TEMPO_HOP_SIZE),
+            sample_rate,
+            bpm: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.bpm = None;
+        match &mut self.detector {
+            // aubio's tracker can't be reset, but it adapts within a few beats anyway
+            #[cfg(feature = "aubio")]
+            BeatDetector::Aubio(_) => (),
+            BeatDetector::Onsets(onsets) => *onsets = OnsetDetector::default(),
+        }
+    }
+
+    /// In BPM, once enough beats were heard
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    /// Returns the beat found in the hop this sample completed, if any
+    pub fn push(&mut self, sample: f32) -> Option<Beat> {
+        self.pending.push(sample);
+        if self.pending.len() < TEMPO_HOP_SIZE {
+            return None;
+        }
+        let hop_ms = TEMPO_HOP_SIZE as f32 / self.sample_rate * 1000.0;
+        let beat = match &mut self.detector {
+            #[cfg(feature = "aubio")]
+            BeatDetector::Aubio(tempo) => {
+                let beat = tempo.0.do_result(self.pending.as_slice()).unwrap_or(0.0);
+                let bpm = tempo.0.get_bpm();
+                if bpm > 0.0 {
+                    self.bpm = Some(fold_bpm(bpm));
+                }
+                // aubio gives the beat's position within the hop, as a fraction of it
+                (beat > 0.0).then(|| Beat {
+                    delay: ((1.0 - beat.fract()) * TEMPO_HOP_SIZE as f32) as usize,
+                    strength: tempo.0.get_confidence().clamp(0.0, 1.0),
+                })
+            }
+            BeatDetector::Onsets(onsets) => {
+                let beat = onsets.process(&self.pending, hop_ms);
+                if let Some(interval_ms) = onsets.interval_ms() {
+                    self.bpm = Some(fold_bpm(60_000.0 / interval_ms));
+                }
+                beat.map(|strength| Beat { delay: 0, strength })
+            }
+        };
+        self.pending.clear();
+        beat
+    }
+}
+
+fn fold_bpm(mut bpm: f32) -> f32 {
+    while bpm < MIN_BPM {
+        bpm *= 2.0;
+    }
+    while bpm > MAX_BPM {
+        bpm /= 2.0;
+    }
+    bpm
+}
+
+/// Time constant of the flux average the onset threshold is based on
+const FLUX_AVERAGE_MS: f32 = 1000.0;
+const ONSET_THRESHOLD: f32 = 1.5;
+/// Onsets closer than this to the previous one are ignored
+const MIN_INTERVAL_MS: f32 = 100.0;
+/// Time constant of the inter-onset interval average, in intervals
+const INTERVAL_SMOOTHING: f32 = 0.2;
+
+/// Finds onsets as jumps in the hops' energy, and averages the time between them
+#[derive(Default)]
+struct OnsetDetector {
+    energy:       f32,
+    flux_average: f32,
+    since_onset:  Option<f32>,
+    /// In milliseconds
+    interval:     Option<f32>,
+}
+
+impl OnsetDetector {
+    /// Returns the onset's strength, if the hop contains one
+    fn process(&mut self, hop: &[f32], hop_ms: f32) -> Option<f32> {
+        let energy = hop.iter().map(|sample| sample * sample).sum::<f32>() / hop.len() as f32;
+        let flux = (energy - self.energy).max(0.0);
+        self.energy = energy;
+        let threshold = ONSET_THRESHOLD * self.flux_average;
+        self.flux_average += (1.0 - (-hop_ms / FLUX_AVERAGE_MS).exp()) * (flux - self.flux_average);
+        if let Some(since_onset) = &mut self.since_onset {
+            *since_onset += hop_ms;
+        }
+
+        let ready = self.since_onset.is_none_or(|since_onset| since_onset >= MIN_INTERVAL_MS);
+        if flux <= threshold || threshold <= 0.0 || !ready {
+            return None;
+        }
+        if let Some(since_onset) = self.since_onset {
+            self.interval = Some(match self.interval {
+                Some(interval) => interval + INTERVAL_SMOOTHING * (since_onset - interval),
+                None => since_onset,
+            });
+        }
+        self.since_onset = Some(0.0);
+        Some((flux / threshold - 1.0).clamp(0.0, 1.0))
+    }
+
+    fn interval_ms(&self) -> Option<f32> {
+        self.interval
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+/// How many note changes the detector looks back on. A trill needs this many quick changes
+/// between the same two notes to be recognized.
+const TRILL_CHANGES: usize = 4;
+/// Trills don't go further apart than this, in semitones
+const MAX_TRILL_INTERVAL: u8 = 3;
+/// Notes lasting longer than this (in milliseconds) aren't part of a trill
+pub const MAX_TRILL_NOTE_MS: f32 = 150.0;
+
+/// What to do with the notes of a trill
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum TrillMode {
+    /// Every note of the trill is sent, as detected
+    #[cfg_attr(feature = "plugin", name = "Faithful")]
+    Faithful,
+    /// Only the principal note is sent, with the keyswitch held for the trill's duration
+    #[cfg_attr(feature = "plugin", name = "Collapse")]
+    Collapse,
+}
+
+/// Recognizes rapid alternations between two notes
+#[derive(Default)]
+pub struct TrillDetector {
+    current:   Option<u8>,
+    /// How long (in samples) the current note has lasted
+    held:      usize,
+    /// The latest note changes: the note that was left and how long it had lasted, the most
+    /// recent one last
+    changes:   [Option<(u8, usize)>; TRILL_CHANGES],
+    /// The note the trill is on, if one is going on
+    principal: Option<u8>,
+}
+
+impl TrillDetector {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Lets time pass, see `Hold::advance()`
+    pub fn advance(&mut self, samples: usize) {
+        self.held = self.held.saturating_add(samples);
+    }
+
+    /// Feeds the note currently played. Returns the principal note while a trill is going on.
+    /// Trill notes lasting longer than `max_note_samples` end the trill.
+    pub fn process(&mut self, note: u8, max_note_samples: usize) -> Option<u8> {
+        if self.current != Some(note) {
+            if let Some(current) = self.current {
+                self.changes.rotate_left(1);
+                self.changes[TRILL_CHANGES - 1] = Some((current, self.held));
+            }
+            self.current = Some(note);
+            self.held = 0;
+        }
+
+        if self.held > max_note_samples {
+            self.principal = None;
+        } else if self.principal.is_none() {
+            self.principal = self.trill_principal(note, max_note_samples);
+        }
+        self.principal
+    }
+
+    /// The note the trill started from, if the latest changes (and `note`) go back and forth
+    /// between two close notes quickly enough. The first of them may have lasted longer, since
+    /// trills usually start from a held note.
+    fn trill_principal(&self, note: u8, max_note_samples: usize) -> Option<u8> {
+        let mut notes = [0; TRILL_CHANGES];
+        for (index, change) in self.changes.iter().enumerate() {
+            let (changed_note, held) = (*change)?;
+            if index > 0 && held > max_note_samples {
+                return None;
+            }
+            notes[index] = changed_note;
+        }
+        let (first, second) = (notes[0], notes[1]);
+        if first == second || first.abs_diff(second) > MAX_TRILL_INTERVAL {
+            return None;
+        }
+        // Alternating, with `note` as the next step
+        let alternating = notes.iter().chain(std::iter::once(&note))
+            .enumerate()
+            .all(|(index, note)| *note == if index % 2 == 0 { first } else { second });
+        alternating.then_some(first)
+    }
+}
@@ -1,30 +1,33 @@
+use crate::mapping::{MappingCurve, Range};
+
 pub fn freq_to_midi(frequency: f32) -> f32 {
     69.0 + 12.0 * (frequency/440.0).log2()
 }
 
-pub fn scale(
-    input:            f32,
-    min_input_value:  f32,
-    max_input_value:  f32,
-    min_output_value: f32,
-    max_output_value: f32,
-) -> f32 {
-    let normalized = (input - min_input_value) / (max_input_value - min_input_value);
-    normalized * max_output_value + (1.0 - normalized) * min_output_value
+pub fn midi_to_freq(midi_pitch: f32) -> f32 {
+    440.0 * ((midi_pitch - 69.0) / 12.0).exp2()
+}
+
+/// Deviation (in cents, between -50 and 50) of a MIDI pitch from the nearest semitone
+pub fn cents_deviation(midi_pitch: f32) -> f32 {
+    100.0 * (midi_pitch - midi_pitch.round())
+}
+
+/// Maps a value between `-range` and `range` to a normalized CC value, in such a way that
+/// zero is sent as 64 (the usual center of bipolar controllers). The output is clamped.
+pub fn bipolar_cc_value(input: f32, range: f32) -> f32 {
+    Range::new(-range, range).map(input, MappingCurve::Linear, Range::new(1.0 / 127.0, 1.0))
 }
 
-pub fn limit_f32(
-    input:     f32,
-    min_value: f32,
-    max_value: f32,
-) -> f32 {
-    f32::min(max_value, f32::max(min_value, input))
+/// Maps a linear level to [0, 1] on a decibel scale, `floor_db` (and below) being mapped to 0
+/// and 0 dB (and above) to 1
+pub fn level_to_unit(level: f32, floor_db: f32) -> f32 {
+    let level_db = 20.0 * level.max(1e-9).log10();
+    Range::new(floor_db, 0.0).clamped_position(level_db)
 }
 
-pub fn limit_u8(
-    input:     u8,
-    min_value: u8,
-    max_value: u8,
-) -> u8 {
-    u8::min(max_value, u8::max(min_value, input))
+/// Names like "A4", with middle C (60) being C4
+pub fn note_name(note: u8) -> String {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    format!("{}{}", NAMES[note as usize % 12], note as i32 / 12 - 1)
 }
@@ -0,0 +1,58 @@
+use crate::engine::EngineSettings;
+use crate::velocity::VelocitySource;
+
+/// What sets the plugins exported by this crate apart. They all share the same engine and
+/// parameters, but the single-purpose ones force some outputs off.
+pub trait Variant: 'static + Send + Sync {
+    const NAME:          &'static str;
+    const CLAP_ID:       &'static str;
+    const VST3_CLASS_ID: [u8; 16];
+
+    /// Overrides whatever settings don't apply to this variant
+    fn restrict(settings: &mut EngineSettings);
+}
+
+/// Everything at once
+pub struct Full;
+
+/// Only the CC lanes, no notes
+pub struct CcOnly;
+
+/// Only notes (with their MPE bends and pressure), no CC lanes
+pub struct NoteOnly;
+
+impl Variant for Full {
+    const NAME:          &'static str = "Aeolus";
+    const CLAP_ID:       &'static str = "io.github.glocq";
+    const VST3_CLASS_ID: [u8; 16]     = *b"AeolusAAAAAAAAAA";
+
+    fn restrict(_settings: &mut EngineSettings) {}
+}
+
+impl Variant for CcOnly {
+    const NAME:          &'static str = "Aeolus CC";
+    const CLAP_ID:       &'static str = "io.github.glocq.cc";
+    const VST3_CLASS_ID: [u8; 16]     = *b"AeolusCCAAAAAAAA";
+
+    fn restrict(settings: &mut EngineSettings) {
+        settings.note_enabled = false;
+        settings.mpe = None;
+        settings.guitar = None;
+        settings.poly_pressure = false;
+        settings.velocity_source = VelocitySource::Fixed;
+    }
+}
+
+impl Variant for NoteOnly {
+    const NAME:          &'static str = "Aeolus Note";
+    const CLAP_ID:       &'static str = "io.github.glocq.note";
+    const VST3_CLASS_ID: [u8; 16]     = *b"AeolusNoteAAAAAA";
+
+    fn restrict(settings: &mut EngineSettings) {
+        settings.note_enabled = true;
+        settings.cc_lane.enabled = false;
+        settings.cents_lane.enabled = false;
+        settings.confidence_lane.enabled = false;
+        settings.accuracy_lane.enabled = false;
+    }
+}
@@ -0,0 +1,142 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+use std::f32::consts::PI;
+
+use crate::fft::Fft;
+use crate::mapping::Range;
+use crate::utils::level_to_unit;
+
+/// About 21 ms at 48 kHz, like the denoiser's frames
+const FRAME_MS:          f32 = 21.0;
+/// How long the strongest recent flux is remembered for, roughly what it takes a note to be
+/// detected and confirmed after its onset
+const FLUX_HOLD_MS:      f32 = 150.0;
+/// Velocities follow the level (or flux) in dB, from this up to full scale
+const VELOCITY_FLOOR_DB: f32 = -40.0;
+/// The velocity of every note in fixed mode
+const FIXED_VELOCITY:    f32 = 0.5;
+/// Release velocities reach full scale for decays this fast (in dB per second), which is
+/// about as fast as the input envelope can fall
+const MAX_DECAY_RATE:    f32 = 80.0;
+
+/// What the velocity of notes follows
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum VelocitySource {
+    /// The same velocity for every note
+    #[cfg_attr(feature = "plugin", name = "Fixed")]
+    Fixed,
+    /// The input level when the note starts, which suits plucked and struck sources
+    #[cfg_attr(feature = "plugin", name = "Level")]
+    Level,
+    /// How fast the spectrum changed around the onset. Bowed and blown instruments take a while
+    /// to reach their level, which makes the level at the start of their notes meaningless, but
+    /// their spectrum changes abruptly as the new note speaks.
+    #[cfg_attr(feature = "plugin", name = "Spectral flux")]
+    SpectralFlux,
+}
+
+impl VelocitySource {
+    /// The velocity for a note starting now, given the current input level and flux tracker
+    pub fn velocity(self, level: f32, flux: &SpectralFlux) -> f32 {
+        let velocity = match self {
+            VelocitySource::Fixed        => return FIXED_VELOCITY,
+            VelocitySource::Level        => level_to_unit(level, VELOCITY_FLOOR_DB),
+            VelocitySource::SpectralFlux => level_to_unit(flux.peak(), VELOCITY_FLOOR_DB),
+        };
+        // Velocity 0 would be a NoteOff for many receivers
+        velocity.max(1.0 / 127.0)
+    }
+}
+
+/// The NoteOff velocity for a note ending while the level falls at `decay_rate` (in dB per
+/// second): abrupt stops make fast releases, fading notes slow ones
+pub fn release_velocity(decay_rate: f32) -> f32 {
+    Range::new(0.0, MAX_DECAY_RATE).clamped_position(decay_rate)
+}
+
+/// Follows the spectral flux of the input: the energy of the increases in magnitude from one
+/// frame to the next, summed over all bins. It is scaled so that a sine appearing out of
+/// silence has a flux equal to its amplitude, which lets it share the level's dB scale.
+pub struct SpectralFlux {
+    fft:         Fft,
+    window:      Vec<f32>,
+    /// The last frame's worth of input, as a ring buffer
+    input:       Vec<f32>,
+    input_index: usize,
+    /// Samples since the last frame was analyzed
+    hop_count:   usize,
+    real:        Vec<f32>,
+    imaginary:   Vec<f32>,
+    /// The previous frame's magnitudes
+    magnitudes:  Vec<f32>,
+    /// The strongest recent flux, decaying over `FLUX_HOLD_MS`
+    peak:        f32,
+    /// Applied to the peak once per frame
+    decay:       f32,
+}
+
+impl SpectralFlux {
+    /// This allocates
+    pub fn new(sample_rate: f32) -> Self {
+        let size = ((FRAME_MS / 1000.0 * sample_rate) as usize).next_power_of_two().max(64);
+        let hop_ms = (size / 2) as f32 / sample_rate * 1000.0;
+        Self {
+            fft: Fft::new(size),
+            window: (0..size)
+                .map(|index| 0.5 - 0.5 * (2.0 * PI * index as f32 / size as f32).cos())
+                .collect(),
+            input: vec![0.0; size],
+            input_index: 0,
+            hop_count: 0,
+            real: vec![0.0; size],
+            imaginary: vec![0.0; size],
+            magnitudes: vec![0.0; size / 2 + 1],
+            peak: 0.0,
+            decay: (-hop_ms / FLUX_HOLD_MS).exp(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.input_index = 0;
+        self.hop_count = 0;
+        self.magnitudes.fill(0.0);
+        self.peak = 0.0;
+    }
+
+    /// The strongest flux over about the last `FLUX_HOLD_MS`
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        let size = self.fft.size();
+        self.input[self.input_index] = sample;
+        self.input_index = (self.input_index + 1) % size;
+        self.hop_count += 1;
+        if self.hop_count == size / 2 {
+            self.hop_count = 0;
+            self.process_frame();
+        }
+    }
+
+    fn process_frame(&mut self) {
+        let size = self.fft.size();
+        for (index, (real, imaginary)) in self.real.iter_mut().zip(self.imaginary.iter_mut()).enumerate() {
+            *real = self.window[index] * self.input[(self.input_index + index) % size];
+            *imaginary = 0.0;
+        }
+        self.fft.forward(&mut self.real, &mut self.imaginary);
+
+        let mut energy = 0.0;
+        for (bin, previous) in self.magnitudes.iter_mut().enumerate() {
+            let magnitude = self.real[bin].hypot(self.imaginary[bin]);
+            energy += (magnitude - *previous).max(0.0).powi(2);
+            *previous = magnitude;
+        }
+        // A Hann-windowed sine of amplitude A peaks at A * size / 4
+        let flux = energy.sqrt() * 4.0 / size as f32;
+        self.peak = flux.max(self.peak * self.decay);
+    }
+}
@@ -0,0 +1,34 @@
+use crate::smoothing::PitchSmoother;
+
+/// Pitch steps larger than this (in semitones) from one hop to the next are note changes, which
+/// the drift follows at once rather than lagging behind
+const MAX_VIBRATO_STEP: f32 = 1.0;
+
+/// A crossover on the pitch track, splitting it into the slow drift (intonation, slides and note
+/// changes) and the fast modulation on top of it (vibrato). The drift is a two-pole low-pass of
+/// the pitch, the modulation is what's left.
+#[derive(Default)]
+pub struct VibratoSplitter {
+    poles:    [PitchSmoother; 2],
+    previous: Option<f32>,
+}
+
+impl VibratoSplitter {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Feeds one hop's pitch (in semitones), and returns the drift and the modulation, which add
+    /// up to it. `crossover` is in Hz.
+    pub fn process(&mut self, pitch: f32, hop_ms: f32, crossover: f32) -> (f32, f32) {
+        if self.previous.is_some_and(|previous| (pitch - previous).abs() > MAX_VIBRATO_STEP) {
+            self.poles.iter_mut().for_each(PitchSmoother::reset);
+        }
+        self.previous = Some(pitch);
+        // The time constant of a one-pole low-pass with that cutoff
+        let time_ms = 1000.0 / (std::f32::consts::TAU * crossover.max(f32::EPSILON));
+        let [first, second] = &mut self.poles;
+        let drift = second.process(first.process(pitch, hop_ms, time_ms), hop_ms, time_ms);
+        (drift, pitch - drift)
+    }
+}
@@ -0,0 +1,192 @@
+use nih_plug::prelude::*;
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::analysis::{AnalysisQuality, AnalysisSpeed, AnalyzerBank, Detection, PitchMethod, TrackingRange};
+use crate::load::LoadMeter;
+
+/// How long the worker sleeps when there is nothing to analyze
+const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// A detection made by the worker
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerDetection {
+    pub detection: Detection,
+    /// In samples at the host rate
+    pub hop_size:  usize,
+    /// How many samples the audio thread had gone through when the hop completed, counting the
+    /// ones it skipped
+    pub position:  u64,
+}
+
+/// What the audio thread sends to the worker
+#[derive(Debug, Clone, Copy)]
+enum WorkerInput {
+    Sample(f32),
+    /// This many samples weren't sent, either because the engine was idle or because the ring
+    /// buffer was full. The worker still counts them, so that positions match on both sides.
+    Gap(u64),
+}
+
+/// Settings the audio thread passes on to the worker
+struct SharedSettings {
+    running:    AtomicBool,
+    /// As given by `TrackingRange::to_index()`
+    range:      AtomicUsize,
+    /// As given by `AnalysisSpeed::to_index()`
+    speed:      AtomicUsize,
+    /// As given by `PitchMethod::to_index()`
+    method:     AtomicUsize,
+    refinement: AtomicBool,
+    /// Whether subharmonic suppression is on
+    undertones: AtomicBool,
+    decimation: AtomicUsize,
+}
+
+/// Runs pitch analysis on a thread of its own, so that expensive detectors don't cause audio
+/// dropouts. The audio thread pushes samples into a lock-free ring buffer, and collects
+/// detections from another one. Nothing here blocks or allocates, except for `new()` and `drop()`.
+pub struct AnalysisWorker {
+    samples:    Producer<WorkerInput>,
+    detections: Consumer<WorkerDetection>,
+    settings:   Arc<SharedSettings>,
+    thread:     Option<JoinHandle<()>>,
+    /// The samples skipped since the last gap was sent, sent along with the next sample
+    gap:        u64,
+}
+
+impl AnalysisWorker {
+    /// Spawns the worker thread. `capacity` is how many samples can be waiting for analysis
+    /// before new ones get dropped. The time spent analyzing is recorded in `load`.
+    pub fn new(sample_rate: f32, capacity: usize, quality: AnalysisQuality, load: Arc<LoadMeter>) -> Self {
+        let (samples, mut worker_samples) = RingBuffer::new(capacity);
+        let (mut worker_detections, detections) = RingBuffer::new(capacity);
+        let settings = Arc::new(SharedSettings {
+            running: AtomicBool::new(true),
+            range: AtomicUsize::new(TrackingRange::Normal.to_index()),
+            speed: AtomicUsize::new(AnalysisSpeed::Balanced.to_index()),
+            method: AtomicUsize::new(PitchMethod::Yin.to_index()),
+            refinement: AtomicBool::new(false),
+            undertones: AtomicBool::new(false),
+            decimation: AtomicUsize::new(1),
+        });
+
+        let worker_settings = settings.clone();
+        let thread = thread::spawn(move || {
+            // The analyzers are created on the worker thread, and never leave it
+            let mut analyzers = AnalyzerBank::new(quality);
+            analyzers.set_host_rate(sample_rate);
+            let mut position = 0;
+            let (mut range, mut speed) = (TrackingRange::Normal, AnalysisSpeed::Balanced);
+            // Like the engine's, the settings only change between hops
+            let mut hop_ended = true;
+
+            while worker_settings.running.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                let mut analyzed = 0;
+                while let Ok(input) = worker_samples.pop() {
+                    let sample = match input {
+                        WorkerInput::Sample(sample) => sample,
+                        WorkerInput::Gap(skipped) => {
+                            position += skipped;
+                            continue;
+                        }
+                    };
+                    analyzed += 1;
+                    position += 1;
+                    if hop_ended {
+                        range = TrackingRange::from_index(worker_settings.range.load(Ordering::Relaxed));
+                        speed = AnalysisSpeed::from_index(worker_settings.speed.load(Ordering::Relaxed));
+                        analyzers.set_method(PitchMethod::from_index(worker_settings.method.load(Ordering::Relaxed)));
+                        analyzers.set_refinement(worker_settings.refinement.load(Ordering::Relaxed));
+                        analyzers.set_subharmonic_suppression(worker_settings.undertones.load(Ordering::Relaxed));
+                        analyzers.set_decimation(worker_settings.decimation.load(Ordering::Relaxed));
+                    }
+                    let pushed = analyzers.push(sample, range, speed);
+                    hop_ended = pushed.is_some();
+                    if let Some((detection, hop_size)) = pushed {
+                        // If the audio thread doesn't keep up collecting detections, there's
+                        // nothing better to do than to drop them
+                        let _ = worker_detections.push(WorkerDetection { detection, hop_size, position });
+                    }
+                }
+                if analyzed == 0 {
+                    thread::park_timeout(IDLE_SLEEP);
+                } else {
+                    load.record(started.elapsed(), Duration::from_secs_f32(analyzed as f32 / sample_rate));
+                }
+            }
+        });
+
+        Self {
+            samples,
+            detections,
+            settings,
+            thread: Some(thread),
+            gap: 0,
+        }
+    }
+
+    pub fn set_range(&self, range: TrackingRange) {
+        self.settings.range.store(range.to_index(), Ordering::Relaxed);
+    }
+
+    pub fn set_speed(&self, speed: AnalysisSpeed) {
+        self.settings.speed.store(speed.to_index(), Ordering::Relaxed);
+    }
+
+    pub fn set_method(&self, method: PitchMethod) {
+        self.settings.method.store(method.to_index(), Ordering::Relaxed);
+    }
+
+    pub fn set_refinement(&self, refine: bool) {
+        self.settings.refinement.store(refine, Ordering::Relaxed);
+    }
+
+    pub fn set_subharmonic_suppression(&self, suppress: bool) {
+        self.settings.undertones.store(suppress, Ordering::Relaxed);
+    }
+
+    pub fn set_decimation(&self, decimation: usize) {
+        self.settings.decimation.store(decimation, Ordering::Relaxed);
+    }
+
+    /// Returns false if the worker is lagging behind so much that the sample had to be dropped.
+    /// Dropped samples still count as a gap.
+    pub fn push(&mut self, sample: f32) -> bool {
+        if self.gap > 0 {
+            if self.samples.push(WorkerInput::Gap(self.gap)).is_err() {
+                self.gap += 1;
+                return false;
+            }
+            self.gap = 0;
+        }
+        let pushed = self.samples.push(WorkerInput::Sample(sample)).is_ok();
+        if !pushed {
+            self.gap += 1;
+        }
+        pushed
+    }
+
+    /// Tells the worker that a sample went by without being analyzed
+    pub fn skip(&mut self) {
+        self.gap += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<WorkerDetection> {
+        self.detections.pop().ok()
+    }
+}
+
+impl Drop for AnalysisWorker {
+    fn drop(&mut self) {
+        self.settings.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::simd::squared_difference_sum;
+
+/// The cumulative mean normalized difference has to go below this for a lag to be considered a
+/// period. This is aubio's default.
+const THRESHOLD: f32 = 0.15;
+
+/// A pure-Rust implementation of the YIN pitch detector, for builds without aubio. It has the
+/// same interface as aubio's detectors, being fed one hop at a time.
+///
+/// See A. de Cheveigné and H. Kawahara, "YIN, a fundamental frequency estimator for speech and
+/// music", 2002.
+pub struct YinDetector {
+    /// The most recent audio, in chronological order
+    window:      Vec<f32>,
+    /// Cumulative mean normalized difference, for lags up to half the window
+    difference:  Vec<f32>,
+    sample_rate: f32,
+    silence_db:  f32,
+    confidence:  f32,
+}
+
+impl YinDetector {
+    /// This allocates
+    pub fn new(buffer_size: usize, sample_rate: f32, silence_db: f32) -> Self {
+        Self {
+            window: vec![0.0; buffer_size],
+            difference: vec![0.0; buffer_size / 2],
+            sample_rate,
+            silence_db,
+            confidence: 0.0,
+        }
+    }
+
+    /// Between 0 and 1, for the last detection
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// Adds a hop's worth of audio to the window, and returns the detected frequency (in Hz),
+    /// zero meaning unvoiced or silent
+    pub fn detect(&mut self, hop: &[f32]) -> f32 {
+        let hop = &hop[hop.len().saturating_sub(self.window.len())..];
+        let kept = self.window.len() - hop.len();
+        self.window.copy_within(hop.len().., 0);
+        self.window[kept..].copy_from_slice(hop);
+
+        let power = self.window.iter().map(|sample| sample * sample).sum::<f32>() / self.window.len() as f32;
+        if 10.0 * power.max(1e-20).log10() < self.silence_db {
+            self.confidence = 0.0;
+            return 0.0;
+        }
+
+        let half = self.difference.len();
+        if half < 3 {
+            return 0.0;
+        }
+        self.difference[0] = 1.0;
+        let mut running_sum = 0.0;
+        for lag in 1..half {
+            let difference = squared_difference_sum(&self.window[..half], &self.window[lag..lag + half]);
+            running_sum += difference;
+            self.difference[lag] = if running_sum > 0.0 { difference * lag as f32 / running_sum } else { 1.0 };
+        }
+
+        // The first dip below the threshold, or failing that the lowest point overall
+        let mut best_lag = None;
+        let mut lag = 2;
+        while lag < half - 1 {
+            if self.difference[lag] < THRESHOLD {
+                while lag + 1 < half - 1 && self.difference[lag + 1] < self.difference[lag] {
+                    lag += 1;
+                }
+                best_lag = Some(lag);
+                break;
+            }
+            lag += 1;
+        }
+        let best_lag = best_lag.unwrap_or_else(|| {
+            (2..half - 1)
+                .min_by(|a, b| self.difference[*a].total_cmp(&self.difference[*b]))
+                .unwrap_or(2)
+        });
+        self.confidence = (1.0 - self.difference[best_lag]).clamp(0.0, 1.0);
+
+        // Sub-sample accuracy, by fitting a parabola through the minimum and its neighbours
+        let (before, at, after) = (
+            self.difference[best_lag - 1],
+            self.difference[best_lag],
+            self.difference[best_lag + 1],
+        );
+        let curvature = before - 2.0 * at + after;
+        let offset = if curvature > 0.0 { 0.5 * (before - after) / curvature } else { 0.0 };
+        self.sample_rate / (best_lag as f32 + offset.clamp(-0.5, 0.5))
+    }
+}
@@ -0,0 +1,64 @@
+// Known-pitch checks for the alternatives to YIN. The golden tests only run the default
+// detector, so these make sure each of the others finds the pitch of a plain harmonic tone
+// across the range, before any smoothing or quantization could hide an error.
+
+use std::f32::consts::PI;
+
+use aeolus::cepstrum::CepstrumDetector;
+use aeolus::hps::HpsDetector;
+use aeolus::swipe::SwipeDetector;
+
+/// The analysis rate, which the detectors run at in the engine
+const SAMPLE_RATE:     f32 = 44100.0;
+/// The window of the Normal tracking range at the accurate end of the Fast/Accurate macro,
+/// which holds two periods of a guitar's low E
+const BUFFER_SIZE:     usize = 2048;
+const SILENCE_DB:      f32 = -50.0;
+/// From the low E of a guitar up to the top of a soprano's range
+const FREQUENCIES:     [f32; 5] = [82.41, 110.0, 220.0, 440.0, 880.0];
+const TOLERANCE_CENTS: f32 = 10.0;
+/// In Hz, how far up the harmonics of the test tone go, as they would for most instruments
+const BANDWIDTH:       f32 = 5000.0;
+
+/// A window of a sawtooth-like tone, its harmonics falling off as 1/n
+fn harmonic_tone(frequency: f32) -> Vec<f32> {
+    let harmonics = (BANDWIDTH / frequency) as usize;
+    (0..BUFFER_SIZE)
+        .map(|index| {
+            let time = index as f32 / SAMPLE_RATE;
+            let tone: f32 = (1..=harmonics)
+                .map(|harmonic| (2.0 * PI * frequency * harmonic as f32 * time).sin() / harmonic as f32)
+                .sum();
+            0.2 * tone
+        })
+        .collect()
+}
+
+fn check_detector(name: &str, mut detect: impl FnMut(&[f32]) -> f32) {
+    for frequency in FREQUENCIES {
+        let detected = detect(&harmonic_tone(frequency));
+        let cents = 1200.0 * (detected / frequency).log2();
+        assert!(
+            detected > 0.0 && cents.abs() < TOLERANCE_CENTS,
+            "{name} found {detected} Hz in a {frequency} Hz tone"
+        );
+    }
+}
+
+#[test]
+fn hps_finds_known_pitches() {
+    let mut detector = HpsDetector::new(BUFFER_SIZE, SAMPLE_RATE, SILENCE_DB);
+    check_detector("HPS", |window| detector.detect(window));
+}
+
+#[test]
+fn swipe_finds_known_pitches() {
+    let mut detector = SwipeDetector::new(BUFFER_SIZE, SAMPLE_RATE, SILENCE_DB, 0.0, f32::INFINITY);
+    check_detector("SWIPE'", |window| detector.detect(window));
+}
+
+#[test]
+fn cepstrum_finds_known_pitches() {
+    let mut detector = CepstrumDetector::new(BUFFER_SIZE, SAMPLE_RATE, SILENCE_DB, 0.0, f32::INFINITY);
+    check_detector("Cepstrum", |window| detector.detect(window));
+}
@@ -0,0 +1,152 @@
+// Runs the audio fixtures in `tests/fixtures` through the engine, and compares the notes it sends
+// to the ones listed in `tests/golden`, so that changes to the detectors or to the smoothing can't
+// silently make tracking worse. The fixtures are synthesized: sines, a sweep from A3 to A5, a
+// Karplus-Strong plucked string standing in for a guitar, and an additive "a" vowel with vibrato
+// standing in for a voice.
+//
+// Timings in the golden files are in seconds, relative to the audio, and compared with the
+// tolerance given on their first line once the reported latency is compensated, like a host
+// would. After an intended change in tracking, run the tests with `AEOLUS_BLESS=1` to rewrite
+// the golden files from the engine's output, and review the diff.
+//
+// The files in the tree come from the pure-Rust YIN, so this only runs without aubio's
+// (`--no-default-features`): aubio's estimates differ slightly, and would need goldens of their own.
+#![cfg(not(feature = "aubio"))]
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use aeolus::analysis::AnalysisQuality;
+use aeolus::engine::Engine;
+use aeolus::events::AeolusEvent;
+
+/// Hosts usually send blocks of about this size
+const BLOCK_SIZE: usize = 512;
+
+#[derive(Debug, PartialEq)]
+struct NoteChange {
+    on:   bool,
+    note: u8,
+    /// In seconds
+    time: f32,
+}
+
+fn read_fixture(name: &str) -> (Vec<f32>, f32) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(format!("{name}.wav"));
+    let mut reader = hound::WavReader::open(&path).expect("missing fixture");
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 1, "fixtures are mono");
+    let scale = (1u32 << (spec.bits_per_sample - 1)) as f32;
+    let samples = reader
+        .samples::<i32>()
+        .map(|sample| sample.unwrap() as f32 / scale)
+        .collect();
+    (samples, spec.sample_rate as f32)
+}
+
+fn run_engine(samples: &[f32], sample_rate: f32) -> Vec<NoteChange> {
+    let mut engine = Engine::new(sample_rate, AnalysisQuality::Realtime);
+    let latency = engine.latency() as f32;
+    let mut changes = Vec::new();
+    for (block_index, block) in samples.chunks(BLOCK_SIZE).enumerate() {
+        let offset = (block_index * BLOCK_SIZE) as f32;
+        for event in engine.analyze_block(block) {
+            let (on, note) = match event {
+                AeolusEvent::NoteOn { note, .. }  => (true, note),
+                AeolusEvent::NoteOff { note, .. } => (false, note),
+                _ => continue,
+            };
+            let time = ((offset + event.timing() as f32 - latency) / sample_rate).max(0.0);
+            changes.push(NoteChange { on, note, time });
+        }
+    }
+    changes
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.txt"))
+}
+
+/// Returns the tolerance (in seconds) and the expected note changes
+fn read_golden(name: &str) -> (f32, Vec<NoteChange>) {
+    let contents = std::fs::read_to_string(golden_path(name)).expect("missing golden file");
+    let mut lines = contents.lines();
+    let tolerance_ms: f32 = lines
+        .next()
+        .and_then(|line| line.strip_prefix("tolerance_ms "))
+        .and_then(|value| value.parse().ok())
+        .expect("golden files start with a tolerance");
+    let changes = lines
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [kind, note, time] => NoteChange {
+                    on: kind == "on",
+                    note: note.parse().unwrap(),
+                    time: time.parse().unwrap(),
+                },
+                _ => panic!("malformed golden line: {line}"),
+            }
+        })
+        .collect();
+    (tolerance_ms / 1000.0, changes)
+}
+
+fn write_golden(name: &str, tolerance: f32, changes: &[NoteChange]) {
+    let mut contents = format!("tolerance_ms {}\n", (tolerance * 1000.0).round());
+    for change in changes {
+        let kind = if change.on { "on" } else { "off" };
+        writeln!(contents, "{kind} {} {:.3}", change.note, change.time).unwrap();
+    }
+    std::fs::write(golden_path(name), contents).unwrap();
+}
+
+fn check_fixture(name: &str) {
+    let (samples, sample_rate) = read_fixture(name);
+    let actual = run_engine(&samples, sample_rate);
+    let (tolerance, expected) = read_golden(name);
+
+    if std::env::var_os("AEOLUS_BLESS").is_some() {
+        write_golden(name, tolerance, &actual);
+        return;
+    }
+
+    let describe = |changes: &[NoteChange]| {
+        changes.iter().map(|change| format!("{change:?}")).collect::<Vec<_>>().join("\n")
+    };
+    assert_eq!(
+        actual.len(),
+        expected.len(),
+        "{name}: expected\n{}\ngot\n{}",
+        describe(&expected),
+        describe(&actual),
+    );
+    for (actual, expected) in actual.iter().zip(&expected) {
+        assert_eq!((actual.on, actual.note), (expected.on, expected.note), "{name} at {}s", expected.time);
+        assert!(
+            (actual.time - expected.time).abs() <= tolerance,
+            "{name}: {actual:?} is too far from {expected:?}",
+        );
+    }
+}
+
+#[test]
+fn sine_steps() {
+    check_fixture("sine_steps");
+}
+
+#[test]
+fn sine_sweep() {
+    check_fixture("sine_sweep");
+}
+
+#[test]
+fn plucked_string() {
+    check_fixture("pluck");
+}
+
+#[test]
+fn sung_vowel() {
+    check_fixture("voice");
+}
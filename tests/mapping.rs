@@ -0,0 +1,104 @@
+// Property tests for the ranges and curves every output stage maps its values through. Whatever
+// comes in, be it inverted or degenerate ranges, infinities or NaN, what goes out must stay
+// within the target range, since it ends up in CCs and bends.
+
+use proptest::prelude::*;
+
+use aeolus::mapping::{MappingCurve, Range};
+use aeolus::utils::bipolar_cc_value;
+
+const CURVES: [MappingCurve; 4] = [
+    MappingCurve::Linear,
+    MappingCurve::Exponential,
+    MappingCurve::Logarithmic,
+    MappingCurve::SCurve,
+];
+
+/// Any value at all, including the awkward ones
+fn any_value() -> impl Strategy<Value = f32> {
+    prop_oneof![
+        8 => -1e6f32..1e6,
+        1 => Just(f32::NAN),
+        1 => Just(f32::INFINITY),
+        1 => Just(f32::NEG_INFINITY),
+    ]
+}
+
+/// Finite ranges, either way round, possibly zero-width
+fn any_range() -> impl Strategy<Value = Range> {
+    prop_oneof![
+        4 => (-1e4f32..1e4, -1e4f32..1e4).prop_map(|(start, end)| Range::new(start, end)),
+        1 => (-1e4f32..1e4).prop_map(|bound| Range::new(bound, bound)),
+    ]
+}
+
+fn contains(range: Range, value: f32) -> bool {
+    value >= range.start.min(range.end) && value <= range.start.max(range.end)
+}
+
+proptest! {
+    #[test]
+    fn mapped_values_stay_within_the_target(
+        value in any_value(),
+        from in any_range(),
+        to in any_range(),
+        curve in 0..CURVES.len(),
+    ) {
+        let mapped = from.map(value, CURVES[curve], to);
+        prop_assert!(contains(to, mapped), "{value} from {from:?} to {to:?} gave {mapped}");
+    }
+
+    #[test]
+    fn clamped_values_stay_within_the_range(value in any_value(), range in any_range()) {
+        prop_assert!(contains(range, range.clamp(value)));
+    }
+
+    #[test]
+    fn positions_and_values_round_trip(start in -1e3f32..1e3, width in 1.0f32..1e4, inverted: bool, position in -2.0f32..2.0) {
+        let range = if inverted { Range::new(start + width, start) } else { Range::new(start, start + width) };
+        prop_assert!((range.position(range.value_at(position)) - position).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ranges_go_from_start_to_end(start in -1e4f32..1e4, end in -1e4f32..1e4) {
+        prop_assume!((end - start).abs() > 1e-2);
+        let range = Range::new(start, end);
+        prop_assert!(range.position(start).abs() < 1e-4);
+        prop_assert!((range.position(end) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn zero_width_ranges_are_thresholds(bound in -1e4f32..1e4, value in any_value()) {
+        let position = Range::new(bound, bound).position(value);
+        prop_assert_eq!(position, if value > bound { 1.0 } else { 0.0 });
+    }
+
+    #[test]
+    fn curves_keep_the_order_of_values(a in any_value(), b in any_value(), curve in 0..CURVES.len()) {
+        prop_assume!(!a.is_nan() && !b.is_nan());
+        let (low, high) = (a.min(b), a.max(b));
+        let curve = CURVES[curve];
+        prop_assert!(curve.apply(low) <= curve.apply(high));
+    }
+
+    #[test]
+    fn bipolar_values_are_valid_cc_values(input in any_value(), range in 1e-3f32..1e4) {
+        prop_assert!(contains(Range::UNIT, bipolar_cc_value(input, range)));
+    }
+}
+
+#[test]
+fn curves_keep_their_ends() {
+    for curve in CURVES {
+        assert_eq!(curve.apply(0.0), 0.0, "{curve:?}");
+        assert_eq!(curve.apply(1.0), 1.0, "{curve:?}");
+        assert_eq!(curve.apply(f32::NAN), 0.0, "{curve:?}");
+    }
+}
+
+#[test]
+fn bipolar_values_are_centered_at_64() {
+    assert_eq!(bipolar_cc_value(0.0, 50.0), 64.0 / 127.0);
+    assert_eq!(bipolar_cc_value(-50.0, 50.0), 1.0 / 127.0);
+    assert_eq!(bipolar_cc_value(50.0, 50.0), 1.0);
+}
@@ -0,0 +1,169 @@
+// Everything that runs on the audio thread must be allocation-free. nih_plug's
+// `assert_process_allocs` feature checks this for `process()` in debug builds of the plugin, but
+// only while a host is running it, so the checks here make sure regressions are caught by
+// `cargo test` as well.
+
+use std::f32::consts::PI;
+
+use aeolus::analysis::{AnalysisQuality, AnalysisSpeed, AnalyzerBank, PitchMethod, TrackingRange};
+use aeolus::drums::DrumNotes;
+use aeolus::dynamics::DynamicsConfig;
+use aeolus::engine::{BendReturn, Engine, EngineSettings};
+use aeolus::events::AeolusEvent;
+use aeolus::gate::GateMode;
+use aeolus::guitar::{BendReset, GuitarConfig};
+use aeolus::key::AutoKey;
+use aeolus::mpe::{MpeConfig, MpeZone};
+use aeolus::pulse::BeatNotes;
+use aeolus::routing::CcLane;
+use aeolus::trill::TrillMode;
+use aeolus::velocity::VelocitySource;
+use assert_no_alloc::{assert_no_alloc, AllocDisabler};
+
+#[global_allocator]
+static ALLOCATOR: AllocDisabler = AllocDisabler;
+
+const SAMPLE_RATE: f32 = 48000.0;
+/// Hosts usually send blocks of about this size
+const BLOCK_SIZE:  usize = 512;
+
+fn check_analysis(quality: AnalysisQuality) {
+    let mut analyzers = AnalyzerBank::new(quality);
+    analyzers.set_host_rate(SAMPLE_RATE);
+    analyzers.set_refinement(true);
+
+    let ranges = [
+        TrackingRange::Normal,
+        TrackingRange::Low,
+        TrackingRange::High,
+        TrackingRange::Adaptive,
+    ];
+    let speeds = [AnalysisSpeed::Fast, AnalysisSpeed::Balanced, AnalysisSpeed::Accurate];
+    assert_no_alloc(|| {
+        for speed in speeds {
+            for range in ranges {
+                for index in 0..SAMPLE_RATE as usize {
+                    let sample = (2.0 * PI * 220.0 * index as f32 / SAMPLE_RATE).sin();
+                    analyzers.push(sample, range, speed);
+                }
+            }
+        }
+    });
+}
+
+#[test]
+fn realtime_analysis_does_not_allocate() {
+    check_analysis(AnalysisQuality::Realtime);
+}
+
+#[test]
+fn offline_analysis_does_not_allocate() {
+    check_analysis(AnalysisQuality::Offline);
+}
+
+/// Every output and every analysis lane switched on, with MPE. `guitar_settings()` and
+/// `drum_settings()` cover what MPE and the notes rule out.
+fn mpe_settings(pitch_method: PitchMethod) -> EngineSettings {
+    let lane = |number| CcLane { enabled: true, number, ..CcLane::default() };
+    EngineSettings {
+        pitch_method,
+        subharmonic_suppression: true,
+        bend_return: BendReturn::Ramp,
+        auto_key: Some(AutoKey { confidence: 0.5, min_interval: 1.0 }),
+        correction: 0.5,
+        vibrato_split: Some(3.0),
+        scale_gate: Some(50.0),
+        hold_time: 20.0,
+        cents_lane: lane(2),
+        confidence_lane: lane(3),
+        note_filter: Some((21, 108)),
+        velocity_source: VelocitySource::SpectralFlux,
+        release_velocity: true,
+        mpe: Some(MpeConfig { zone: MpeZone::Lower, member_channels: 15, bend_range: 48 }),
+        mpe_brightness: true,
+        poly_pressure: true,
+        dynamics: Some(DynamicsConfig {
+            medium_threshold: -30.0,
+            loud_threshold:   -12.0,
+            channels:         Some([1, 2, 3]),
+            velocity_offsets: [-0.2, 0.0, 0.2],
+            keyswitches:      Some(12),
+        }),
+        accuracy_lane: lane(4),
+        high_resolution: true,
+        portamento: true,
+        trill_mode: TrillMode::Collapse,
+        register_lane: lane(9),
+        register_keyswitches: Some(36),
+        f1_lane: lane(14),
+        f2_lane: lane(15),
+        denoise: true,
+        gate_mode: GateMode::Adaptive,
+        clock_output: true,
+        beat_notes: Some(BeatNotes { channel: 9, beat: 37, downbeat: 36, beats_per_bar: 4 }),
+        ..EngineSettings::default()
+    }
+}
+
+fn guitar_settings(pitch_method: PitchMethod) -> EngineSettings {
+    EngineSettings {
+        mpe: None,
+        guitar: Some(GuitarConfig { bend_range: 12, reset: BendReset::Glide }),
+        ..mpe_settings(pitch_method)
+    }
+}
+
+fn drum_settings(pitch_method: PitchMethod) -> EngineSettings {
+    EngineSettings {
+        drums: Some(DrumNotes { channel: 9, kick: 36, snare: 38, hat: 42 }),
+        ..mpe_settings(pitch_method)
+    }
+}
+
+/// Runs a few seconds of plucked notes through an engine, block by block as a host would, the
+/// events going to a sink that was allocated beforehand
+fn check_engine(quality: AnalysisQuality, settings: EngineSettings) {
+    let mut engine = Engine::new(SAMPLE_RATE, quality);
+    engine.set_settings(settings);
+    engine.set_target(Some(57));
+    let mut events: Vec<AeolusEvent> = Vec::with_capacity(4096);
+
+    // Decaying harmonic tones with a little vibrato, each followed by a short pause
+    let notes = [220.0, 330.0, 220.0, 440.0];
+    let note_samples = SAMPLE_RATE as usize / 2;
+    let samples: Vec<f32> = (0..4 * note_samples)
+        .map(|index| {
+            let (note, position) = (index / note_samples, index % note_samples);
+            let time = position as f32 / SAMPLE_RATE;
+            if position > note_samples * 3 / 4 {
+                return 0.0;
+            }
+            let frequency = notes[note] * (1.0 + 0.003 * (2.0 * PI * 5.0 * time).sin());
+            let envelope = 0.5 * (-4.0 * time).exp();
+            let tone: f32 = (1..=4)
+                .map(|harmonic| (2.0 * PI * frequency * harmonic as f32 * time).sin() / harmonic as f32)
+                .sum();
+            envelope * tone
+        })
+        .collect();
+
+    assert_no_alloc(|| {
+        for block in samples.chunks(BLOCK_SIZE) {
+            events.clear();
+            engine.send_configuration(&mut events, 0);
+            for (index, &sample) in block.iter().enumerate() {
+                engine.process_sample(&mut events, index as u32, sample);
+            }
+        }
+        engine.panic(&mut events, 0);
+    });
+}
+
+#[test]
+fn engine_does_not_allocate() {
+    for method in [PitchMethod::Yin, PitchMethod::Hps, PitchMethod::Swipe, PitchMethod::Cepstrum] {
+        for settings in [mpe_settings(method), guitar_settings(method), drum_settings(method)] {
+            check_engine(AnalysisQuality::Realtime, settings);
+        }
+    }
+}